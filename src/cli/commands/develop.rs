@@ -9,10 +9,12 @@ pub fn develop(context: &Context) -> Result<(), Error> {
     let Context {
         paths, venv_runner, ..
     } = context;
-    commands::expect_venv(&context)?;
+    commands::expect_venv(context)?;
     print_info_2("Running setup_py.py develop");
     if !&paths.setup_py.exists() {
-        return Err(Error::MissingSetupPy {});
+        return Err(Error::MissingSetupPy {
+            project_path: paths.project.to_path_buf(),
+        });
     }
 
     venv_runner.run(&["python", "setup.py", "develop", "--no-deps"])
@@ -0,0 +1,125 @@
+use colored::*;
+
+use crate::cli::commands;
+use crate::error::*;
+use crate::lock;
+use crate::operations;
+use crate::python_info;
+use crate::ui::*;
+use crate::Context;
+
+/// Check the templates embedded in dmenv, see `operations::selfcheck()`.
+// Note: this does not need a project, so the Python interpreter is looked up
+// without a Context
+pub fn selfcheck(python: &Option<String>, use_cache: bool) -> Result<(), Error> {
+    let python_info = python_info::PythonInfo::new(python, use_cache)?;
+    print_info_1(&format!(
+        "Checking templates with {}",
+        python_info.binary.display()
+    ));
+    operations::selfcheck(&python_info.binary)?;
+    print_info_1("All templates are valid");
+    Ok(())
+}
+
+/// Run a few checks to diagnose common problems, like using the wrong
+/// Python interpreter or a missing virtualenv.
+/// Returns an error if any of the checks failed.
+// Note: everything is resolved the same way as in the other commands
+// (PythonInfo, PathsResolver, VenvRunner), so that what is reported
+// here matches what dmenv actually uses
+pub fn doctor(context: &Context) -> Result<(), Error> {
+    print_info_1("Checking dmenv setup");
+    let checks = [
+        ("python interpreter", check_python(context)),
+        ("python version", check_python_version(context)),
+        ("virtualenv", check_venv(context)),
+        ("lock file", check_lock(context)),
+        ("setup.py", check_setup_py(context)),
+    ];
+    let mut failures = 0;
+    for (name, result) in checks.iter() {
+        match result {
+            Ok(details) => println!("{} {}: {}", "[ok]".green(), name, details),
+            Err(error) => {
+                failures += 1;
+                println!("{} {}: {}", "[failed]".red(), name, error)
+            }
+        }
+    }
+    if failures != 0 {
+        return Err(new_error(format!("{} check(s) failed", failures)));
+    }
+    Ok(())
+}
+
+// Note: the interpreter already ran the `info.py` script when the context
+// was created, so we know it works
+fn check_python(context: &Context) -> Result<String, Error> {
+    let Context { python_info, .. } = context;
+    Ok(format!(
+        "{} (python {}, on {})",
+        python_info.binary.display(),
+        python_info.version,
+        python_info.platform
+    ))
+}
+
+fn check_python_version(context: &Context) -> Result<String, Error> {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    let requested = python_info::read_python_version_file(&paths.project)?;
+    let requested = match requested {
+        None => return Ok("no .python-version file".to_string()),
+        Some(v) => v,
+    };
+    if !python_info::version_matches(&python_info.version, &requested) {
+        return Err(new_error(format!(
+            ".python-version requires {}, but the interpreter is {}",
+            requested, python_info.version
+        )));
+    }
+    Ok(format!("{} matches .python-version", python_info.version))
+}
+
+fn check_venv(context: &Context) -> Result<String, Error> {
+    let Context {
+        paths, venv_runner, ..
+    } = context;
+    commands::expect_venv(context)?;
+    venv_runner.resolve_binary("python")?;
+    Ok(paths.venv.display().to_string())
+}
+
+fn check_lock(context: &Context) -> Result<String, Error> {
+    let Context { paths, .. } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    let lock_contents =
+        std::fs::read_to_string(&paths.lock).map_err(|e| new_read_error(e, &paths.lock))?;
+    let deps = lock::parse(&lock_contents)?;
+    Ok(format!(
+        "{} ({} dependencies)",
+        paths.lock.display(),
+        deps.len()
+    ))
+}
+
+fn check_setup_py(context: &Context) -> Result<String, Error> {
+    let Context { paths, .. } = context;
+    if !paths.setup_py.exists() {
+        return Err(Error::MissingSetupPy {
+            project_path: paths.project.to_path_buf(),
+        });
+    }
+    let setup_cfg = paths.project.join("setup.cfg");
+    if setup_cfg.exists() {
+        Ok("found, along with setup.cfg".to_string())
+    } else {
+        Ok("found".to_string())
+    }
+}
@@ -1,14 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::error::*;
 use crate::operations;
 
 pub fn init(
     project_path: Option<String>,
-    name: &str,
-    version: &str,
-    author: &Option<String>,
-    setup_cfg: bool,
+    mut init_options: operations::InitOptions,
+    from_requirements: Option<&Path>,
 ) -> Result<(), Error> {
     let init_path = if let Some(p) = project_path {
         PathBuf::from(p)
@@ -16,12 +14,12 @@ pub fn init(
         std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?
     };
 
-    let mut init_options = operations::InitOptions::new(name.to_string(), version.to_string());
-    if !setup_cfg {
-        init_options.no_setup_cfg();
-    };
-    if let Some(author) = author {
-        init_options.author(&author);
+    // Note: the path is relative to the working directory, like
+    // other paths given on the command line
+    if let Some(requirements_path) = from_requirements {
+        let contents = std::fs::read_to_string(requirements_path)
+            .map_err(|e| new_read_error(e, requirements_path))?;
+        init_options.requirements(operations::parse_requirements(&contents));
     }
     operations::init(&init_path, &init_options)
 }
@@ -1,53 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::syntax::Command;
 use crate::commands;
 use crate::error::*;
+use crate::lock;
 use crate::ui::*;
-use crate::Context;
+use crate::Metadata;
 use crate::PostInstallAction;
+use crate::{get_context, Context};
 
 pub fn install(context: &Context, post_install_action: PostInstallAction) -> Result<(), Error> {
-    let Context {
-        settings, paths, ..
-    } = context;
+    let Context { settings, .. } = context;
     if settings.production {
         print_info_1("Preparing project for production")
     } else {
         print_info_1("Preparing project for development")
     };
-    let lock_path = &paths.lock;
-    if !lock_path.exists() {
-        return Err(Error::MissingLock {
-            expected_path: lock_path.to_path_buf(),
-        });
-    }
+    let requirements_path = get_requirements_path(context)?;
 
+    // Note: there's no metadata to check in a custom requirements file
+    if settings.install_requirement.is_none() {
+        check_lock_platform(context)?;
+    }
     commands::ensure_venv(context)?;
-    install_from_lock(context)?;
+    install_from_lock(context, &requirements_path)?;
+    run_post_install_hooks(context)?;
 
     match post_install_action {
-        PostInstallAction::RunSetupPyDevelop => commands::develop(context)?,
-        PostInstallAction::None => (),
+        PostInstallAction::RunSetupPyDevelop => {
+            print_info_2("Installing the project itself in development mode");
+            commands::develop(context)?
+        }
+        PostInstallAction::None => print_info_2("Skipping installation of the project itself"),
+    }
+    Ok(())
+}
+
+/// Clean the virtualenv, then install everything from scratch.
+// Note: useful when the virtualenv is broken, for instance after
+// an interrupted install
+pub fn force_install(
+    cmd: &Command,
+    context: Context,
+    post_install_action: PostInstallAction,
+) -> Result<(), Error> {
+    // Don't clean the virtualenv if we know the install is going to fail anyway
+    get_requirements_path(&context)?;
+    print_info_1("Forcing re-installation of the virtualenv");
+    let settings = context.settings.clone();
+    commands::clean_venv(context)?;
+    // Re-create a context, for the same reasons as in `tidy()`
+    let context = get_context(cmd, settings)?;
+    install(&context, post_install_action)
+}
+
+/// Return the path of the requirements file to install: the lock, unless
+/// `dmenv install --requirement` was used.
+/// Returns an error if the file does not exist
+// Note: the `--requirement` path is relative to the working directory
+fn get_requirements_path(context: &Context) -> Result<PathBuf, Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let custom_path = match &settings.install_requirement {
+        None => {
+            if !paths.lock.exists() {
+                return Err(Error::MissingLock {
+                    expected_path: paths.lock.to_path_buf(),
+                });
+            }
+            return Ok(paths.lock.to_path_buf());
+        }
+        Some(path) => path,
+    };
+    let cwd = std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
+    let requirements_path = cwd.join(custom_path);
+    if !requirements_path.exists() {
+        return Err(new_error(format!(
+            "requirements file {} does not exist",
+            requirements_path.display()
+        )));
+    }
+    Ok(requirements_path)
+}
+
+/// Make sure the lock was generated on the same platform, since
+/// the lock may not contain the dependencies required by this one.
+/// Only print a warning, unless `settings.strict_platform` is true.
+/// In this case, a different Python implementation is reported too
+// Note: locks without a header (written by hand, for instance) are not checked
+fn check_lock_platform(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        settings,
+        ..
+    } = context;
+    let lock_contents =
+        std::fs::read_to_string(&paths.lock).map_err(|e| new_read_error(e, &paths.lock))?;
+    let metadata = match lock::parse_metadata(&lock_contents) {
+        None => return Ok(()),
+        Some(m) => m,
+    };
+    if settings.strict_platform {
+        check_lock_implementation(context, &metadata);
+    }
+    if metadata.python_platform == python_info.platform {
+        return Ok(());
+    }
+    let message = format!(
+        "{} was generated on {}, but the current platform is {}",
+        paths.lock.display(),
+        metadata.python_platform,
+        python_info.platform
+    );
+    if settings.strict_platform {
+        return Err(new_error(message));
+    }
+    print_warning(&message);
+    Ok(())
+}
+
+// Note: locks generated by older versions of dmenv do not record the implementation
+fn check_lock_implementation(context: &Context, metadata: &Metadata) {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    let lock_implementation = match &metadata.python_implementation {
+        None => return,
+        Some(x) => x,
+    };
+    if lock_implementation == &python_info.implementation {
+        return;
+    }
+    print_warning(&format!(
+        "{} was generated with {}, but the current Python implementation is {}",
+        paths.lock.display(),
+        lock_implementation,
+        python_info.implementation
+    ));
+}
+
+/// Run the commands from `settings.post_install` in the virtualenv,
+/// stopping at the first one that fails
+// Note: commands are split on whitespace, like DMENV_PIP_EXTRA_ARGS
+fn run_post_install_hooks(context: &Context) -> Result<(), Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    for hook in &settings.post_install {
+        let cmd: Vec<_> = hook.split_whitespace().collect();
+        if cmd.is_empty() {
+            continue;
+        }
+        print_info_2(&format!("Running post-install hook: {}", hook));
+        venv_runner.run(&cmd)?;
     }
     Ok(())
 }
 
-fn install_from_lock(context: &Context) -> Result<(), Error> {
+fn install_from_lock(context: &Context, lock_path: &Path) -> Result<(), Error> {
     let Context {
         paths, venv_runner, ..
     } = context;
-    let lock_path = &paths.lock;
     print_info_2(&format!(
         "Installing dependencies from {}",
         lock_path.display()
     ));
     // Since we'll be running the command using self.paths.project
     // as working directory, we must use the *relative* lock file
-    // name when calling `pip install`.
-    let lock_name = paths
-        .lock
-        .file_name()
-        .unwrap_or_else(|| panic!("self.path.lock has no filename component"));
-
-    let as_str = lock_name.to_string_lossy();
-    let cmd = &["python", "-m", "pip", "install", "--requirement", &as_str];
-    venv_runner.run(cmd)
+    // path when calling `pip install` - unless the lock is shared
+    // with other projects and lives outside the project path.
+    let relative_lock_path = lock_path.strip_prefix(&paths.project).unwrap_or(lock_path);
+
+    let mut cmd = commands::pip_install_cmd(context)?;
+    cmd.push("--requirement".to_string());
+    cmd.push(relative_lock_path.to_string_lossy().to_string());
+    venv_runner.run(&cmd)
 }
@@ -1,4 +1,7 @@
+use std::path::Path;
+
 use crate::cli::commands;
+use crate::dependencies::{installed_packages, normalize_name, FrozenDependency};
 use crate::error::*;
 use crate::operations;
 use crate::ui::*;
@@ -17,21 +20,65 @@ use crate::UpdateLockOptions;
 //    * If that fails, we know if the virtualenv is broken
 //    * Also, we know sure that `pip` can handle all the options
 //      (such as `--local`, `--exclude-editable`) we use in the other functions
-// * The path of the lock file is computed by PathsResolver.
+// * The path of the lock file is computed by PathsResolver, unless
+//   `--output` is used.
 //     See PathsResolver.paths() for details
+// * When `--hashes` is used, the hash of each frozen dependency
+//   is computed and written in the lock too
+// * When `--upgrade` is used, the other dependencies are constrained
+//   by the existing lock, so only the given packages change
 pub fn update_lock(context: &Context, update_options: UpdateLockOptions) -> Result<(), Error> {
     print_info_1("Updating lock");
+    let Context {
+        paths, settings, ..
+    } = context;
+    let frozen_deps = frozen_dependencies(context, &update_options)?;
+    let metadata = commands::metadata(context);
+    let lock_path = match &update_options.output {
+        Some(output) => output.clone(),
+        None => paths.lock.clone(),
+    };
+    if settings.dry_run {
+        print_info_2(&format!("Would write {}", lock_path.display()));
+        return Ok(());
+    }
+    operations::lock::update(
+        &lock_path,
+        frozen_deps,
+        update_options,
+        &metadata,
+        settings.dry_run,
+    )
+}
+
+/// Install the project and its dependencies in the virtualenv, and
+/// return the dependencies `dmenv lock` would write, without writing the lock.
+/// See `update_lock()` for details
+pub fn frozen_dependencies(
+    context: &Context,
+    update_options: &UpdateLockOptions,
+) -> Result<Vec<FrozenDependency>, Error> {
     let Context { paths, .. } = context;
     if !&paths.setup_py.exists() {
-        return Err(Error::MissingSetupPy {});
+        return Err(Error::MissingSetupPy {
+            project_path: paths.project.to_path_buf(),
+        });
+    }
+    commands::ensure_venv(context)?;
+    if context.settings.upgrade_pip {
+        commands::upgrade_pip(context)?;
     }
-    commands::ensure_venv(&context)?;
-    commands::upgrade_pip(&context)?;
-    commands::install_editable(&context)?;
-    let metadata = commands::metadata(&context);
-    let frozen_deps = commands::get_frozen_deps(&context)?;
-    let lock_path = &paths.lock;
-    operations::lock::update(lock_path, frozen_deps, update_options, &metadata)
+    commands::install_build_deps(context)?;
+    if !update_options.upgrade.is_empty() && paths.lock.exists() {
+        commands::install_editable_with_upgrades(context, &update_options.upgrade)?;
+    } else {
+        commands::install_editable(context)?;
+    }
+    let mut frozen_deps = commands::get_frozen_deps(context)?;
+    if update_options.hashes {
+        commands::add_hashes(context, &mut frozen_deps)?;
+    }
+    Ok(frozen_deps)
 }
 
 /// Bump a dependency in the lock file
@@ -42,9 +89,189 @@ pub fn bump_in_lock(
     bump_type: BumpType,
 ) -> Result<(), Error> {
     print_info_1(&format!("Bumping {} to {} ...", name, version));
-    let metadata = commands::metadata(&context);
+    let metadata = commands::metadata(context);
+    let Context {
+        paths, settings, ..
+    } = context;
+    operations::lock::bump(
+        &paths.lock,
+        name,
+        version,
+        bump_type,
+        &metadata,
+        settings.dry_run,
+    )
+}
+
+/// Check that the virtualenv matches the lock.
+// Note: this is read-only: neither the lock nor the virtualenv
+// are modified
+pub fn check(context: &Context) -> Result<(), Error> {
+    print_info_1("Checking virtualenv");
+    let Context { paths, .. } = context;
+    commands::expect_venv(context)?;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    let frozen_deps = commands::get_frozen_deps(context)?;
+    operations::lock::check(&paths.lock, &frozen_deps)
+}
+
+/// Check that no package appears more than once in the lock file
+pub fn check_duplicates(context: &Context) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    operations::lock::check_duplicates(&paths.lock)
+}
+
+/// Check that the installed packages match the hashes recorded in the lock.
+// Notes:
+// * pip only records the hash of the archive a package was installed from
+//   when it comes from an archive URL (see `dependencies::installed_packages()`).
+//   For the other packages, the matching distributions are downloaded again
+//   and hashed, like when running `dmenv lock --hashes`, which only checks
+//   the index against the lock
+// * The installed versions are checked first, since the hashes are
+//   meaningless if they don't match the lock. They come from `pip inspect`
+//   too, since `pip freeze` does not show the versions of the packages
+//   installed from an URL
+pub fn verify_hashes(context: &Context) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    commands::expect_venv(context)?;
+    let inspect_output = commands::run_pip_inspect(context)?;
+    let installed_deps = installed_packages(&inspect_output)?;
+    operations::lock::check(&paths.lock, &installed_deps)?;
+
+    let locked_deps = operations::lock::read_frozen(&paths.lock)?;
+    let (locked_deps, without_hashes): (Vec<_>, Vec<_>) =
+        locked_deps.into_iter().partition(|x| !x.hashes.is_empty());
+    for dep in &without_hashes {
+        print_warning(&format!("{}: no hashes in lock, skipping", dep.name));
+    }
+    let mut computed_deps: Vec<_> = installed_deps
+        .into_iter()
+        .filter(|x| !x.hashes.is_empty())
+        .collect();
+    let is_installed_archive_known = |name: &str| {
+        computed_deps
+            .iter()
+            .any(|x| normalize_name(&x.name) == normalize_name(name))
+    };
+    let mut from_index: Vec<_> = locked_deps
+        .iter()
+        .filter(|x| !is_installed_archive_known(&x.name))
+        .map(|x| FrozenDependency {
+            name: x.name.to_string(),
+            version: x.version.to_string(),
+            hashes: vec![],
+        })
+        .collect();
+    for dep in &from_index {
+        print_warning(&format!(
+            "{}: the archive it was installed from is unknown, checking the one from the index instead",
+            dep.name
+        ));
+    }
+    if !from_index.is_empty() {
+        commands::add_hashes(context, &mut from_index)?;
+    }
+    computed_deps.extend(from_index);
+    print_info_2("Comparing hashes");
+    operations::lock::verify_hashes(&locked_deps, &computed_deps)
+}
+
+/// Sort the lock file
+pub fn sort_lock(context: &Context) -> Result<(), Error> {
+    let metadata = commands::metadata(context);
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    operations::lock::sort(&paths.lock, &metadata, settings.dry_run)
+}
+
+/// Record the current Python interpreter in the header of the lock
+pub fn pin_python(context: &Context) -> Result<(), Error> {
+    let metadata = commands::metadata(context);
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    operations::lock::pin_python(&paths.lock, &metadata, settings.dry_run)
+}
+
+/// Add a pinned package to the lock file, without touching the virtualenv
+pub fn add_to_lock(context: &Context, spec: &str) -> Result<(), Error> {
+    let metadata = commands::metadata(context);
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    operations::lock::add(&paths.lock, spec, &metadata, settings.dry_run)
+}
+
+/// Remove some packages from the lock file
+pub fn prune_lock(context: &Context, names: &[String]) -> Result<(), Error> {
+    let metadata = commands::metadata(context);
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    operations::lock::prune(&paths.lock, names, &metadata, settings.dry_run)
+}
+
+/// Compare the lock with an other one
+pub fn diff_lock(context: &Context, other: &Path, base: Option<&Path>) -> Result<(), Error> {
     let Context { paths, .. } = context;
-    operations::lock::bump(&paths.lock, name, version, bump_type, &metadata)
+    let base = base.unwrap_or(&paths.lock);
+    operations::lock::diff(base, other)
+}
+
+/// Merge an other lock into the lock of the project
+pub fn merge_lock(context: &Context, other: &Path, prefer_newer: bool) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    if !paths.lock.exists() {
+        return Err(Error::MissingLock {
+            expected_path: paths.lock.to_path_buf(),
+        });
+    }
+    let metadata = metadata(context);
+    operations::lock::merge(
+        &paths.lock,
+        other,
+        prefer_newer,
+        &metadata,
+        settings.dry_run,
+    )
 }
 
 pub fn metadata(context: &Context) -> Metadata {
@@ -56,5 +283,6 @@ pub fn metadata(context: &Context) -> Metadata {
         dmenv_version: dmenv_version.to_string(),
         python_platform: python_platform.to_string(),
         python_version: python_version.to_string(),
+        python_implementation: Some(python_info.implementation.to_string()),
     }
 }
@@ -1,4 +1,5 @@
 mod develop;
+mod doctor;
 mod init;
 mod install;
 mod lock;
@@ -10,12 +11,27 @@ mod tidy;
 mod venv;
 
 pub use develop::develop;
+pub use doctor::{doctor, selfcheck};
 pub use init::init;
-pub use install::install;
-pub use lock::{bump_in_lock, metadata, update_lock};
-pub use pip::{get_frozen_deps, install_editable, install_editable_with_constraint, upgrade_pip};
-pub use run::{run, run_and_die};
-pub use scripts::process_scripts;
-pub use show::{show_deps, show_outdated, show_venv_bin_path, show_venv_path};
-pub use tidy::tidy;
-pub use venv::{clean_venv, create_venv, ensure_venv, expect_venv};
+pub use install::{force_install, install};
+pub use lock::{
+    add_to_lock, bump_in_lock, check, check_duplicates, diff_lock, frozen_dependencies, merge_lock,
+    metadata, pin_python, prune_lock, sort_lock, update_lock, verify_hashes,
+};
+pub use pip::{
+    add_hashes, get_frozen_deps, install_build_deps, install_editable,
+    install_editable_with_constraint, install_editable_with_upgrades, pip_install_cmd,
+    run_pip_inspect, upgrade_pip,
+};
+pub use run::{echo_run, list_binaries, run, run_all, run_and_die, shell_quote};
+pub use scripts::{process_scripts, process_shebangs};
+pub use show::{
+    freeze_metadata, show_bin, show_config, show_deps, show_lock, show_lock_path, show_lock_stats,
+    show_outdated, show_python, show_venv_activate, show_venv_bin_path, show_venv_path,
+    show_venv_size,
+};
+pub use tidy::{tidy, upgrade_all};
+pub use venv::{
+    clean_all_venvs, clean_venv, create_venv, ensure_venv, expect_venv, list_venvs, recreate_venv,
+    relocate_venv,
+};
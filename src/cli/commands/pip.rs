@@ -1,36 +1,65 @@
-use crate::dependencies::FrozenDependency;
+use crate::dependencies::{
+    build_only_packages, remove_excluded, remove_pkg_resources, FrozenDependency,
+};
 use crate::error::*;
+use crate::operations;
+use crate::paths::REQUIREMENTS_IN_FILENAME;
 use crate::ui::*;
 use crate::Context;
 
+/// Upgrade pip in the virtualenv, or install the version
+/// set in `settings.pip_version`, if any
 pub fn upgrade_pip(context: &Context) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
-    print_info_2("Upgrading pip");
-    let cmd = &["python", "-m", "pip", "install", "pip", "--upgrade"];
-    venv_runner.run(cmd).map_err(|_| Error::UpgradePipError {})
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    let pip_requirement = settings.pip_requirement();
+    match &settings.pip_version {
+        None => print_info_2("Upgrading pip"),
+        Some(version) => print_info_2(&format!("Installing pip {}", version)),
+    }
+    let mut cmd = pip_install_cmd(context)?;
+    cmd.push(pip_requirement);
+    cmd.push("--upgrade".to_string());
+    venv_runner.run(&cmd).map_err(|_| Error::UpgradePipError {})
 }
 
-pub fn install_editable(context: &Context) -> Result<(), Error> {
+/// Install the packages from `settings.build_deps`, which must be present
+/// before the project itself can be installed (like `wheel` or `cython`).
+/// Does nothing if there are none.
+// Note: they are filtered out of the lock, see `get_frozen_deps()`
+pub fn install_build_deps(context: &Context) -> Result<(), Error> {
     let Context {
         settings,
         venv_runner,
         ..
     } = context;
-    let mut message = "Installing deps from setup.py".to_string();
-    if settings.production {
-        message.push_str(" using 'prod' extra dependencies");
-    } else {
-        message.push_str(" using 'dev' extra dependencies");
+    if settings.build_deps.is_empty() {
+        return Ok(());
     }
-    print_info_2(&message);
-    let cmd = get_install_editable_cmd(&context);
+    print_info_2(&format!(
+        "Installing build dependencies: {}",
+        settings.build_deps.join(", ")
+    ));
+    let mut cmd = pip_install_cmd(context)?;
+    cmd.extend(settings.build_deps.iter().cloned());
     venv_runner.run(&cmd)
 }
 
+pub fn install_editable(context: &Context) -> Result<(), Error> {
+    let Context { settings, .. } = context;
+    print_info_2(&format!(
+        "Installing deps from setup.py using '{}' extra dependencies",
+        settings.extra()
+    ));
+    let cmd = get_install_editable_cmd(context)?;
+    run_install_editable(context, &cmd)
+}
+
 pub fn install_editable_with_constraint(context: &Context) -> Result<(), Error> {
-    let Context {
-        paths, venv_runner, ..
-    } = context;
+    let Context { paths, .. } = context;
     let lock_path = &paths.lock;
     let message = format!(
         "Installing deps from setup.py, constrained by {}",
@@ -38,41 +67,239 @@ pub fn install_editable_with_constraint(context: &Context) -> Result<(), Error>
     );
     print_info_2(&message);
     let lock_path_str = lock_path.to_string_lossy();
-    let mut cmd = get_install_editable_cmd(&context).to_vec();
-    cmd.extend(&["--constraint", &lock_path_str]);
-    venv_runner.run(&cmd)
+    let mut cmd = get_install_editable_cmd(context)?;
+    cmd.push("--constraint".to_string());
+    cmd.push(lock_path_str.to_string());
+    run_install_editable(context, &cmd)
+}
+
+/// Same as `install_editable_with_constraint`, but allow upgrading the given packages.
+// Note: the upgraded packages are removed from a copy of the lock, so that
+// pip does not see conflicting constraints
+pub fn install_editable_with_upgrades(context: &Context, packages: &[String]) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    print_info_2(&format!(
+        "Installing deps from setup.py, upgrading {}",
+        packages.join(", ")
+    ));
+    let tmp_dir = tempdir::TempDir::new("dmenv-upgrade")
+        .map_err(|e| new_error(format!("Could not create temporary directory: {}", e)))?;
+    let constraints_path = tmp_dir.path().join("constraints.txt");
+    let constraints = operations::lock::constraints_without(&paths.lock, packages)?;
+    std::fs::write(&constraints_path, constraints)
+        .map_err(|e| new_write_error(e, &constraints_path))?;
+
+    let mut cmd = get_install_editable_cmd(context)?;
+    cmd.push("--constraint".to_string());
+    cmd.push(constraints_path.to_string_lossy().to_string());
+    cmd.push("--upgrade".to_string());
+    cmd.push("--upgrade-strategy".to_string());
+    cmd.push("only-if-needed".to_string());
+    cmd.extend(packages.iter().cloned());
+    run_install_editable(context, &cmd)
+}
+
+/// Run the `pip install --editable` command built by `get_install_editable_cmd()`.
+// Note: when it fails, the end of pip's error output is repeated in the
+// error message, so that errors in setup.py or setup.cfg, for instance,
+// are not lost in the middle of pip's output
+fn run_install_editable(context: &Context, cmd: &[String]) -> Result<(), Error> {
+    let Context {
+        paths, venv_runner, ..
+    } = context;
+    let (success, stderr) = venv_runner.run_and_get_stderr(cmd)?;
+    if success {
+        return Ok(());
+    }
+    Err(Error::ProjectInstallFailed {
+        project_path: paths.project.to_path_buf(),
+        stderr_tail: tail(&stderr, STDERR_TAIL_LINES),
+    })
+}
+
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Return the last `count` lines of `text`
+fn tail(text: &str, count: usize) -> String {
+    let lines: Vec<_> = text.trim_end().lines().collect();
+    let start = lines.len().saturating_sub(count);
+    lines[start..].join("\n")
 }
 
-fn get_install_editable_cmd(context: &Context) -> [&str; 6] {
+// Notes:
+// * if `settings.use_requirements_in` is true and there is a `requirements.in`
+//   file in the project, it's installed too, so that the lock contains its
+//   dependencies (like with pip-tools)
+// * options go before the editable target, so that the command line
+//   printed reads like `pip install <options> --editable .[dev]`
+fn get_install_editable_cmd(context: &Context) -> Result<Vec<String>, Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let extra = settings.extra();
+    operations::extras::check(&paths.project, extra)?;
+    let editable = format!(".[{}]", extra);
+    let mut cmd = pip_install_cmd(context)?;
+    if settings.use_requirements_in && paths.requirements_in.exists() {
+        // Note: commands run in the project path, so the relative path works
+        cmd.push("--requirement".to_string());
+        cmd.push(REQUIREMENTS_IN_FILENAME.to_string());
+    }
+    cmd.push("--editable".to_string());
+    cmd.push(editable);
+    Ok(cmd)
+}
+
+/// Return the `pip install` command, followed by the options shared by all
+/// the commands that install packages: the index and find-links options,
+/// and `settings.pip_extra_args`
+pub fn pip_install_cmd(context: &Context) -> Result<Vec<String>, Error> {
     let Context { settings, .. } = context;
-    let extra = if settings.production {
-        ".[prod]"
-    } else {
-        ".[dev]"
+    let mut cmd = pip_lookup_cmd(context, "install")?;
+    cmd.extend(settings.pip_extra_args.iter().cloned());
+    Ok(cmd)
+}
+
+/// Start a `pip <subcommand>` command that looks up distributions
+/// the same way `pip install` does (index and find-links options)
+fn pip_lookup_cmd(context: &Context, subcommand: &str) -> Result<Vec<String>, Error> {
+    let Context { settings, .. } = context;
+    let mut cmd: Vec<String> = ["python", "-m", "pip", subcommand]
+        .iter()
+        .map(|x| (*x).to_string())
+        .collect();
+    cmd.extend(settings.pip_index_args());
+    cmd.extend(find_links_args(context)?);
+    Ok(cmd)
+}
+
+/// Arguments to pass to the pip commands that install or download dependencies,
+/// so that they are only looked up in the `find_links_dir` directory (if set).
+// Note: relative paths are relative to the project path. Paths from the
+// configuration files are already absolute, see `config::read_config()`
+fn find_links_args(context: &Context) -> Result<Vec<String>, Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let find_links_dir = match &settings.find_links_dir {
+        None => return Ok(vec![]),
+        Some(dir) => paths.project.join(dir),
     };
-    ["python", "-m", "pip", "install", "--editable", extra]
+    if !find_links_dir.is_dir() {
+        return Err(new_error(format!(
+            "find-links directory {} does not exist",
+            find_links_dir.display()
+        )));
+    }
+    Ok(vec![
+        "--find-links".to_string(),
+        find_links_dir.to_string_lossy().to_string(),
+        "--no-index".to_string(),
+    ])
 }
 
 /// Get the list of the *actual* deps in the virtualenv by calling `pip freeze`.
 pub fn get_frozen_deps(context: &Context) -> Result<Vec<FrozenDependency>, Error> {
-    let freeze_output = run_pip_freeze(&context)?;
+    let freeze_output = run_pip_freeze(context)?;
     // First, collect all the `pip freeze` lines into frozen dependencies
     let deps: Result<Vec<_>, _> = freeze_output
         .lines()
         .map(|x| FrozenDependency::from_string(x.into()))
         .collect();
-    let deps = deps?;
-    // Then filter out pkg-resources: this works around a Debian bug in pip:
-    // https://bugs.debian.org/cgi-bin/bugreport.cgi?bug=871790
-    let res: Vec<_> = deps
-        .into_iter()
-        .filter(|x| x.name != "pkg-resources")
-        .collect();
-    Ok(res)
+    // Then filter out pkg-resources, the packages excluded by the user,
+    // and the ones only needed to build the project
+    let Context { settings, .. } = context;
+    let deps = remove_pkg_resources(deps?);
+    let deps = remove_excluded(deps, &settings.exclude);
+    Ok(remove_excluded(deps, &get_build_only_packages(context)?))
 }
 
-fn run_pip_freeze(context: &Context) -> Result<String, Error> {
+/// Get the names of the build dependencies and of the packages they
+/// require, unless the project needs them too, see `dependencies::build_only_packages()`
+// Note: when running dry, nothing was installed, so only the names of
+// the build dependencies are returned
+fn get_build_only_packages(context: &Context) -> Result<Vec<String>, Error> {
+    let Context { settings, .. } = context;
+    let build_deps = settings.build_dep_names();
+    if build_deps.is_empty() || settings.dry_run {
+        return Ok(build_deps);
+    }
+    let output = run_pip_inspect(context)?;
+    build_only_packages(&output, &build_deps)
+}
+
+/// Return the JSON report of `pip inspect` about the packages of the virtualenv
+pub fn run_pip_inspect(context: &Context) -> Result<String, Error> {
     let Context { venv_runner, .. } = context;
+    venv_runner
+        .get_output(&["python", "-m", "pip", "inspect", "--local"])
+        .map_err(|e| {
+            new_error(format!(
+                "could not run `pip inspect`: {}\nNote: pip >= 22.2 is required",
+                e
+            ))
+        })
+}
+
+/// Set the `hashes` field of every frozen dependency.
+// Note: `pip freeze` does not know about hashes, so we download
+// each distribution in a temporary directory and run `pip hash` on it.
+// This means only the hashes of the distributions matching the current
+// platform are recorded.
+pub fn add_hashes(context: &Context, frozen_deps: &mut [FrozenDependency]) -> Result<(), Error> {
+    let Context { venv_runner, .. } = context;
+    print_info_2("Computing hashes");
+    let tmp_dir = tempdir::TempDir::new("dmenv-hashes")
+        .map_err(|e| new_error(format!("Could not create temporary directory: {}", e)))?;
+    for dep in frozen_deps.iter_mut() {
+        let dest_path = tmp_dir.path().join(&dep.name);
+        let dest_str = dest_path.to_string_lossy();
+        let spec = format!("{}=={}", dep.name, dep.version);
+        let mut cmd = pip_lookup_cmd(context, "download")?;
+        #[rustfmt::skip]
+        cmd.extend([
+            "--no-deps",
+            "--dest", &dest_str,
+            &spec,
+        ].iter().map(|x| (*x).to_string()));
+        venv_runner.get_output(&cmd)?;
+
+        let entries = std::fs::read_dir(&dest_path).map_err(|e| new_read_error(e, &dest_path))?;
+        let mut cmd: Vec<String> = ["python", "-m", "pip", "hash"]
+            .iter()
+            .map(|x| (*x).to_string())
+            .collect();
+        for entry in entries {
+            let entry = entry.map_err(|e| new_read_error(e, &dest_path))?;
+            cmd.push(entry.path().to_string_lossy().to_string());
+        }
+        let hash_output = venv_runner.get_output(&cmd)?;
+        dep.hashes = parse_pip_hash(&hash_output);
+        print_info_2(&format!("{}: {} hash(es)", dep.name, dep.hashes.len()));
+    }
+    Ok(())
+}
+
+// `pip hash` output looks like:
+//
+// /path/to/foo-0.42.whl:
+// --hash=sha256:32fde42...
+fn parse_pip_hash(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|x| x.trim().strip_prefix("--hash="))
+        .map(|x| x.to_string())
+        .collect()
+}
+
+// Note: the error output of `pip freeze` is only shown when it fails,
+// unless `--show-warnings` is used (with `--verbose`, it's always shown)
+fn run_pip_freeze(context: &Context) -> Result<String, Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
     #[rustfmt::skip]
         let cmd = &[
             "python", "-m", "pip", "freeze",
@@ -80,5 +307,24 @@ fn run_pip_freeze(context: &Context) -> Result<String, Error> {
             "--all",
             "--local",
         ];
-    venv_runner.get_output(cmd)
+    let (stdout, stderr) = venv_runner.get_output_and_stderr(cmd)?;
+    if settings.show_warnings && !is_verbose() {
+        for line in stderr.lines().filter(|x| !x.trim().is_empty()) {
+            print_warning(&format!("pip freeze: {}", line));
+        }
+    }
+    // Note: pip uses CRLF line endings on Windows
+    Ok(stdout.replace("\r\n", "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail("one\ntwo\nthree\n", 2), "two\nthree");
+        assert_eq!(tail("one\n", 5), "one");
+        assert_eq!(tail("", 5), "");
+    }
 }
@@ -1,23 +1,134 @@
 use crate::cli::commands;
 use crate::error::*;
+use crate::ui::*;
 use crate::Context;
+use crate::RunOptions;
 
 /// Run a program from the virtualenv, making sure it dies
 /// when we get killed and that the exit code is forwarded
-pub fn run_and_die<T: AsRef<str>>(context: &Context, cmd: &[T]) -> Result<(), Error> {
-    let Context { venv_runner, .. } = context;
-    commands::expect_venv(&context)?;
-    venv_runner.run_and_die(cmd)
+pub fn run_and_die<T: AsRef<str>>(
+    context: &Context,
+    cmd: &[T],
+    options: &RunOptions,
+) -> Result<(), Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    if !options.no_venv_check {
+        commands::expect_venv(context)?;
+    }
+    venv_runner.run_and_die(&settings.expand_alias(cmd), options)
 }
 
 /// On Windows:
 ///   - same as run
+///
 /// On Linux:
 ///   - same as run, but create a new process instead of using execv()
 // Note: mostly for tests. We want to *check* the return code of
 // `dmenv run` and so we need a child process
-pub fn run<T: AsRef<str>>(context: &Context, cmd: &[T]) -> Result<(), Error> {
+pub fn run<T: AsRef<str>>(context: &Context, cmd: &[T], options: &RunOptions) -> Result<(), Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    if !options.no_venv_check {
+        commands::expect_venv(context)?;
+    }
+    venv_runner.run_with_options(&settings.expand_alias(cmd), options)
+}
+
+/// List the binaries that can be used with `dmenv run`
+pub fn list_binaries(context: &Context) -> Result<(), Error> {
     let Context { venv_runner, .. } = context;
-    commands::expect_venv(&context)?;
-    venv_runner.run(cmd)
+    commands::expect_venv(context)?;
+    for name in venv_runner.list_binaries()? {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+/// Print the command `run()` would run, with the absolute path of the binary, on a
+/// single line, and nothing else, so that it can be evaluated by a shell.
+/// Environment variables from `options.env` are printed first, as `KEY=VALUE`
+// Note: fails if the binary does not exist, like `run()`
+pub fn echo_run<T: AsRef<str>>(
+    context: &Context,
+    cmd: &[T],
+    options: &RunOptions,
+) -> Result<(), Error> {
+    let Context {
+        settings,
+        venv_runner,
+        ..
+    } = context;
+    if !options.no_venv_check {
+        commands::expect_venv(context)?;
+    }
+    let resolved = venv_runner.resolve_command(&settings.expand_alias(cmd), options)?;
+    let env = options
+        .env
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, shell_quote(value)));
+    let words: Vec<_> = env.chain(resolved.iter().map(|x| shell_quote(x))).collect();
+    println!("{}", words.join(" "));
+    Ok(())
+}
+
+/// Quote `word` for a POSIX shell, unless it only contains safe characters
+pub fn shell_quote(word: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Run each command from the virtualenv, in order.
+///
+/// Stop at the first failure, unless `keep_going` is true. In that case,
+/// run every command and report the failed ones at the end.
+pub fn run_all(context: &Context, cmds: &[Vec<String>], keep_going: bool) -> Result<(), Error> {
+    let Context { venv_runner, .. } = context;
+    commands::expect_venv(context)?;
+    let mut failures = vec![];
+    for cmd in cmds {
+        let res = venv_runner.run(cmd);
+        match res {
+            Err(e) if keep_going => failures.push((cmd.join(" "), e)),
+            Err(e) => return Err(e),
+            Ok(()) => (),
+        }
+    }
+    if failures.is_empty() {
+        return Ok(());
+    }
+    for (cmd, error) in &failures {
+        print_error(&format!("{}: {}", cmd, error));
+    }
+    Err(new_error(format!(
+        "{} command(s) out of {} failed",
+        failures.len(),
+        cmds.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("/path/to/venv/bin/pytest"),
+            "/path/to/venv/bin/pytest"
+        );
+        assert_eq!(shell_quote("--maxfail=1"), "--maxfail=1");
+        assert_eq!(shell_quote("print('ok')"), r"'print('\''ok'\'')'");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote(""), "''");
+    }
 }
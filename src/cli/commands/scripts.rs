@@ -1,8 +1,20 @@
+use crate::cli::commands;
 use crate::error::*;
 use crate::operations;
+use crate::ui::*;
 use crate::Context;
 use crate::ProcessScriptsMode;
 
 pub fn process_scripts(context: &Context, mode: ProcessScriptsMode) -> Result<(), Error> {
     operations::scripts::process(&context.paths, mode)
 }
+
+/// Make sure the scripts in the virtualenv use its Python binary,
+/// for instance after the virtualenv has been moved
+pub fn process_shebangs(context: &Context, mode: ProcessScriptsMode) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    commands::expect_venv(context)?;
+    let count = operations::shebangs::fix(&paths.venv, mode)?;
+    print_info_1(&format!("{} script(s) updated", count));
+    Ok(())
+}
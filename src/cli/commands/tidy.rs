@@ -2,6 +2,7 @@ use crate::cli::commands;
 use crate::cli::syntax::Command;
 use crate::error::*;
 use crate::operations;
+use crate::ui::*;
 use crate::{get_context, Context};
 
 // Re-generate a clean lock:
@@ -12,15 +13,75 @@ use crate::{get_context, Context};
 //     in `self.install_editable_with_constraint()`
 //  - re-generate the lock by only keeping existing dependencies:
 //    see `operations::lock::tidy()`
-pub fn tidy(cmd: &Command, context: Context) -> Result<(), Error> {
+//
+// Note: this refuses to run from an activated virtualenv, because it
+// would get deleted (see #110), unless `allow_active_venv` is true. In
+// this case the virtualenv managed by dmenv is used instead (see
+// `PathsResolver`)
+pub fn tidy(cmd: &Command, context: Context, allow_active_venv: bool) -> Result<(), Error> {
+    check_active_venv("tidy", allow_active_venv)?;
+    let settings = context.settings.clone();
     commands::clean_venv(context)?;
     // Re-create a context since we've potenntially just
     // deleted the python we used to clean the previous virtualenv
-    let context = get_context(&cmd)?;
+    let context = get_context(cmd, settings)?;
     commands::create_venv(&context)?;
+    commands::install_build_deps(&context)?;
     commands::install_editable_with_constraint(&context)?;
     let metadata = commands::metadata(&context);
     let frozen_deps = commands::get_frozen_deps(&context)?;
-    let Context { paths, .. } = context;
-    operations::lock::tidy(&paths.lock, frozen_deps, &metadata)
+    let Context {
+        paths, settings, ..
+    } = context;
+    if settings.dry_run {
+        print_info_2(&format!("Would write {}", paths.lock.display()));
+        return Ok(());
+    }
+    operations::lock::tidy(&paths.lock, frozen_deps, &metadata, settings.dry_run)
+}
+
+// Re-generate the lock with the latest versions allowed by setup.py:
+//   - clean the virtualenv and re-create it from scratch, like `tidy()`
+//   - install the project *without* constraints
+//   - re-generate the lock from the frozen dependencies:
+//     see `operations::lock::upgrade_all()`
+pub fn upgrade_all(cmd: &Command, context: Context, allow_active_venv: bool) -> Result<(), Error> {
+    check_active_venv("lock:upgrade-all", allow_active_venv)?;
+    print_warning(&format!(
+        "Upgrading every dependency in {}: many versions may change",
+        context.paths.lock.display()
+    ));
+    let settings = context.settings.clone();
+    commands::clean_venv(context)?;
+    // Re-create a context, for the same reasons as in `tidy()`
+    let context = get_context(cmd, settings)?;
+    commands::create_venv(&context)?;
+    commands::install_build_deps(&context)?;
+    commands::install_editable(&context)?;
+    let metadata = commands::metadata(&context);
+    let frozen_deps = commands::get_frozen_deps(&context)?;
+    let Context {
+        paths, settings, ..
+    } = context;
+    if settings.dry_run {
+        print_info_2(&format!("Would write {}", paths.lock.display()));
+        return Ok(());
+    }
+    operations::lock::upgrade_all(&paths.lock, frozen_deps, &metadata, settings.dry_run)
+}
+
+/// Commands that re-create the virtualenv refuse to run from an activated one,
+/// since it would get deleted - unless `allow_active_venv` is true
+fn check_active_venv(cmd_name: &str, allow_active_venv: bool) -> Result<(), Error> {
+    if let Ok(active_venv) = std::env::var("VIRTUAL_ENV") {
+        if !allow_active_venv {
+            return Err(new_error(format!(
+                "refusing to run `dmenv {}` from an activated virtualenv ({})\n\
+                 Deactivate it first or use `dmenv {} --allow-active-venv`",
+                cmd_name, active_venv, cmd_name
+            )));
+        }
+        print_warning(&format!("Ignoring activated virtualenv: {}", active_venv));
+    }
+    Ok(())
 }
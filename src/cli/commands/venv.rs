@@ -1,7 +1,11 @@
+use std::path::Path;
+
+use crate::cli::commands;
+use crate::cli::syntax::Command;
 use crate::error::*;
 use crate::operations;
 use crate::ui::*;
-use crate::Context;
+use crate::{get_context, Context};
 
 pub fn ensure_venv(context: &Context) -> Result<(), Error> {
     let Context { paths, .. } = context;
@@ -10,6 +14,7 @@ pub fn ensure_venv(context: &Context) -> Result<(), Error> {
             "Using existing virtualenv: {}",
             paths.venv.display()
         ));
+        check_venv_python(context)?;
     } else {
         create_venv(context)?;
     }
@@ -21,6 +26,9 @@ pub fn ensure_venv(context: &Context) -> Result<(), Error> {
 // Notes:
 // * The path comes from PathsResolver.paths()
 // * Called by `ensure_venv()` *if* the path does not exist
+// * When the pip version is pinned, it's installed right away, so that
+//   the pip bundled with the Python interpreter is never used, unless
+//   `settings.upgrade_pip` is false
 pub fn create_venv(context: &Context) -> Result<(), Error> {
     let Context {
         paths,
@@ -28,15 +36,97 @@ pub fn create_venv(context: &Context) -> Result<(), Error> {
         settings,
         ..
     } = context;
-    operations::venv::create(&paths.venv, python_info, settings)
+    operations::venv::create(&paths.venv, python_info, settings)?;
+    if settings.pip_version.is_some() && settings.upgrade_pip {
+        commands::upgrade_pip(context)?;
+    }
+    Ok(())
 }
 
 /// Clean virtualenv. No-op if the virtualenv does not exist
 // Note: the Context is moved because if you call this function
 // from inside a virtualenv, the Python binary gets invalidated
 pub fn clean_venv(context: Context) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    if settings.dry_run {
+        print_info_1(&format!("Would clean {}", paths.venv.display()));
+        return Ok(());
+    }
+    operations::venv::clean(paths.venv, paths.venv_root.as_deref())
+}
+
+/// Clean every virtualenv of the project
+pub fn clean_all_venvs(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths, settings, ..
+    } = context;
+    let venv_root = paths.venv_root.as_ref().ok_or_else(|| {
+        new_error("virtualenvs are not stored in the project, nothing to clean".to_string())
+    })?;
+    if settings.dry_run {
+        print_info_1(&format!("Would clean {}", venv_root.display()));
+        return Ok(());
+    }
+    operations::venv::clean_all(venv_root)
+}
+
+/// Make a virtualenv that was moved usable again, without re-creating it.
+/// `venv_path` defaults to the path of the virtualenv used by dmenv
+// Note: relative paths are relative to the working directory
+pub fn relocate_venv(context: &Context, venv_path: Option<&Path>) -> Result<(), Error> {
+    let Context {
+        paths, python_info, ..
+    } = context;
+    let venv_path = match venv_path {
+        None => {
+            expect_venv(context)?;
+            paths.venv.to_path_buf()
+        }
+        Some(path) => {
+            let cwd =
+                std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
+            cwd.join(path)
+        }
+    };
+    operations::relocate::relocate(&venv_path, python_info)?;
+    print_info_1(&format!("Virtualenv relocated to {}", venv_path.display()));
+    Ok(())
+}
+
+/// Clean the virtualenv, then create an empty one.
+/// Nothing gets installed
+pub fn recreate_venv(cmd: &Command, context: Context) -> Result<(), Error> {
+    let settings = context.settings.clone();
+    clean_venv(context)?;
+    // Re-create a context, for the same reasons as in `tidy()`
+    let context = get_context(cmd, settings)?;
+    create_venv(&context)?;
     let Context { paths, .. } = context;
-    operations::venv::clean(paths.venv)
+    print_info_1(&format!(
+        "Virtualenv re-created in {}",
+        paths.venv.display()
+    ));
+    Ok(())
+}
+
+/// List the virtualenvs of the project.
+// Note: the current virtualenv is marked with a `*`, and virtualenvs
+// that were not created by dmenv are marked as orphans
+pub fn list_venvs(context: &Context) -> Result<(), Error> {
+    let Context { paths, .. } = context;
+    let venv_root = paths.venv_root.as_ref().ok_or_else(|| {
+        new_error("virtualenvs are not stored in the project, nothing to list".to_string())
+    })?;
+    let entries = operations::venv::list(venv_root)?;
+    for entry in entries {
+        let marker = if entry.path == paths.venv { "*" } else { " " };
+        let version = entry.python_version.as_deref().unwrap_or("unknown version");
+        let orphan = if entry.is_orphan() { " (orphan)" } else { "" };
+        println!("{} {} (python {}){}", marker, entry.name, version, orphan);
+    }
+    Ok(())
 }
 
 /// Make sure the virtualenv exists, or return an error
@@ -45,7 +135,31 @@ pub fn clean_venv(context: Context) -> Result<(), Error> {
 // virtualenv to exist, like `show_deps` or `run`:
 // this ensures that error messages printed when the
 // virtualenv does not exist are consistent.
+// When running dry, the virtualenv would have been created
+// by a previous step, so don't check anything.
 pub fn expect_venv(context: &Context) -> Result<(), Error> {
-    let Context { paths, .. } = context;
-    operations::venv::expect(&paths.venv)
+    let Context {
+        paths, settings, ..
+    } = context;
+    if settings.dry_run {
+        return Ok(());
+    }
+    operations::venv::expect(&paths.venv)?;
+    check_venv_python(context)
+}
+
+// Note: the virtualenv path contains the Python version, so this only
+// fails if an existing virtualenv is used (see `PathsResolver`) or if
+// the virtualenv was re-created by hand
+fn check_venv_python(context: &Context) -> Result<(), Error> {
+    let Context {
+        paths,
+        python_info,
+        settings,
+        ..
+    } = context;
+    if !settings.check_venv_python {
+        return Ok(());
+    }
+    operations::venv::check_python_version(&paths.venv, &python_info.version)
 }
@@ -2,6 +2,8 @@ use regex::Regex;
 use structopt::StructOpt;
 
 use crate::error::*;
+use crate::ui::ColorChoice;
+use crate::{OutputFormat, ProcessScriptsMode, Shell};
 
 #[derive(StructOpt, Debug)]
 #[structopt(
@@ -15,6 +17,18 @@ pub struct Command {
     #[structopt(long = "project", help = "path to use as the project directory")]
     pub project_path: Option<String>,
 
+    #[structopt(
+        long = "keep",
+        help = "When --project is a git URL, keep the clone instead of removing it afterwards"
+    )]
+    pub keep: bool,
+
+    #[structopt(
+        long = "config",
+        help = "Read the [tool.dmenv] table from this file instead of pyproject.toml"
+    )]
+    pub config_path: Option<String>,
+
     #[structopt(long = "production", help = "Ignore dev dependencies")]
     pub production: bool,
 
@@ -24,14 +38,98 @@ pub struct Command {
     )]
     pub system_site_packages: bool,
 
+    #[structopt(
+        long = "quiet",
+        short = "q",
+        conflicts_with = "verbose",
+        help = "Only print errors and the output of the commands"
+    )]
+    pub quiet: bool,
+
+    #[structopt(
+        long = "verbose",
+        short = "v",
+        help = "Also print the error output of the commands run by dmenv"
+    )]
+    pub verbose: bool,
+
+    #[structopt(
+        long = "show-warnings",
+        help = "Print the warnings emitted by `pip freeze`, even when it succeeds"
+    )]
+    pub show_warnings: bool,
+
+    #[structopt(
+        long = "no-cache",
+        help = "Do not use the cached information about the Python interpreter"
+    )]
+    pub no_cache: bool,
+
+    #[structopt(
+        long = "no-upgrade-pip",
+        help = "Use the pip bundled with the virtualenv, instead of upgrading it in `create` and `lock`"
+    )]
+    pub no_upgrade_pip: bool,
+
+    #[structopt(
+        long = "progress",
+        help = "Show the phases pip goes through (resolving, downloading, installing) instead of its output"
+    )]
+    pub progress: bool,
+
+    #[structopt(
+        long = "json-errors",
+        help = "Print errors as JSON objects on stderr, for tools that parse dmenv's output"
+    )]
+    pub json_errors: bool,
+
+    #[structopt(
+        long = "color",
+        help = "When to use colors: auto, always or never",
+        default_value = "auto",
+        parse(try_from_str = "parse_color_choice")
+    )]
+    pub color: ColorChoice,
+
+    #[structopt(
+        long = "dry-run",
+        help = "Print the commands dmenv would run, without running them"
+    )]
+    pub dry_run: bool,
+
     #[structopt(subcommand)]
     pub sub_cmd: SubCommand,
 }
 
 #[derive(StructOpt, Debug)]
 pub enum SubCommand {
+    #[structopt(name = "bin", about = "Show the path of a binary in the virtualenv")]
+    Bin {
+        #[structopt(help = "Name of the binary")]
+        name: String,
+    },
+
+    #[structopt(name = "check", about = "Check that the virtualenv matches the lock")]
+    Check {},
+
     #[structopt(name = "clean", about = "Clean existing virtualenv")]
-    Clean {},
+    Clean {
+        #[structopt(long = "all", help = "Clean every virtualenv of the project")]
+        all: bool,
+    },
+
+    #[structopt(name = "env:list", about = "List the virtualenvs of the project")]
+    EnvList {},
+
+    #[structopt(name = "doctor", about = "Diagnose common problems")]
+    Doctor {},
+
+    #[structopt(
+        name = "selfcheck",
+        about = "Check the setup.py and setup.cfg templates used by dmenv init",
+        raw(setting = "structopt::clap::AppSettings::Hidden")
+    )]
+    SelfCheck {},
 
     #[structopt(name = "develop", about = "Run setup.py develop")]
     Develop {},
@@ -43,6 +141,24 @@ pub enum SubCommand {
     Install {
         #[structopt(long = "--no-develop", help = "Do not run setup.py develop")]
         no_develop: bool,
+
+        #[structopt(
+            long = "--force",
+            help = "Clean the virtualenv first, then re-install everything"
+        )]
+        force: bool,
+
+        #[structopt(
+            long = "--strict",
+            help = "Fail if the lock was generated on a different platform"
+        )]
+        strict: bool,
+
+        #[structopt(
+            long = "--requirement",
+            help = "Install dependencies from this requirements file instead of the lock"
+        )]
+        requirement: Option<String>,
     },
 
     #[structopt(name = "bump-in-lock", about = "Bump a dependency in the lock file")]
@@ -68,14 +184,38 @@ pub enum SubCommand {
         #[structopt(long = "author", help = "Author name")]
         author: Option<String>,
 
+        #[structopt(long = "author-email", help = "Author email")]
+        author_email: Option<String>,
+
+        #[structopt(
+            long = "license",
+            help = "License of the project, for instance `MIT` or `BSD-3-Clause`"
+        )]
+        license: Option<String>,
+
+        // Note: writing a setup.cfg is the default, this flag is here
+        // so that scripts can be explicit about it
+        #[structopt(
+            long = "setup-cfg",
+            conflicts_with = "no_setup_cfg",
+            help = "Put metadata in a `setup.cfg` file, next to a minimal `setup.py` (the default)"
+        )]
+        setup_cfg: bool,
+
         #[structopt(
             long = "no-setup-cfg",
             help = "Keep all code in the `setup.py` file, do not use `setup.cfg`"
         )]
         no_setup_cfg: bool,
+
+        #[structopt(
+            long = "from-requirements",
+            help = "Add the packages from this requirements file to install_requires"
+        )]
+        from_requirements: Option<String>,
     },
 
-    #[structopt(name = "lock", about = "(Re)-generate requirements.lock")]
+    #[structopt(name = "lock", about = "(Re)-generate the lock")]
     Lock {
         #[structopt(
             long = "python-version",
@@ -86,9 +226,139 @@ pub enum SubCommand {
 
         #[structopt(long = "platform", help = "Restrict platform")]
         sys_platform: Option<String>,
+
+        #[structopt(long = "hashes", help = "Record package hashes in the lock")]
+        hashes: bool,
+
+        #[structopt(
+            long = "output",
+            help = "Write the lock to this path instead of the default one"
+        )]
+        output: Option<String>,
+
+        #[structopt(
+            long = "upgrade",
+            help = "Upgrade this package, keeping the others pinned. Can be used several times",
+            number_of_values = 1
+        )]
+        upgrade: Vec<String>,
+
+        #[structopt(
+            long = "dev",
+            conflicts_with = "prod",
+            help = "Use the 'dev' extra dependencies and write the dev lock"
+        )]
+        dev: bool,
+
+        #[structopt(
+            long = "prod",
+            help = "Use the 'prod' extra dependencies and write the prod lock"
+        )]
+        prod: bool,
+
+        #[structopt(long = "index-url", help = "Base URL of the Python package index")]
+        index_url: Option<String>,
+
+        #[structopt(
+            long = "extra-index-url",
+            help = "Extra URL of a package index to use. Can be used several times",
+            number_of_values = 1
+        )]
+        extra_index_url: Vec<String>,
+
+        #[structopt(
+            long = "exclude",
+            help = "Do not write this package in the lock. Can be used several times",
+            number_of_values = 1
+        )]
+        exclude: Vec<String>,
+
+        #[structopt(
+            long = "with-build-deps",
+            help = "Install this package before the project, but do not write it in the lock. Can be used several times",
+            number_of_values = 1
+        )]
+        with_build_deps: Vec<String>,
+    },
+
+    #[structopt(name = "lock:diff", about = "Show differences between two lock files")]
+    LockDiff {
+        #[structopt(help = "Path of the lock to compare with")]
+        other: String,
+
+        #[structopt(
+            long = "base",
+            help = "Path of the base lock. Defaults to the lock of the project"
+        )]
+        base: Option<String>,
+    },
+
+    #[structopt(name = "lock:merge", about = "Merge an other lock into the lock file")]
+    LockMerge {
+        #[structopt(help = "Path of the lock to merge")]
+        other: String,
+
+        #[structopt(
+            long = "prefer-newer",
+            help = "Use the newer version when a package is locked at different versions"
+        )]
+        prefer_newer: bool,
+    },
+
+    #[structopt(
+        name = "lock:add",
+        about = "Add a pinned dependency to the lock file, or update its version"
+    )]
+    LockAdd {
+        #[structopt(help = "Dependency to add, like foo==0.42")]
+        spec: String,
+    },
+
+    #[structopt(name = "lock:prune", about = "Remove packages from the lock file")]
+    LockPrune {
+        #[structopt(help = "Names of the packages to remove", raw(required = "true"))]
+        names: Vec<String>,
+    },
+
+    #[structopt(
+        name = "lock:verify-hashes",
+        about = "Check the hashes in the lock file against the installed packages, or the index"
+    )]
+    LockVerifyHashes {},
+
+    #[structopt(
+        name = "lock:check-duplicates",
+        about = "Check that no package appears more than once in the lock file"
+    )]
+    LockCheckDuplicates {},
+
+    #[structopt(name = "lock:stats", about = "Show a summary of the lock file")]
+    LockStats {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
     },
 
-    #[structopt(name = "run", about = "Run the given binary from the virtualenv")]
+    #[structopt(name = "lock:sort", about = "Sort the dependencies in the lock file")]
+    LockSort {},
+
+    #[structopt(
+        name = "lock:pin-python",
+        about = "Record the current Python interpreter in the lock file"
+    )]
+    LockPinPython {},
+
+    // Note: everything after the binary name (or after `--`) is passed
+    // verbatim to the binary, even if it looks like a dmenv option
+    #[structopt(
+        name = "run",
+        about = "Run the given binary from the virtualenv",
+        raw(setting = "structopt::clap::AppSettings::TrailingVarArg")
+    )]
     Run {
         #[structopt(
             long = "--no-exec",
@@ -96,24 +366,171 @@ pub enum SubCommand {
         )]
         no_exec: bool,
 
-        #[structopt(name = "command", raw(required = "true"))]
+        #[structopt(
+            long = "--no-venv-check",
+            help = "Do not require the virtualenv to exist, and look for the binary in PATH if it's not in the virtualenv"
+        )]
+        no_venv_check: bool,
+
+        #[structopt(
+            long = "env",
+            help = "Set an environment variable (KEY=VALUE) for the command. Can be used several times",
+            number_of_values = 1,
+            parse(try_from_str = "parse_env_var")
+        )]
+        env: Vec<(String, String)>,
+
+        #[structopt(
+            long = "cwd",
+            help = "Run the command in this directory (relative to the project) instead of the project directory"
+        )]
+        cwd: Option<String>,
+
+        #[structopt(
+            long = "timeout",
+            help = "Kill the command if it is still running after this number of seconds"
+        )]
+        timeout: Option<u64>,
+
+        #[structopt(
+            long = "capture",
+            help = "Hide the output of the command, unless it fails",
+            conflicts_with = "timeout"
+        )]
+        capture: bool,
+
+        #[structopt(
+            long = "echo-only",
+            help = "Print the resolved command instead of running it, so that it can be evaluated by a shell"
+        )]
+        echo_only: bool,
+
+        #[structopt(
+            long = "list",
+            help = "List the binaries of the virtualenv that can be run, instead of running a command"
+        )]
+        list: bool,
+
+        #[structopt(
+            name = "command",
+            raw(required_unless = r#""list""#, allow_hyphen_values = "true")
+        )]
         cmd: Vec<String>,
     },
 
+    // Note: commands are separated by `--`, for instance:
+    // `dmenv run:all -- black --check . -- pytest`
+    #[structopt(
+        name = "run:all",
+        about = "Run several binaries from the virtualenv, one after the other",
+        raw(setting = "structopt::clap::AppSettings::TrailingVarArg")
+    )]
+    RunAll {
+        #[structopt(
+            long = "--keep-going",
+            help = "Run the remaining commands when one fails, and report failures at the end"
+        )]
+        keep_going: bool,
+
+        #[structopt(
+            name = "commands",
+            raw(required = "true", allow_hyphen_values = "true")
+        )]
+        args: Vec<String>,
+    },
+
     #[structopt(name = "process-scripts", help = "Process generated scripts")]
     ProcessScripts {
         #[structopt(long = "--force", help = "force override of existing files")]
         force: bool,
     },
 
+    #[structopt(
+        name = "process:shebangs",
+        about = "Make the scripts in the virtualenv use its Python binary"
+    )]
+    ProcessShebangs {
+        #[structopt(
+            long = "mode",
+            help = "safe: skip scripts that are already correct, override: re-write all of them",
+            default_value = "safe",
+            parse(try_from_str = "parse_process_scripts_mode")
+        )]
+        mode: ProcessScriptsMode,
+    },
+
     #[structopt(name = "show:deps", about = "Show installed dependencies information")]
-    ShowDeps {},
+    ShowDeps {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+
+        #[structopt(
+            long = "tree",
+            help = "Show dependencies as a tree (requires pip >= 22.2)"
+        )]
+        tree: bool,
+    },
 
     #[structopt(
         name = "show:outdated",
         about = "Show outdated dependencies information"
     )]
-    ShowOutDated {},
+    ShowOutDated {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(
+        name = "show:python",
+        about = "Show the Python interpreter used by dmenv"
+    )]
+    ShowPython {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(name = "config:show", about = "Show the configuration used by dmenv")]
+    ConfigShow {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+    },
+
+    #[structopt(name = "show:lock_path", about = "Show path of the lock file")]
+    ShowLockPath {},
+
+    #[structopt(
+        name = "show:lock",
+        about = "Same as show:lock_path, followed by whether the lock exists and how many packages it contains"
+    )]
+    ShowLock {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+    },
 
     #[structopt(name = "show:venv_path", about = "Show path of the virtualenv")]
     ShowVenvPath {},
@@ -124,8 +541,94 @@ pub enum SubCommand {
     )]
     ShowVenvBin {},
 
+    #[structopt(
+        name = "show:venv:activate",
+        about = "Show the command to run to activate the virtualenv"
+    )]
+    ShowVenvActivate {
+        #[structopt(
+            long = "shell",
+            help = "Shell syntax to use: sh, bash, zsh, fish, csh, powershell or cmd (defaults to sh, or cmd on Windows)",
+            parse(try_from_str = "parse_shell")
+        )]
+        shell: Option<Shell>,
+    },
+
+    #[structopt(
+        name = "show:venv:size",
+        about = "Show the disk usage of the virtualenv"
+    )]
+    ShowVenvSize {
+        #[structopt(long = "by-dir", help = "Show the size of each top-level directory")]
+        by_dir: bool,
+    },
+
     #[structopt(name = "tidy", about = "Re-generate a clean lock")]
-    Tidy {},
+    Tidy {
+        #[structopt(
+            long = "allow-active-venv",
+            help = "Ignore the activated virtualenv and use the one managed by dmenv"
+        )]
+        allow_active_venv: bool,
+
+        #[structopt(long = "index-url", help = "Base URL of the Python package index")]
+        index_url: Option<String>,
+
+        #[structopt(
+            long = "extra-index-url",
+            help = "Extra URL of a package index to use. Can be used several times",
+            number_of_values = 1
+        )]
+        extra_index_url: Vec<String>,
+
+        #[structopt(
+            long = "with-build-deps",
+            help = "Install this package before the project, but do not write it in the lock. Can be used several times",
+            number_of_values = 1
+        )]
+        with_build_deps: Vec<String>,
+    },
+
+    #[structopt(
+        name = "lock:upgrade-all",
+        about = "Re-generate the lock with the latest versions of every dependency"
+    )]
+    LockUpgradeAll {
+        #[structopt(
+            long = "allow-active-venv",
+            help = "Ignore the activated virtualenv and use the one managed by dmenv"
+        )]
+        allow_active_venv: bool,
+    },
+
+    #[structopt(
+        name = "venv:recreate",
+        about = "Clean the virtualenv and create an empty one"
+    )]
+    RecreateVenv {},
+
+    #[structopt(
+        name = "venv:relocate",
+        about = "Make a virtualenv that was moved usable at its new location"
+    )]
+    RelocateVenv {
+        #[structopt(help = "Path of the virtualenv (defaults to the one used by dmenv)")]
+        path: Option<String>,
+    },
+
+    #[structopt(
+        name = "venv:freeze-metadata",
+        about = "Show the dmenv version, Python version and platform recorded in the lock file"
+    )]
+    FreezeMetadata {
+        #[structopt(
+            long = "format",
+            help = "Output format (text or json)",
+            default_value = "text",
+            parse(try_from_str = "parse_output_format")
+        )]
+        format: OutputFormat,
+    },
 
     #[structopt(name = "upgrade-pip", about = "Upgrade pip in the virtualenv")]
     UpgradePip {},
@@ -147,6 +650,67 @@ fn parse_python_version(string: &str) -> Result<String, Error> {
     Ok(string.to_string())
 }
 
+fn parse_output_format(string: &str) -> Result<OutputFormat, Error> {
+    match string {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(new_error("should be either `text` or `json`".to_string())),
+    }
+}
+
+fn parse_shell(string: &str) -> Result<Shell, Error> {
+    match string {
+        "sh" | "bash" | "zsh" => Ok(Shell::Posix),
+        "fish" => Ok(Shell::Fish),
+        "csh" | "tcsh" => Ok(Shell::Csh),
+        "powershell" | "pwsh" => Ok(Shell::PowerShell),
+        "cmd" => Ok(Shell::Cmd),
+        _ => Err(new_error(
+            "should be one of `sh`, `bash`, `zsh`, `fish`, `csh`, `powershell` or `cmd`"
+                .to_string(),
+        )),
+    }
+}
+
+fn parse_env_var(string: &str) -> Result<(String, String), Error> {
+    let mut parts = string.splitn(2, '=');
+    let key = parts.next().unwrap_or_default();
+    match parts.next() {
+        Some(value) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(new_error("should look like KEY=VALUE".to_string())),
+    }
+}
+
+/// Split the arguments of `dmenv run:all` into commands,
+/// using `--` as a separator
+pub fn split_commands(args: &[String]) -> Vec<Vec<String>> {
+    args.split(|arg| arg == "--")
+        .filter(|cmd| !cmd.is_empty())
+        .map(|cmd| cmd.to_vec())
+        .collect()
+}
+
+fn parse_color_choice(string: &str) -> Result<ColorChoice, Error> {
+    match string {
+        "auto" => Ok(ColorChoice::Auto),
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        _ => Err(new_error(
+            "should be one of `auto`, `always` or `never`".to_string(),
+        )),
+    }
+}
+
+fn parse_process_scripts_mode(string: &str) -> Result<ProcessScriptsMode, Error> {
+    match string {
+        "safe" => Ok(ProcessScriptsMode::Safe),
+        "override" => Ok(ProcessScriptsMode::Override),
+        _ => Err(new_error(
+            "should be either `safe` or `override`".to_string(),
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -165,4 +729,151 @@ mod tests {
     fn test_parse_python_version_not_quoted() {
         parse_python_version("<= 3.6").unwrap_err();
     }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!(parse_output_format("json").unwrap(), OutputFormat::Json);
+        parse_output_format("yaml").unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_color_choice() {
+        assert_eq!(parse_color_choice("never").unwrap(), ColorChoice::Never);
+        assert_eq!(parse_color_choice("always").unwrap(), ColorChoice::Always);
+        parse_color_choice("yes").unwrap_err();
+    }
+
+    #[test]
+    fn test_quiet_conflicts_with_verbose() {
+        let args = &["dmenv", "--quiet", "--verbose", "show:venv_path"];
+        Command::from_iter_safe(args).unwrap_err();
+    }
+
+    #[test]
+    fn test_setup_cfg_conflicts_with_no_setup_cfg() {
+        let args = &["dmenv", "init", "foo", "--setup-cfg"];
+        Command::from_iter_safe(args).unwrap();
+        let args = &["dmenv", "init", "foo", "--setup-cfg", "--no-setup-cfg"];
+        Command::from_iter_safe(args).unwrap_err();
+    }
+
+    fn parse_run_all_args(args: &[&str]) -> (bool, Vec<Vec<String>>) {
+        let mut all_args = vec!["dmenv", "run:all"];
+        all_args.extend(args);
+        let cmd = Command::from_iter_safe(all_args).unwrap();
+        match cmd.sub_cmd {
+            SubCommand::RunAll { keep_going, args } => (keep_going, split_commands(&args)),
+            _ => panic!("Expecting a run:all subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_run_all_splits_commands() {
+        let (keep_going, cmds) = parse_run_all_args(&[
+            "--keep-going",
+            "--",
+            "black",
+            "--check",
+            ".",
+            "--",
+            "pytest",
+        ]);
+        assert!(keep_going);
+        assert_eq!(cmds, vec![vec!["black", "--check", "."], vec!["pytest"]]);
+    }
+
+    #[test]
+    fn test_run_all_without_leading_separator() {
+        let (keep_going, cmds) = parse_run_all_args(&["flake8", "--", "--", "pytest", "-k", "foo"]);
+        assert!(!keep_going);
+        assert_eq!(cmds, vec![vec!["flake8"], vec!["pytest", "-k", "foo"]]);
+    }
+
+    fn parse_run_args(args: &[&str]) -> (bool, Vec<String>) {
+        let mut all_args = vec!["dmenv", "run"];
+        all_args.extend(args);
+        let cmd = Command::from_iter_safe(all_args).unwrap();
+        match cmd.sub_cmd {
+            SubCommand::Run { cmd, no_exec, .. } => (no_exec, cmd),
+            _ => panic!("Expecting a run subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_run_after_double_dash() {
+        let (no_exec, cmd) = parse_run_args(&["--", "black", "--check", "."]);
+        assert!(!no_exec);
+        assert_eq!(cmd, &["black", "--check", "."]);
+    }
+
+    #[test]
+    fn test_run_with_flag_like_args() {
+        let (no_exec, cmd) = parse_run_args(&["--no-exec", "pytest", "--maxfail=1", "--project"]);
+        assert!(no_exec);
+        assert_eq!(cmd, &["pytest", "--maxfail=1", "--project"]);
+    }
+
+    #[test]
+    fn test_run_dmenv_options_after_binary_name() {
+        let (no_exec, cmd) = parse_run_args(&["pytest", "--no-exec"]);
+        assert!(!no_exec);
+        assert_eq!(cmd, &["pytest", "--no-exec"]);
+    }
+
+    #[test]
+    fn test_lock_dev_conflicts_with_prod() {
+        Command::from_iter_safe(&["dmenv", "lock", "--prod"]).unwrap();
+        Command::from_iter_safe(&["dmenv", "lock", "--dev", "--prod"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_run_requires_a_command_unless_listing() {
+        Command::from_iter_safe(&["dmenv", "run"]).unwrap_err();
+        let (_, cmd) = parse_run_args(&["--list"]);
+        assert!(cmd.is_empty());
+    }
+
+    #[test]
+    fn test_run_capture_conflicts_with_timeout() {
+        Command::from_iter_safe(&["dmenv", "run", "--capture", "pytest"]).unwrap();
+        Command::from_iter_safe(&["dmenv", "run", "--capture", "--timeout", "10", "pytest"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_process_scripts_mode() {
+        match parse_process_scripts_mode("override").unwrap() {
+            ProcessScriptsMode::Override => (),
+            ProcessScriptsMode::Safe => panic!("Expecting Override"),
+        }
+        parse_process_scripts_mode("force").unwrap_err();
+    }
+
+    #[test]
+    fn test_lock_upgrade_stacks() {
+        let args = &["dmenv", "lock", "--upgrade", "foo", "--upgrade", "bar"];
+        let cmd = Command::from_iter_safe(args).unwrap();
+        match cmd.sub_cmd {
+            SubCommand::Lock { upgrade, .. } => assert_eq!(upgrade, &["foo", "bar"]),
+            _ => panic!("Expecting a lock subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_env_var() {
+        assert_eq!(
+            parse_env_var("PYTHONPATH=src").unwrap(),
+            ("PYTHONPATH".to_string(), "src".to_string())
+        );
+        assert_eq!(
+            parse_env_var("FLAGS=a=b").unwrap(),
+            ("FLAGS".to_string(), "a=b".to_string())
+        );
+        assert_eq!(
+            parse_env_var("EMPTY=").unwrap(),
+            ("EMPTY".to_string(), "".to_string())
+        );
+        parse_env_var("PYTHONPATH").unwrap_err();
+        parse_env_var("=src").unwrap_err();
+    }
 }
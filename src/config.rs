@@ -0,0 +1,419 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::settings::VenvCreator;
+
+pub const PYPROJECT_FILENAME: &str = "pyproject.toml";
+pub const DMENV_TOML_FILENAME: &str = ".dmenv.toml";
+
+/// Configuration read from the `[tool.dmenv]` table of `pyproject.toml`.
+///
+/// Every field is optional, and matches a field in `Settings` (except for
+/// `python`, which matches the `--python` option).
+/// Environment variables and command line options take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub python: Option<String>,
+    pub production: Option<bool>,
+    pub system_site_packages: Option<bool>,
+    pub venv_from_stdlib: Option<bool>,
+    pub venv_creator: Option<VenvCreator>,
+    pub venv_outside_project: Option<bool>,
+    pub venv_outdir: Option<PathBuf>,
+    pub use_python_version_file: Option<bool>,
+    pub use_requirements_in: Option<bool>,
+    pub index_url: Option<String>,
+    pub pip_extra_args: Option<Vec<String>>,
+    pub pip_version: Option<String>,
+    pub upgrade_pip: Option<bool>,
+    pub lock_path: Option<PathBuf>,
+    pub prod_lock_path: Option<PathBuf>,
+    pub lock_name: Option<String>,
+    pub prod_lock_name: Option<String>,
+    pub platform_specific_locks: Option<bool>,
+    pub venv_creation_attempts: Option<u32>,
+    pub strict_platform: Option<bool>,
+    pub check_venv_python: Option<bool>,
+    pub find_links_dir: Option<PathBuf>,
+    pub post_install: Option<Commands>,
+    pub exclude: Option<Vec<String>>,
+    pub build_deps: Option<Vec<String>>,
+    pub aliases: Option<BTreeMap<String, String>>,
+    pub proxy: Option<String>,
+    pub dev_extra: Option<String>,
+    pub prod_extra: Option<String>,
+}
+
+/// Either a single command, or a list of commands, as in:
+/// `post_install = "python codegen.py"` or
+/// `post_install = ["python codegen.py", "python migrate.py"]`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Commands {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Commands {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Commands::One(cmd) => vec![cmd.to_string()],
+            Commands::Many(cmds) => cmds.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PyProject {
+    tool: Option<Tool>,
+}
+
+#[derive(Deserialize)]
+struct Tool {
+    dmenv: Option<Config>,
+}
+
+/// Read the configuration from `config_path` if set (see `dmenv --config`), or
+/// from the project, see `read_project_config()`
+// Note: relative paths are relative to the directory of the file that sets
+// them, see `resolve_relative_paths()`
+pub fn read_config(project_path: &Path, config_path: Option<&Path>) -> Result<Config, Error> {
+    match config_path {
+        Some(config_path) => read_config_file(config_path),
+        None => read_project_config(project_path),
+    }
+}
+
+/// The settings containing paths
+const PATH_KEYS: [&str; 4] = [
+    "lock_path",
+    "prod_lock_path",
+    "venv_outdir",
+    "find_links_dir",
+];
+
+/// Make the relative paths in `table` relative to `config_dir`, the directory
+/// of the file the table comes from.
+// Note: this way, `lock_path = "shared.lock"` in a `.dmenv.toml` at the root
+// of a monorepo points to the same file for every project
+fn resolve_relative_paths(table: &mut toml::value::Table, config_dir: &Path) {
+    for key in PATH_KEYS.iter() {
+        if let Some(toml::Value::String(path)) = table.get_mut(*key) {
+            *path = config_dir.join(&path).to_string_lossy().to_string();
+        }
+    }
+}
+
+/// Return the directory of the given configuration file
+fn config_dir(config_path: &Path) -> PathBuf {
+    let config_dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+    if config_dir.is_absolute() {
+        return config_dir.to_path_buf();
+    }
+    std::env::current_dir()
+        .map(|cwd| cwd.join(config_dir))
+        .unwrap_or_else(|_| config_dir.to_path_buf())
+}
+
+/// Read the configuration from the `.dmenv.toml` file found by looking up
+/// from the project path (see `find_dmenv_toml()`), and from the `[tool.dmenv]`
+/// table of the `pyproject.toml` file in the project.
+/// Both are optional, and values from `pyproject.toml` take precedence.
+//
+// Note: `.dmenv.toml` contains the settings at the top level, without
+// a `[tool.dmenv]` table. The two files are merged key by key, so for
+// instance the `aliases` table from `pyproject.toml` replaces the one
+// from `.dmenv.toml`
+fn read_project_config(project_path: &Path) -> Result<Config, Error> {
+    let mut merged = toml::value::Table::new();
+    let mut sources = vec![];
+    if let Some(dmenv_toml_path) = find_dmenv_toml(project_path) {
+        let contents = std::fs::read_to_string(&dmenv_toml_path)
+            .map_err(|e| new_read_error(e, &dmenv_toml_path))?;
+        let malformed = |details| Error::MalformedConfig {
+            path: dmenv_toml_path.clone(),
+            details,
+        };
+        // Parse as a Config first, so that errors contain line numbers
+        toml::from_str::<Config>(&contents).map_err(|e| malformed(e.to_string()))?;
+        let mut table: toml::value::Table =
+            toml::from_str(&contents).map_err(|e| malformed(e.to_string()))?;
+        resolve_relative_paths(&mut table, &config_dir(&dmenv_toml_path));
+        merged.extend(table);
+        sources.push(dmenv_toml_path);
+    }
+
+    let pyproject_path = project_path.join(PYPROJECT_FILENAME);
+    if pyproject_path.exists() {
+        let contents = std::fs::read_to_string(&pyproject_path)
+            .map_err(|e| new_read_error(e, &pyproject_path))?;
+        let malformed = |details| Error::MalformedConfig {
+            path: pyproject_path.clone(),
+            details,
+        };
+        parse_config(&contents).map_err(malformed)?;
+        let pyproject: toml::Value =
+            toml::from_str(&contents).map_err(|e| malformed(e.to_string()))?;
+        let table = pyproject.get("tool").and_then(|x| x.get("dmenv"));
+        if let Some(toml::Value::Table(table)) = table {
+            let mut table = table.clone();
+            resolve_relative_paths(&mut table, &config_dir(&pyproject_path));
+            merged.extend(table);
+        }
+        sources.push(pyproject_path);
+    }
+
+    // Note: both files were checked above, so this is not expected to fail.
+    // If it does, name all of them since the culprit is unknown
+    Config::deserialize(toml::Value::Table(merged)).map_err(|e| {
+        let names: Vec<_> = sources.iter().map(|x| x.display().to_string()).collect();
+        Error::MalformedConfig {
+            path: sources
+                .last()
+                .cloned()
+                .unwrap_or_else(|| project_path.join(PYPROJECT_FILENAME)),
+            details: format!("{} (when merging {})", e, names.join(" and ")),
+        }
+    })
+}
+
+/// Look for a `.dmenv.toml` file in the project path, then in its parent
+/// directories, up to the root of the file system.
+// Note: this allows sharing settings across the projects of a monorepo
+fn find_dmenv_toml(project_path: &Path) -> Option<PathBuf> {
+    let project_path = if project_path.is_absolute() {
+        project_path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(project_path)
+    };
+    project_path
+        .ancestors()
+        .map(|dir| dir.join(DMENV_TOML_FILENAME))
+        .find(|path| path.is_file())
+}
+
+/// Read a configuration file given explicitly by the user.
+/// Unlike `pyproject.toml` in the project, the file must exist
+/// and contain a `[tool.dmenv]` table
+fn read_config_file(config_path: &Path) -> Result<Config, Error> {
+    if !config_path.exists() {
+        return Err(Error::MissingConfig {
+            path: config_path.to_path_buf(),
+        });
+    }
+    let contents =
+        std::fs::read_to_string(config_path).map_err(|e| new_read_error(e, config_path))?;
+    let malformed = |details| Error::MalformedConfig {
+        path: config_path.to_path_buf(),
+        details,
+    };
+    // Parse as a Config first, so that errors contain line numbers
+    parse_dmenv_table(&contents).map_err(malformed)?;
+    let pyproject: toml::Value = toml::from_str(&contents).map_err(|e| malformed(e.to_string()))?;
+    let table = pyproject.get("tool").and_then(|x| x.get("dmenv"));
+    let mut table = table
+        .and_then(toml::Value::as_table)
+        .cloned()
+        .ok_or_else(|| malformed("no [tool.dmenv] table found".to_string()))?;
+    resolve_relative_paths(&mut table, &config_dir(config_path));
+    Config::deserialize(toml::Value::Table(table)).map_err(|e| malformed(e.to_string()))
+}
+
+/// Parse the `[tool.dmenv]` table of the `pyproject.toml` contents.
+/// An empty Config is returned if there is no such table
+pub fn parse_config(text: &str) -> Result<Config, String> {
+    let config = parse_dmenv_table(text)?;
+    Ok(config.unwrap_or_default())
+}
+
+// Note: the errors returned by the toml crate contain the line and column
+// of the problem
+fn parse_dmenv_table(text: &str) -> Result<Option<Config>, String> {
+    let pyproject: PyProject = toml::from_str(text).map_err(|e| e.to_string())?;
+    Ok(pyproject.tool.and_then(|x| x.dmenv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let text = r#"
+[build-system]
+requires = ["setuptools", "wheel"]
+
+[tool.black]
+line-length = 100
+
+[tool.dmenv]
+python = "/usr/bin/python3.7"
+production = true
+pip_extra_args = ["--no-build-isolation"]
+venv_creation_attempts = 3
+venv_creator = "virtualenv"
+"#;
+        let config = parse_config(text).unwrap();
+        assert_eq!(config.python.unwrap(), "/usr/bin/python3.7");
+        assert_eq!(config.production, Some(true));
+        assert_eq!(config.pip_extra_args.unwrap(), &["--no-build-isolation"]);
+        assert_eq!(config.venv_creation_attempts, Some(3));
+        assert_eq!(config.venv_creator, Some(VenvCreator::Virtualenv));
+        assert!(config.index_url.is_none());
+    }
+
+    #[test]
+    fn test_post_install_commands() {
+        let config = parse_config("[tool.dmenv]\npost_install = \"python codegen.py\"\n").unwrap();
+        assert_eq!(
+            config.post_install.unwrap().to_vec(),
+            &["python codegen.py"]
+        );
+
+        let text = "[tool.dmenv]\npost_install = [\"python codegen.py\", \"python migrate.py\"]\n";
+        let config = parse_config(text).unwrap();
+        assert_eq!(
+            config.post_install.unwrap().to_vec(),
+            &["python codegen.py", "python migrate.py"]
+        );
+
+        parse_config("[tool.dmenv]\npost_install = 42\n").unwrap_err();
+    }
+
+    #[test]
+    fn test_aliases() {
+        let text = "[tool.dmenv.aliases]\ntest = \"pytest --maxfail=1\"\n";
+        let config = parse_config(text).unwrap();
+        assert_eq!(config.aliases.unwrap()["test"], "pytest --maxfail=1");
+    }
+
+    #[test]
+    fn test_no_dmenv_table() {
+        let config = parse_config("[tool.black]\nline-length = 100\n").unwrap();
+        assert!(config.python.is_none());
+        let config = parse_config("").unwrap();
+        assert!(config.production.is_none());
+    }
+
+    #[test]
+    fn test_malformed_config() {
+        parse_config("[tool.dmenv\n").unwrap_err();
+        parse_config("[tool.dmenv]\nproduction = \"yes\"\n").unwrap_err();
+        parse_config("[tool.dmenv]\nno_such_setting = true\n").unwrap_err();
+    }
+
+    #[test]
+    fn test_explicit_config_file() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let config_path = tmp.path().join("dmenv.toml");
+        let project_path = tmp.path().join("project");
+
+        let error = read_config(&project_path, Some(&config_path)).unwrap_err();
+        assert!(error.to_string().contains("dmenv.toml"));
+
+        std::fs::write(&config_path, "[tool.black]\nline-length = 100\n").unwrap();
+        let error = read_config(&project_path, Some(&config_path)).unwrap_err();
+        assert!(error.to_string().contains("no [tool.dmenv] table"));
+
+        std::fs::write(&config_path, "[tool.dmenv]\nproduction = \"yes\"\n").unwrap();
+        let error = read_config(&project_path, Some(&config_path)).unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+
+        std::fs::write(&config_path, "[tool.dmenv]\nproduction = true\n").unwrap();
+        let config = read_config(&project_path, Some(&config_path)).unwrap();
+        assert_eq!(config.production, Some(true));
+    }
+
+    #[test]
+    fn test_dmenv_toml_in_parent_directory() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let project_path = tmp.path().join("projects/foo");
+        std::fs::create_dir_all(&project_path).unwrap();
+        std::fs::write(
+            tmp.path().join(DMENV_TOML_FILENAME),
+            "production = true\nindex_url = \"https://pypi.corp.tld/simple\"\n",
+        )
+        .unwrap();
+
+        let config = read_config(&project_path, None).unwrap();
+        assert_eq!(config.production, Some(true));
+        assert_eq!(config.index_url.unwrap(), "https://pypi.corp.tld/simple");
+
+        // pyproject.toml in the project takes precedence
+        std::fs::write(
+            project_path.join(PYPROJECT_FILENAME),
+            "[tool.dmenv]\nproduction = false\n",
+        )
+        .unwrap();
+        let config = read_config(&project_path, None).unwrap();
+        assert_eq!(config.production, Some(false));
+        assert_eq!(config.index_url.unwrap(), "https://pypi.corp.tld/simple");
+
+        // --config bypasses the search
+        let config_path = tmp.path().join("ci.toml");
+        std::fs::write(&config_path, "[tool.dmenv]\nstrict_platform = true\n").unwrap();
+        let config = read_config(&project_path, Some(&config_path)).unwrap();
+        assert!(config.production.is_none());
+        assert!(config.index_url.is_none());
+    }
+
+    #[test]
+    fn test_malformed_dmenv_toml() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        std::fs::write(tmp.path().join(DMENV_TOML_FILENAME), "production = 42\n").unwrap();
+        let error = read_config(tmp.path(), None).unwrap_err();
+        assert!(error.to_string().contains(DMENV_TOML_FILENAME));
+        assert!(error.to_string().contains("line 1"));
+    }
+
+    #[test]
+    fn test_relative_paths_in_parent_dmenv_toml() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let project_path = tmp.path().join("foo");
+        std::fs::create_dir_all(&project_path).unwrap();
+        std::fs::write(
+            tmp.path().join(DMENV_TOML_FILENAME),
+            "lock_path = \"shared.lock\"\nvenv_outdir = \".envs\"\nfind_links_dir = \"wheels\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            project_path.join(PYPROJECT_FILENAME),
+            "[tool.dmenv]\nprod_lock_path = \"production.lock\"\n",
+        )
+        .unwrap();
+
+        let config = read_config(&project_path, None).unwrap();
+        assert_eq!(config.lock_path.unwrap(), tmp.path().join("shared.lock"));
+        assert_eq!(config.venv_outdir.unwrap(), tmp.path().join(".envs"));
+        assert_eq!(config.find_links_dir.unwrap(), tmp.path().join("wheels"));
+        assert_eq!(
+            config.prod_lock_path.unwrap(),
+            project_path.join("production.lock")
+        );
+    }
+
+    #[test]
+    fn test_relative_paths_in_explicit_config_file() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let config_path = tmp.path().join("ci").join("dmenv.toml");
+        std::fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        std::fs::write(&config_path, "[tool.dmenv]\nlock_path = \"ci.lock\"\n").unwrap();
+        let config = read_config(&tmp.path().join("foo"), Some(&config_path)).unwrap();
+        assert_eq!(
+            config.lock_path.unwrap(),
+            tmp.path().join("ci").join("ci.lock")
+        );
+    }
+
+    #[test]
+    fn test_relative_lock_path() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let pyproject = tmp.path().join(PYPROJECT_FILENAME);
+        std::fs::write(&pyproject, "[tool.dmenv]\nlock_path = \"../shared.lock\"\n").unwrap();
+        let config = read_config(tmp.path(), None).unwrap();
+        assert_eq!(config.lock_path.unwrap(), tmp.path().join("../shared.lock"));
+    }
+}
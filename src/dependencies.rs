@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::error::Error;
 use crate::lock::parse_simple_line;
 
@@ -8,6 +10,8 @@ use crate::lock::parse_simple_line;
 ///   are either the Simple variant (foo==42), or the Git variant
 ///   (git+https://git.local/foo@master#egg=foo)
 ///
+/// * Installed dependencies come from `pip list` output.
+///
 /// Locked dependencies can either be *bumped* (when using `dmenv bump-in-lock`,
 /// or *updated*, when using `dmenv lock` and updating the contents of the lock file
 /// with the output from `pip freeze`
@@ -16,11 +20,15 @@ use crate::lock::parse_simple_line;
 pub struct FrozenDependency {
     pub name: String,
     pub version: String,
+    /// Hashes as written in the lock, for instance `sha256:32fde42...`.
+    /// Only set when using `dmenv lock --hashes`
+    pub hashes: Vec<String>,
 }
 
 impl FrozenDependency {
     /// Construct a new FrozenDependency from a line coming from
-    /// `pip freeze` output
+    /// `pip freeze` output, or from a lock file line containing
+    /// `--hash` options (possibly spanning several physical lines)
     pub fn from_string(string: String) -> Result<Self, Error> {
         // Custom error in case we can't parse `pip freeze` output
         // This really should never happen (tm)
@@ -28,11 +36,13 @@ impl FrozenDependency {
             line: string.to_string(),
         };
 
-        if !string.contains("==") {
+        let (spec, hashes) = split_hashes(&string);
+
+        if !spec.contains("==") {
             return Err(err);
         }
 
-        let words: Vec<_> = string.split("==").collect();
+        let words: Vec<_> = spec.split("==").collect();
         if words.len() != 2 {
             return Err(err);
         }
@@ -46,10 +56,399 @@ impl FrozenDependency {
         Ok(FrozenDependency {
             name: name.to_string(),
             version: version.to_string(),
+            hashes,
         })
     }
 }
 
+impl FrozenDependency {
+    /// Convert a LockedDependency to a FrozenDependency, so that
+    /// locks can be compared with each other or with `pip freeze` output.
+    /// For git dependencies, the version is the git reference.
+    pub fn from_locked(locked: &LockedDependency) -> Self {
+        let (_, hashes) = split_hashes(&locked.line());
+        FrozenDependency {
+            name: locked.name(),
+            version: locked.version(),
+            hashes,
+        }
+    }
+}
+
+/// Filter out pkg-resources: this works around a Debian bug in pip:
+/// https://bugs.debian.org/cgi-bin/bugreport.cgi?bug=871790
+pub fn remove_pkg_resources(deps: Vec<FrozenDependency>) -> Vec<FrozenDependency> {
+    deps.into_iter()
+        .filter(|x| x.name != "pkg-resources")
+        .collect()
+}
+
+/// Filter out the packages from `settings.exclude`.
+// Note: names are compared after normalization, so that `Foo_Bar`
+// excludes `foo-bar`
+pub fn remove_excluded(deps: Vec<FrozenDependency>, excluded: &[String]) -> Vec<FrozenDependency> {
+    let excluded: Vec<_> = excluded.iter().map(|x| normalize_name(x)).collect();
+    deps.into_iter()
+        .filter(|x| !excluded.contains(&normalize_name(&x.name)))
+        .collect()
+}
+
+/// Split a line like `foo==42 --hash=sha256:abc` into the
+/// requirement specification (`foo==42`) and the list of hashes
+/// (`["sha256:abc"]`)
+fn split_hashes(line: &str) -> (&str, Vec<String>) {
+    let (spec, rest) = match line.find("--hash=") {
+        Some(index) => line.split_at(index),
+        None => (line, ""),
+    };
+    let spec = spec.trim().trim_end_matches('\\').trim_end();
+    let hashes = rest
+        .split_whitespace()
+        .filter_map(|x| x.strip_prefix("--hash="))
+        .map(|x| x.to_string())
+        .collect();
+    (spec, hashes)
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstalledDependency {
+    pub name: String,
+    pub version: String,
+    pub editable: bool,
+}
+
+// One item of `pip list --format json` output
+#[derive(Deserialize)]
+struct PipListItem {
+    name: String,
+    version: String,
+    // Only set for editable packages, and only by pip >= 21.3
+    editable_project_location: Option<String>,
+}
+
+impl InstalledDependency {
+    /// Parse the output of `pip list --format json`.
+    /// Results are sorted by name, so that the output is stable.
+    pub fn from_pip_list_json(json: &str) -> Result<Vec<Self>, Error> {
+        let items: Vec<PipListItem> =
+            serde_json::from_str(json).map_err(|e| Error::ParsePipListError {
+                details: e.to_string(),
+            })?;
+        let mut res: Vec<_> = items
+            .into_iter()
+            .map(|x| InstalledDependency {
+                name: x.name,
+                version: x.version,
+                editable: x.editable_project_location.is_some(),
+            })
+            .collect();
+        res.sort_by_key(|x| x.name.to_lowercase());
+        Ok(res)
+    }
+}
+
+/// A node in the dependency tree displayed by `dmenv show:deps --tree`
+#[derive(Debug, Serialize)]
+pub struct DependencyNode {
+    pub name: String,
+    pub version: String,
+    pub dependencies: Vec<DependencyNode>,
+}
+
+// `pip inspect` output looks like:
+// {"installed": [{"metadata": {"name": "foo", "version": "1.0", "requires_dist": [...]}}]}
+#[derive(Deserialize)]
+struct PipInspectOutput {
+    installed: Vec<PipInspectItem>,
+}
+
+#[derive(Deserialize)]
+struct PipInspectItem {
+    metadata: PipInspectMetadata,
+    direct_url: Option<PipInspectDirectUrl>,
+}
+
+#[derive(Deserialize)]
+struct PipInspectDirectUrl {
+    dir_info: Option<PipInspectDirInfo>,
+    archive_info: Option<PipInspectArchiveInfo>,
+}
+
+/// Set when the package was installed from an archive URL.
+// Note: `hash` is the legacy form of `hashes`, like `sha256=32fde42...`
+#[derive(Deserialize)]
+struct PipInspectArchiveInfo {
+    #[serde(default)]
+    hashes: std::collections::BTreeMap<String, String>,
+    hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PipInspectDirInfo {
+    #[serde(default)]
+    editable: bool,
+}
+
+impl PipInspectItem {
+    fn is_editable(&self) -> bool {
+        let dir_info = self.direct_url.as_ref().and_then(|x| x.dir_info.as_ref());
+        dir_info.map(|x| x.editable).unwrap_or(false)
+    }
+
+    /// The hashes of the archive the package was installed from,
+    /// in the lock format (`sha256:32fde42...`)
+    fn archive_hashes(&self) -> Vec<String> {
+        let archive_info = match self
+            .direct_url
+            .as_ref()
+            .and_then(|x| x.archive_info.as_ref())
+        {
+            None => return vec![],
+            Some(x) => x,
+        };
+        let mut res: Vec<_> = archive_info
+            .hashes
+            .iter()
+            .map(|(algorithm, value)| format!("{}:{}", algorithm, value))
+            .collect();
+        if let Some(hash) = &archive_info.hash {
+            res.push(hash.replacen('=', ":", 1));
+        }
+        res.sort();
+        res.dedup();
+        res
+    }
+}
+
+#[derive(Deserialize)]
+struct PipInspectMetadata {
+    name: String,
+    version: String,
+    #[serde(default)]
+    requires_dist: Vec<String>,
+}
+
+/// The installed packages and their requirements, from the output of `pip inspect`
+//
+// Notes:
+// * Environment markers are not evaluated: instead, a requirement is
+//   part of the graph if the package it names is installed.
+// * Requirements of extras are skipped, except for editable packages,
+//   since the project is installed with `.[dev]` or `.[prod]`
+struct RequirementsGraph {
+    packages: Vec<PipInspectMetadata>,
+    /// Normalized names of the packages, sorted
+    installed: Vec<String>,
+    /// Normalized names of the requirements of each package
+    requires: Vec<Vec<String>>,
+}
+
+impl RequirementsGraph {
+    fn from_pip_inspect_json(json: &str) -> Result<Self, Error> {
+        let output: PipInspectOutput =
+            serde_json::from_str(json).map_err(|e| Error::ParsePipListError {
+                details: e.to_string(),
+            })?;
+        let mut items = output.installed;
+        items.sort_by_key(|x| x.metadata.name.to_lowercase());
+        let installed: Vec<_> = items
+            .iter()
+            .map(|x| normalize_name(&x.metadata.name))
+            .collect();
+        let requires: Vec<Vec<String>> = items
+            .iter()
+            .map(|x| {
+                let with_extras = x.is_editable();
+                let mut names: Vec<_> = x
+                    .metadata
+                    .requires_dist
+                    .iter()
+                    .filter(|r| with_extras || !is_extra_requirement(r))
+                    .map(|r| normalize_name(requirement_name(r)))
+                    .filter(|r| installed.contains(r))
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            })
+            .collect();
+        let packages: Vec<_> = items.into_iter().map(|x| x.metadata).collect();
+        Ok(RequirementsGraph {
+            packages,
+            installed,
+            requires,
+        })
+    }
+
+    /// The given packages, plus everything they require, recursively
+    fn closure(&self, names: &[String]) -> Vec<String> {
+        let mut res: Vec<String> = vec![];
+        let mut to_visit: Vec<String> = names.to_vec();
+        while let Some(name) = to_visit.pop() {
+            if res.contains(&name) {
+                continue;
+            }
+            if let Some(index) = self.installed.iter().position(|x| *x == name) {
+                to_visit.extend(self.requires[index].iter().cloned());
+                res.push(name);
+            }
+        }
+        res
+    }
+}
+
+/// Return the normalized names of the packages installed only to build the project,
+/// from the output of `pip inspect`: the given build dependencies and everything
+/// they require, except the packages also required by another installed package
+pub fn build_only_packages(json: &str, build_deps: &[String]) -> Result<Vec<String>, Error> {
+    let graph = RequirementsGraph::from_pip_inspect_json(json)?;
+    let build_deps: Vec<_> = build_deps.iter().map(|x| normalize_name(x)).collect();
+    let for_build = graph.closure(&build_deps);
+    let others: Vec<_> = graph
+        .installed
+        .iter()
+        .filter(|x| !for_build.contains(x))
+        .cloned()
+        .collect();
+    let for_runtime = graph.closure(&others);
+    let mut res: Vec<_> = for_build
+        .into_iter()
+        .filter(|x| !for_runtime.contains(x))
+        .collect();
+    res.sort();
+    Ok(res)
+}
+
+/// Return the installed packages, from the output of `pip inspect`, along
+/// with the hashes of the archives they were installed from, if known.
+// Note: pip only records them (in `direct_url.json`) for packages installed
+// from an archive URL, not for the ones installed from an index
+pub fn installed_packages(json: &str) -> Result<Vec<FrozenDependency>, Error> {
+    let output: PipInspectOutput =
+        serde_json::from_str(json).map_err(|e| Error::ParsePipListError {
+            details: e.to_string(),
+        })?;
+    let res = output
+        .installed
+        .iter()
+        .map(|x| FrozenDependency {
+            name: x.metadata.name.to_string(),
+            version: x.metadata.version.to_string(),
+            hashes: x.archive_hashes(),
+        })
+        .collect();
+    Ok(res)
+}
+
+impl DependencyNode {
+    /// Build the dependency tree from the output of `pip inspect`.
+    /// Roots are the packages no other package depends on, sorted by name.
+    // Note: a package already displayed in the current branch is not expanded
+    // again, in case of circular dependencies. See `RequirementsGraph` for
+    // how requirements are computed
+    pub fn from_pip_inspect_json(json: &str) -> Result<Vec<Self>, Error> {
+        let RequirementsGraph {
+            packages,
+            installed,
+            requires,
+        } = RequirementsGraph::from_pip_inspect_json(json)?;
+        let mut res = vec![];
+        for (i, name) in installed.iter().enumerate() {
+            let is_required = requires.iter().any(|r| r.contains(name));
+            if !is_required {
+                res.push(Self::build(
+                    i,
+                    &packages,
+                    &installed,
+                    &requires,
+                    &mut vec![],
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    fn build(
+        index: usize,
+        packages: &[PipInspectMetadata],
+        installed: &[String],
+        requires: &[Vec<String>],
+        parents: &mut Vec<usize>,
+    ) -> Self {
+        let package = &packages[index];
+        let mut dependencies = vec![];
+        if !parents.contains(&index) {
+            parents.push(index);
+            for name in &requires[index] {
+                let child = installed.iter().position(|x| x == name).unwrap();
+                dependencies.push(Self::build(child, packages, installed, requires, parents));
+            }
+            parents.pop();
+        }
+        DependencyNode {
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            dependencies,
+        }
+    }
+
+    /// Write the tree with one line per node, indented by depth
+    pub fn format(&self, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(&format!("{}=={}\n", self.name, self.version));
+        for dependency in &self.dependencies {
+            dependency.format(depth + 1, out);
+        }
+    }
+}
+
+// `Foo.Bar_baz` and `foo-bar-baz` are the same package, see PEP 503
+pub fn normalize_name(name: &str) -> String {
+    name.to_lowercase().replace(['_', '.'], "-")
+}
+
+// For instance: `foo[bar] >= 1.0 ; python_version < "3.8"` -> `foo`
+pub fn requirement_name(requirement: &str) -> &str {
+    let end = requirement
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .unwrap_or(requirement.len());
+    &requirement[..end]
+}
+
+// For instance: `pytest ; extra == 'dev'` -> true, but `extras-tool` -> false
+// Note: only the environment marker, after the `;`, is checked
+pub fn is_extra_requirement(requirement: &str) -> bool {
+    let marker = match requirement.split_once(';') {
+        None => return false,
+        Some((_, marker)) => marker,
+    };
+    let marker: String = marker.chars().filter(|c| !c.is_whitespace()).collect();
+    marker.contains("extra==") || marker.contains("==extra")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutdatedDependency {
+    pub name: String,
+    pub version: String,
+    pub latest_version: String,
+    // Either "wheel" or "sdist"
+    #[serde(rename(serialize = "type"))]
+    pub latest_filetype: String,
+}
+
+impl OutdatedDependency {
+    /// Parse the output of `pip list --outdated --format json`.
+    /// Results are sorted by name, so that the output is stable.
+    pub fn from_pip_list_json(json: &str) -> Result<Vec<Self>, Error> {
+        let mut res: Vec<Self> =
+            serde_json::from_str(json).map_err(|e| Error::ParsePipListError {
+                details: e.to_string(),
+            })?;
+        res.sort_by_key(|x| x.name.to_lowercase());
+        Ok(res)
+    }
+}
+
 #[derive(Debug)]
 pub enum LockedDependency {
     Git(GitDependency),
@@ -71,6 +470,21 @@ impl LockedDependency {
         }
     }
 
+    /// Comment lines written right above the dependency in the lock
+    pub fn comment(&self) -> &str {
+        match self {
+            LockedDependency::Git(x) => &x.comment,
+            LockedDependency::Simple(x) => &x.comment,
+        }
+    }
+
+    pub fn set_comment(&mut self, comment: &str) {
+        match self {
+            LockedDependency::Git(x) => x.comment = comment.to_string(),
+            LockedDependency::Simple(x) => x.comment = comment.to_string(),
+        }
+    }
+
     pub fn version(&self) -> String {
         match self {
             LockedDependency::Git(x) => x.git_ref.value.to_string(),
@@ -118,6 +532,7 @@ pub struct VersionSpec {
 
 #[derive(Debug)]
 pub struct GitDependency {
+    pub comment: String,
     pub name: String,
     pub line: String,
     pub git_ref: VersionSpec,
@@ -133,6 +548,7 @@ impl GitDependency {
 
 #[derive(Debug)]
 pub struct SimpleDependency {
+    pub comment: String,
     pub name: String,
     pub line: String,
     pub version: VersionSpec,
@@ -147,6 +563,34 @@ impl SimpleDependency {
         parse_simple_line(&line).expect("failed to parse frozen line")
     }
 
+    /// Replace the `--hash` options of this dependency.
+    // Note: hashes are always written last, one per physical line,
+    // after any environment marker
+    pub fn set_hashes(&mut self, hashes: &[String]) {
+        let (spec, _) = split_hashes(&self.line);
+        let mut line = spec.to_string();
+        for hash in hashes {
+            line.push_str(&format!(" \\\n    --hash={}", hash));
+        }
+        line.push('\n');
+        self.line = line;
+    }
+
+    /// Whether this dependency has an environment marker,
+    /// like `; sys_platform == 'win32'`
+    pub fn has_marker(&self) -> bool {
+        self.marker().is_some()
+    }
+
+    /// The environment marker of this dependency, if any
+    /// (`sys_platform == 'win32'` for `foo==0.42 ; sys_platform == 'win32'`)
+    pub fn marker(&self) -> Option<&str> {
+        let (spec, _) = split_hashes(&self.line);
+        let without_comment = spec.split('#').next().unwrap_or_default();
+        let (_, marker) = without_comment.split_once(';')?;
+        Some(marker.trim().trim_end_matches('\\').trim())
+    }
+
     /// Make this dependency specific to a Python version
     pub fn python_version(&mut self, python_version: &str) {
         let trimmed_line = self.line.trim_end_matches('\n');
@@ -186,7 +630,135 @@ impl SimpleDependency {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lock::parse_git_line;
+    use crate::lock::{parse_git_line, parse_line};
+
+    #[test]
+    fn test_dependency_tree() {
+        let json = r#"
+{
+  "version": "1",
+  "installed": [
+    {"metadata": {"name": "pytest", "version": "5.2.1",
+                  "requires_dist": ["py>=1.5.0", "attrs (>=17.4.0)", "colorama ; sys_platform == \"win32\"",
+                                    "pluggy-extra"]}},
+    {"metadata": {"name": "attrs", "version": "19.3.0",
+                  "requires_dist": ["zope.interface ; extra == 'dev'"]}},
+    {"metadata": {"name": "py", "version": "1.8.0"}},
+    {"metadata": {"name": "demo", "version": "0.1.0",
+                  "requires_dist": ["Path.py", "pytest ; extra == 'dev'"]},
+     "direct_url": {"url": "file:///path/to/demo", "dir_info": {"editable": true}}},
+    {"metadata": {"name": "setuptools", "version": "65.5.0",
+                  "requires_dist": ["py ; extra == 'testing'"]}},
+    {"metadata": {"name": "path_py", "version": "12.0.1"}},
+    {"metadata": {"name": "pluggy-extra", "version": "1.0.0"}}
+  ]
+}
+"#;
+        let roots = DependencyNode::from_pip_inspect_json(json).unwrap();
+        let mut actual = String::new();
+        for root in &roots {
+            root.format(0, &mut actual);
+        }
+        let expected = "\
+demo==0.1.0
+  path_py==12.0.1
+  pytest==5.2.1
+    attrs==19.3.0
+    pluggy-extra==1.0.0
+    py==1.8.0
+setuptools==65.5.0
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_only_packages() {
+        let json = r#"
+{
+  "version": "1",
+  "installed": [
+    {"metadata": {"name": "Cython", "version": "0.29.36",
+                  "requires_dist": ["setuptools-scm", "packaging"]}},
+    {"metadata": {"name": "setuptools_scm", "version": "7.1.0",
+                  "requires_dist": ["tomli"]}},
+    {"metadata": {"name": "tomli", "version": "2.0.1"}},
+    {"metadata": {"name": "packaging", "version": "23.1"}},
+    {"metadata": {"name": "demo", "version": "0.1.0",
+                  "requires_dist": ["packaging", "pytest ; extra == 'dev'"]},
+     "direct_url": {"url": "file:///path/to/demo", "dir_info": {"editable": true}}},
+    {"metadata": {"name": "pytest", "version": "5.2.1"}}
+  ]
+}
+"#;
+        let actual = build_only_packages(json, &["cython".to_string()]).unwrap();
+        assert_eq!(actual, &["cython", "setuptools-scm", "tomli"]);
+    }
+
+    #[test]
+    fn test_installed_packages() {
+        let json = r#"
+{
+  "version": "1",
+  "installed": [
+    {"metadata": {"name": "attrs", "version": "19.3.0"},
+     "direct_url": {"url": "https://example.com/attrs-19.3.0-py2.py3-none-any.whl",
+                    "archive_info": {"hashes": {"sha256": "abc"}, "hash": "sha256=abc"}}},
+    {"metadata": {"name": "py", "version": "1.8.0"},
+     "direct_url": {"url": "https://example.com/py-1.8.0.tar.gz",
+                    "archive_info": {"hash": "sha256=def"}}},
+    {"metadata": {"name": "pytest", "version": "5.2.1"}},
+    {"metadata": {"name": "demo", "version": "0.1.0"},
+     "direct_url": {"url": "file:///path/to/demo", "dir_info": {"editable": true}}}
+  ]
+}
+"#;
+        let actual = installed_packages(json).unwrap();
+        let actual: Vec<_> = actual
+            .iter()
+            .map(|x| (x.name.as_str(), x.version.as_str(), x.hashes.join(",")))
+            .collect();
+        assert_eq!(
+            actual,
+            &[
+                ("attrs", "19.3.0", "sha256:abc".to_string()),
+                ("py", "1.8.0", "sha256:def".to_string()),
+                ("pytest", "5.2.1", "".to_string()),
+                ("demo", "0.1.0", "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_excluded() {
+        let deps = vec![
+            FrozenDependency::from_string("foo==0.42".to_string()).unwrap(),
+            FrozenDependency::from_string("Internal_Tool==1.0".to_string()).unwrap(),
+            FrozenDependency::from_string("bar==0.1".to_string()).unwrap(),
+        ];
+        let excluded = &["internal-tool".to_string(), "BAR".to_string()];
+        let deps = remove_excluded(deps, excluded);
+        let names: Vec<_> = deps.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, &["foo"]);
+    }
+
+    #[test]
+    fn test_requirement_name() {
+        assert_eq!(requirement_name("foo"), "foo");
+        assert_eq!(requirement_name("foo.bar>=1.0"), "foo.bar");
+        assert_eq!(requirement_name("foo[baz] ; extra == 'dev'"), "foo");
+        assert_eq!(requirement_name("foo (>=1.0)"), "foo");
+    }
+
+    #[test]
+    fn test_is_extra_requirement() {
+        assert!(is_extra_requirement("foo[baz] ; extra == 'dev'"));
+        assert!(is_extra_requirement(
+            "foo ; python_version < \"3.8\" and extra==\"testing\""
+        ));
+        assert!(!is_extra_requirement("extras-tool>=1.0"));
+        assert!(!is_extra_requirement("foo[extra] (>=1.0)"));
+        assert!(!is_extra_requirement("foo ; sys_platform == 'win32'"));
+    }
 
     #[test]
     fn git_bump() {
@@ -201,4 +773,91 @@ mod tests {
         dep.simple_bump("0.43");
         assert_eq!(dep.line, "foo == 0.43");
     }
+
+    #[test]
+    fn frozen_from_string_with_hashes() {
+        let line = "foo==0.42 \\\n    --hash=sha256:abc \\\n    --hash=sha256:def\n";
+        let dep = FrozenDependency::from_string(line.to_string()).unwrap();
+        assert_eq!(dep.name, "foo");
+        assert_eq!(dep.version, "0.42");
+        assert_eq!(dep.hashes, &["sha256:abc", "sha256:def"]);
+    }
+
+    #[test]
+    fn frozen_from_string_without_hashes() {
+        let dep = FrozenDependency::from_string("foo==0.42".to_string()).unwrap();
+        assert_eq!(dep.version, "0.42");
+        assert!(dep.hashes.is_empty());
+    }
+
+    #[test]
+    fn installed_from_pip_list_json() {
+        let json = r#"[
+            {"name": "pytest", "version": "5.2.1"},
+            {"name": "demo", "version": "0.6.1", "editable_project_location": "/tmp/demo"},
+            {"name": "attrs", "version": "19.3.0"}
+        ]"#;
+        let deps = InstalledDependency::from_pip_list_json(json).unwrap();
+        let names: Vec<_> = deps.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, &["attrs", "demo", "pytest"]);
+        assert!(deps[1].editable);
+        assert!(!deps[0].editable);
+    }
+
+    #[test]
+    fn installed_from_invalid_json() {
+        let err = InstalledDependency::from_pip_list_json("Package Version").unwrap_err();
+        match err {
+            Error::ParsePipListError { .. } => (),
+            _ => panic!("Expecting ParsePipListError, got: {}", err),
+        }
+    }
+
+    #[test]
+    fn outdated_from_pip_list_json() {
+        let json = r#"[
+            {"name": "pytest", "version": "5.2.1", "latest_version": "5.3.0", "latest_filetype": "wheel"},
+            {"name": "Babel", "version": "2.7.0", "latest_version": "2.8.0", "latest_filetype": "sdist"}
+        ]"#;
+        let deps = OutdatedDependency::from_pip_list_json(json).unwrap();
+        let names: Vec<_> = deps.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, &["Babel", "pytest"]);
+        assert_eq!(deps[1].latest_version, "5.3.0");
+
+        let serialized = serde_json::to_string(&deps[0]).unwrap();
+        assert!(serialized.contains(r#""type":"sdist""#));
+    }
+
+    #[test]
+    fn frozen_from_locked_git() {
+        let locked = parse_line("git@example.com/bar.git@dae42f#egg=bar\n").unwrap();
+        let frozen = FrozenDependency::from_locked(&locked);
+        assert_eq!(frozen.name, "bar");
+        assert_eq!(frozen.version, "dae42f");
+    }
+
+    #[test]
+    fn frozen_from_locked_with_marker_and_hashes() {
+        let line = "foo==0.42 ; python_version < '3.6' \\\n    --hash=sha256:abc\n";
+        let locked = parse_line(line).unwrap();
+        let frozen = FrozenDependency::from_locked(&locked);
+        assert_eq!(frozen.name, "foo");
+        assert_eq!(frozen.version, "0.42");
+        assert_eq!(frozen.hashes, &["sha256:abc"]);
+    }
+
+    #[test]
+    fn set_hashes() {
+        let mut dep = parse_simple_line("foo==0.42 ; python_version < '3.6'\n").unwrap();
+        dep.set_hashes(&["sha256:abc".to_string()]);
+        assert_eq!(
+            dep.line,
+            "foo==0.42 ; python_version < '3.6' \\\n    --hash=sha256:abc\n"
+        );
+        dep.set_hashes(&["sha256:def".to_string()]);
+        assert_eq!(
+            dep.line,
+            "foo==0.42 ; python_version < '3.6' \\\n    --hash=sha256:def\n"
+        );
+    }
 }
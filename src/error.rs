@@ -32,6 +32,16 @@ pub enum Error {
     GetProcessOutputError {
         io_error: std::io::Error,
     },
+    ProcessFailed {
+        name: String,
+        exit_code: Option<i32>,
+        working_path: PathBuf,
+    },
+    ProcessTimedOut {
+        name: String,
+        timeout: std::time::Duration,
+        working_path: PathBuf,
+    },
 
     RunInfoPyError {
         message: String,
@@ -41,14 +51,32 @@ pub enum Error {
     ParsePipFreezeError {
         line: String,
     },
+    ParsePipListError {
+        details: String,
+    },
 
-    MissingSetupPy {},
+    MissingSetupPy {
+        project_path: PathBuf,
+    },
+    ProjectInstallFailed {
+        project_path: PathBuf,
+        stderr_tail: String,
+    },
     MissingLock {
         expected_path: PathBuf,
     },
     MissingVenv {
         path: PathBuf,
     },
+    VenvPythonMismatch {
+        path: PathBuf,
+        venv_version: String,
+        python_version: String,
+    },
+    MissingExtra {
+        name: String,
+        available: Vec<String>,
+    },
 
     FileExists {
         path: PathBuf,
@@ -61,6 +89,13 @@ pub enum Error {
     MalformedLock {
         details: String,
     },
+    MalformedConfig {
+        path: PathBuf,
+        details: String,
+    },
+    MissingConfig {
+        path: PathBuf,
+    },
 
     NothingToBump {
         name: String,
@@ -75,6 +110,90 @@ pub enum Error {
     },
 }
 
+impl Error {
+    /// Exit code to use when dmenv fails with this error.
+    // Note: when a process we ran failed, use its exit code,
+    // so that it's propagated to the shell.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::ProcessFailed {
+                exit_code: Some(code),
+                ..
+            } if *code != 0 => *code,
+            // Note: same exit code as the `timeout` command from coreutils
+            Error::ProcessTimedOut { .. } => 124,
+            _ => 1,
+        }
+    }
+
+    /// Name of the variant, for tools that parse the output of `dmenv --json-errors`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::ReadError { .. } => "ReadError",
+            Error::WriteError { .. } => "WriteError",
+            Error::NoWorkingDirectory { .. } => "NoWorkingDirectory",
+            Error::NulByteError { .. } => "NulByteError",
+            Error::StartProcessError { .. } => "StartProcessError",
+            Error::WaitProcessError { .. } => "WaitProcessError",
+            Error::GetProcessOutputError { .. } => "GetProcessOutputError",
+            Error::ProcessFailed { .. } => "ProcessFailed",
+            Error::ProcessTimedOut { .. } => "ProcessTimedOut",
+            Error::RunInfoPyError { .. } => "RunInfoPyError",
+            Error::UpgradePipError { .. } => "UpgradePipError",
+            Error::ParsePipFreezeError { .. } => "ParsePipFreezeError",
+            Error::ParsePipListError { .. } => "ParsePipListError",
+            Error::MissingSetupPy { .. } => "MissingSetupPy",
+            Error::ProjectInstallFailed { .. } => "ProjectInstallFailed",
+            Error::MissingLock { .. } => "MissingLock",
+            Error::MissingVenv { .. } => "MissingVenv",
+            Error::VenvPythonMismatch { .. } => "VenvPythonMismatch",
+            Error::MissingExtra { .. } => "MissingExtra",
+            Error::FileExists { .. } => "FileExists",
+            Error::Other { .. } => "Other",
+            Error::MalformedLock { .. } => "MalformedLock",
+            Error::MalformedConfig { .. } => "MalformedConfig",
+            Error::MissingConfig { .. } => "MissingConfig",
+            Error::NothingToBump { .. } => "NothingToBump",
+            Error::MultipleBumps { .. } => "MultipleBumps",
+            Error::IncorrectLockedType { .. } => "IncorrectLockedType",
+        }
+    }
+
+    /// The file or directory the error is about, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::ReadError { path, .. }
+            | Error::WriteError { path, .. }
+            | Error::MissingVenv { path }
+            | Error::VenvPythonMismatch { path, .. }
+            | Error::FileExists { path }
+            | Error::MalformedConfig { path, .. }
+            | Error::MissingConfig { path } => Some(path),
+            Error::ProcessFailed { working_path, .. }
+            | Error::ProcessTimedOut { working_path, .. } => Some(working_path),
+            Error::MissingSetupPy { project_path }
+            | Error::ProjectInstallFailed { project_path, .. } => Some(project_path),
+            Error::MissingLock { expected_path } => Some(expected_path),
+            _ => None,
+        }
+    }
+
+    /// Serialize the error as a JSON object, see `dmenv --json-errors`
+    // Note: paths are converted lossily, since serde refuses to
+    // serialize paths that are not valid UTF-8
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::json!({
+            "kind": self.kind(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        });
+        if let Some(path) = self.path() {
+            json["path"] = serde_json::Value::from(path.to_string_lossy());
+        }
+        json.to_string()
+    }
+}
+
 pub fn new_error(message: String) -> Error {
     Error::Other { message }
 }
@@ -113,7 +232,6 @@ impl std::fmt::Display for Error {
                 format!("could not get current working directory: {}", io_error)
             }
 
-
             Error::StartProcessError { message } => format!("could not start process: {}", message),
             Error::WaitProcessError { io_error } => {
                 format!("could not wait for process: {}", io_error)
@@ -121,15 +239,45 @@ impl std::fmt::Display for Error {
             Error::GetProcessOutputError { io_error } => {
                 format!("could not get process output: {}", io_error)
             }
+            Error::ProcessFailed {
+                name,
+                exit_code,
+                working_path,
+            } => {
+                let message = match exit_code {
+                    Some(code) => format!("`{}` failed with exit code {}", name, code),
+                    None => format!("`{}` was terminated by a signal", name),
+                };
+                format!("{} (in {})", message, working_path.display())
+            }
+            Error::ProcessTimedOut {
+                name,
+                timeout,
+                working_path,
+            } => format!(
+                "`{}` killed after {} second(s) (in {})",
+                name,
+                timeout.as_secs(),
+                working_path.display()
+            ),
 
             Error::RunInfoPyError { message } => {
                 format!("could not determine Python version and platform while running the `info.py` script: {}",
                       message)
-            },
-
-            Error::MissingSetupPy {} => {
-                "setup.py not found.\nYou may want to run `dmenv init` now".to_string()
             }
+
+            Error::MissingSetupPy { project_path } => format!(
+                "setup.py not found in {}.\nYou may want to run `dmenv init` now",
+                project_path.display()
+            ),
+            Error::ProjectInstallFailed {
+                project_path,
+                stderr_tail,
+            } => format!(
+                "Could not install the project in {}:\n{}",
+                project_path.display(),
+                stderr_tail
+            ),
             Error::MissingLock { expected_path } => format!(
                 "{} not found.\nYou may want to run `dmenv lock` now",
                 expected_path.display()
@@ -140,9 +288,37 @@ impl std::fmt::Display for Error {
                 message
             }
 
+            Error::VenvPythonMismatch {
+                path,
+                venv_version,
+                python_version,
+            } => {
+                let mut message = format!(
+                    "virtualenv in '{}' was created with Python {}, but the current Python version is {}\n",
+                    path.display(),
+                    venv_version,
+                    python_version
+                );
+                message.push_str("Please run `dmenv install --force` to re-create it");
+                message
+            }
+            Error::MissingExtra { name, available } => {
+                let mut message = format!("'{}' extra not found in setup.cfg\n", name);
+                if available.is_empty() {
+                    message
+                        .push_str("No extras are declared in the [options.extras_require] section");
+                } else {
+                    message.push_str(&format!("Available extras: {}", available.join(", ")));
+                }
+                message
+            }
+
             Error::ParsePipFreezeError { line } => {
                 format!("could not parse `pip freeze` output at line: '{}'", line)
             }
+            Error::ParsePipListError { details } => {
+                format!("could not parse `pip list` output: {}", details)
+            }
             Error::UpgradePipError {} => {
                 "could not upgrade pip. Try using `dmenv clean`".to_string()
             }
@@ -150,6 +326,12 @@ impl std::fmt::Display for Error {
             Error::FileExists { path } => format!("{} already exists", path.display()),
 
             Error::MalformedLock { details } => format!("Malformed lock: {}", details),
+            Error::MalformedConfig { path, details } => {
+                format!("Malformed config in {}: {}", path.display(), details)
+            }
+            Error::MissingConfig { path } => {
+                format!("Config file {} does not exist", path.display())
+            }
 
             Error::NothingToBump { name } => format!("'{}' not found in lock", name),
             Error::MultipleBumps { name } => {
@@ -184,4 +366,83 @@ mod tests {
         fn assert_sync<T: Sync>() {}
         assert_sync::<Error>();
     }
+
+    #[test]
+    fn exit_code_of_failed_process() {
+        let error = Error::ProcessFailed {
+            name: "pip".to_string(),
+            exit_code: Some(2),
+            working_path: PathBuf::from("/path/to/project"),
+        };
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(
+            error.to_string(),
+            "`pip` failed with exit code 2 (in /path/to/project)"
+        );
+    }
+
+    #[test]
+    fn exit_code_defaults_to_one() {
+        let error = Error::ProcessFailed {
+            name: "pip".to_string(),
+            exit_code: None,
+            working_path: PathBuf::from("/path/to/project"),
+        };
+        assert_eq!(error.exit_code(), 1);
+        assert_eq!(new_error("oops".to_string()).exit_code(), 1);
+    }
+
+    #[test]
+    fn missing_setup_py_contains_project_path() {
+        let error = Error::MissingSetupPy {
+            project_path: PathBuf::from("/path/to/project"),
+        };
+        assert!(error.to_string().contains("/path/to/project"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let error = Error::ProcessFailed {
+            name: "pip".to_string(),
+            exit_code: Some(2),
+            working_path: PathBuf::from("/path/to/project"),
+        };
+        let json: serde_json::Value = serde_json::from_str(&error.to_json()).unwrap();
+        assert_eq!(json["kind"], "ProcessFailed");
+        assert_eq!(
+            json["message"],
+            "`pip` failed with exit code 2 (in /path/to/project)"
+        );
+        assert_eq!(json["exit_code"], 2);
+        assert_eq!(json["path"], "/path/to/project");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&new_error("oops".to_string()).to_json()).unwrap();
+        assert_eq!(json["kind"], "Other");
+        assert!(json.get("path").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_to_json_with_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/tmp/\xff"));
+        let error = Error::MissingLock {
+            expected_path: path.to_path_buf(),
+        };
+        let json: serde_json::Value = serde_json::from_str(&error.to_json()).unwrap();
+        assert_eq!(json["path"], "/tmp/\u{fffd}");
+    }
+
+    #[test]
+    fn project_install_failed_contains_stderr() {
+        let error = Error::ProjectInstallFailed {
+            project_path: PathBuf::from("/path/to/project"),
+            stderr_tail: "SyntaxError: invalid syntax".to_string(),
+        };
+        let message = error.to_string();
+        assert!(message.contains("Could not install the project"));
+        assert!(message.ends_with("SyntaxError: invalid syntax"));
+    }
 }
@@ -1,4 +1,7 @@
 import platform
+import sys
 
 print(platform.python_version())
 print(platform.system())
+print(sys.executable)
+print(platform.python_implementation())
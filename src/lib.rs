@@ -1,6 +1,8 @@
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 
 mod cli;
+mod config;
 mod dependencies;
 mod error;
 #[cfg(unix)]
@@ -16,22 +18,26 @@ mod ui;
 mod win_job;
 
 use crate::cli::commands;
-use crate::cli::syntax::SubCommand;
+use crate::cli::syntax::{split_commands, SubCommand};
+use crate::operations::InitOptions;
 use crate::paths::{Paths, PathsResolver};
 use crate::python_info::PythonInfo;
 use crate::run::VenvRunner;
 
 pub use crate::cli::syntax::Command;
+pub use crate::dependencies::FrozenDependency;
 pub use crate::error::*;
 pub use crate::paths::{DEV_LOCK_FILENAME, PROD_LOCK_FILENAME};
 pub use crate::settings::Settings;
-pub use crate::ui::{print_error, print_info_1, print_info_2};
+pub use crate::ui::{print_error, print_info_1, print_info_2, ColorChoice, Verbosity};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Metadata {
     pub dmenv_version: String,
     pub python_platform: String,
     pub python_version: String,
+    // None for locks generated by older versions of dmenv
+    pub python_implementation: Option<String>,
 }
 
 #[derive(Debug)]
@@ -46,6 +52,33 @@ pub enum ProcessScriptsMode {
     Override,
 }
 
+/// How to display the output of `show:*` commands
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Shell syntax used by `dmenv show:venv:activate`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Shell {
+    Posix,
+    Fish,
+    Csh,
+    PowerShell,
+    Cmd,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Posix
+        }
+    }
+}
+
 pub enum BumpType {
     Git,
     Simple,
@@ -57,6 +90,20 @@ pub enum BumpType {
 pub struct UpdateLockOptions {
     pub python_version: Option<String>,
     pub sys_platform: Option<String>,
+    pub hashes: bool,
+    pub output: Option<PathBuf>,
+    pub upgrade: Vec<String>,
+}
+
+#[derive(Default, Debug)]
+/// Represents options passed to `dmenv run`,
+/// see `cmd::SubCommand::Run`
+pub struct RunOptions {
+    pub no_venv_check: bool,
+    pub env: Vec<(String, String)>,
+    pub cwd: Option<PathBuf>,
+    pub timeout: Option<std::time::Duration>,
+    pub capture: bool,
 }
 
 #[derive(Debug)]
@@ -67,18 +114,52 @@ pub struct Context {
     venv_runner: VenvRunner,
 }
 
-fn get_context(cmd: &Command) -> Result<Context, Error> {
-    let project_path = if let Some(p) = &cmd.project_path {
-        PathBuf::from(p)
+fn get_project_path(cmd: &Command) -> Result<PathBuf, Error> {
+    match &cmd.project_path {
+        Some(p) => Ok(PathBuf::from(p)),
+        None => look_up_for_project_path(),
+    }
+}
+
+/// Build the settings from the configuration of the project, the environment
+/// variables and the command line options, see `Settings::new()`
+fn get_settings(cmd: &Command) -> Result<Settings, Error> {
+    let project_path = get_project_path(cmd)?;
+    let config_path = cmd.config_path.as_ref().map(PathBuf::from);
+    let config = config::read_config(&project_path, config_path.as_deref())?;
+    Ok(Settings::new(cmd, &config))
+}
+
+/// Return the Python interpreter to use: `settings.python` (from `--python`
+/// or the configuration), or the one from the `.python-version` file of the
+/// project if `use_python_version_file` is set.
+/// None means the default interpreter, see `PythonInfo::new()`
+fn get_python_binary(project_path: &Path, settings: &Settings) -> Result<Option<String>, Error> {
+    if settings.python.is_some() {
+        Ok(settings.python.clone())
+    } else if settings.use_python_version_file {
+        python_info::python_from_version_file(project_path)
     } else {
-        look_up_for_project_path()?
-    };
-    let python_info = PythonInfo::new(&cmd.python_binary)?;
+        Ok(None)
+    }
+}
+
+fn get_context(cmd: &Command, settings: Settings) -> Result<Context, Error> {
+    let project_path = get_project_path(cmd)?;
+    let python_binary = get_python_binary(&project_path, &settings)?;
+    let python_info = PythonInfo::new(&python_binary, settings.use_cache)?;
     let python_version = python_info.version.clone();
-    let settings = Settings::from_shell(&cmd);
-    let paths_resolver = PathsResolver::new(project_path.clone(), python_version, &settings);
+    let mut paths_resolver = PathsResolver::new(project_path.clone(), python_version, &settings);
+    paths_resolver.set_python_platform(&python_info.platform);
     let paths = paths_resolver.paths()?;
-    let venv_runner = VenvRunner::new(&project_path, &paths.venv);
+    log::debug!("Using python interpreter: {}", python_info.binary.display());
+    log::debug!("Using virtualenv: {}", paths.venv.display());
+    if let Some(proxy) = &settings.proxy {
+        log::debug!("Using proxy: {}", ui::mask_credentials(proxy));
+    }
+    let mut venv_runner = VenvRunner::new(&project_path, &paths.venv);
+    venv_runner.set_dry_run(settings.dry_run);
+    venv_runner.set_progress(settings.progress);
     Ok(Context {
         paths,
         python_info,
@@ -88,8 +169,9 @@ fn get_context(cmd: &Command) -> Result<Context, Error> {
 }
 
 fn look_up_for_project_path() -> Result<PathBuf, Error> {
-    let mut candidate = std::env::current_dir()
+    let working_dir = std::env::current_dir()
         .map_err(|e| new_error(format!("Could not get current directory: {}", e)))?;
+    let mut candidate = working_dir.clone();
     loop {
         let setup_py_path = candidate.join("setup.py");
         if setup_py_path.exists() {
@@ -98,9 +180,10 @@ fn look_up_for_project_path() -> Result<PathBuf, Error> {
             let parent = candidate.parent();
             match parent {
                 None => {
-                    return Err(new_error(
-                        "Could not find setup.py in any of the parent directories".to_string(),
-                    ))
+                    return Err(new_error(format!(
+                        "Could not find setup.py in {} or any of its parent directories",
+                        working_dir.display()
+                    )))
                 }
                 Some(p) => candidate = p.to_path_buf(),
             }
@@ -108,29 +191,134 @@ fn look_up_for_project_path() -> Result<PathBuf, Error> {
     }
 }
 
-pub fn run_cmd(cmd: Command) -> Result<(), Error> {
-    let context = get_context(&cmd);
+/// Parse the lock file at the given path into a list of frozen dependencies
+/// (names, versions and hashes), for tools that use dmenv as a library.
+// Note: pkg-resources is filtered out, just like when running `pip freeze`.
+// Git dependencies use their git reference as version
+pub fn parse_lock(lock_path: &Path) -> Result<Vec<FrozenDependency>, Error> {
+    operations::lock::read_frozen(lock_path)
+}
+
+/// Return the dependencies `dmenv lock` would write for the project, without
+/// writing the lock, so that they can be inspected or transformed first.
+/// Like `dmenv lock`, this creates the virtualenv if needed and installs the project in it.
+// Note: `cmd` is used for the global options (like `--project` or `--python`),
+// its sub-command is ignored
+pub fn frozen_dependencies(
+    cmd: &Command,
+    update_options: &UpdateLockOptions,
+) -> Result<Vec<FrozenDependency>, Error> {
+    let settings = get_settings(cmd)?;
+    let context = get_context(cmd, settings)?;
+    commands::frozen_dependencies(&context, update_options)
+}
+
+/// When `--project` is a git URL, clone the repository and use the
+/// clone (or its `#subdirectory=`) as the project path. The clone is removed
+/// when the returned TempDir is dropped, unless `--keep` is used
+fn clone_project(cmd: &mut Command) -> Result<Option<tempdir::TempDir>, Error> {
+    let url = match &cmd.project_path {
+        Some(project) if operations::clone::is_git_url(project) => project.clone(),
+        _ => return Ok(None),
+    };
+    let tmp_dir = operations::clone::clone(&url, cmd.dry_run)?;
+    let mut project_path = tmp_dir.path().to_path_buf();
+    if let Some(subdirectory) = operations::clone::subdirectory(&url) {
+        project_path = project_path.join(subdirectory);
+    }
+    cmd.project_path = Some(project_path.to_string_lossy().to_string());
+    if cmd.keep {
+        let clone_path = tmp_dir.into_path();
+        print_info_1(&format!("Keeping clone in {}", clone_path.display()));
+        return Ok(None);
+    }
+    Ok(Some(tmp_dir))
+}
+
+pub fn run_cmd(mut cmd: Command) -> Result<(), Error> {
+    // Note: the clone must outlive the context. When `dmenv run` replaces
+    // the process with execv(), it is not removed
+    let _clone = clone_project(&mut cmd)?;
+    // Note: when the project cannot be found (like for `dmenv init`), or when
+    // its configuration is invalid, the settings come from the command line
+    // and the environment only, and the error is reported by the commands
+    // that need a context
+    let (settings, settings_error) = match get_settings(&cmd) {
+        Ok(settings) => (settings, None),
+        Err(e) => (Settings::from_shell(&cmd), Some(e)),
+    };
+    ui::set_verbosity(settings.verbosity);
+    ui::init_logger();
+    ui::set_color_choice(settings.color);
+    let context = match settings_error {
+        None => get_context(&cmd, settings.clone()),
+        Some(e) => Err(e),
+    };
 
     match &cmd.sub_cmd {
         SubCommand::Init {
             name,
             version,
             author,
+            author_email,
+            license,
             no_setup_cfg,
-        } => commands::init(cmd.project_path, name, version, author, !no_setup_cfg),
+            from_requirements,
+            ..
+        } => {
+            let mut init_options = InitOptions::new(name.to_string(), version.to_string());
+            if *no_setup_cfg {
+                init_options.no_setup_cfg();
+            }
+            if let Some(author) = author {
+                init_options.author(author);
+            }
+            if let Some(author_email) = author_email {
+                init_options.author_email(author_email);
+            }
+            if let Some(license) = license {
+                init_options.license(license);
+            }
+            commands::init(
+                cmd.project_path,
+                init_options,
+                from_requirements.as_ref().map(Path::new),
+            )
+        }
 
-        SubCommand::Install { no_develop } => {
+        SubCommand::Install {
+            no_develop, force, ..
+        } => {
             let post_install_action = if *no_develop {
                 PostInstallAction::None
             } else {
                 PostInstallAction::RunSetupPyDevelop
             };
-            commands::install(&context?, post_install_action)
+            if *force {
+                commands::force_install(&cmd, context?, post_install_action)
+            } else {
+                commands::install(&context?, post_install_action)
+            }
         }
 
+        SubCommand::Bin { name } => commands::show_bin(&context?, name),
+
+        SubCommand::Check {} => commands::check(&context?),
         SubCommand::Create {} => commands::create_venv(&context?),
-        SubCommand::Clean {} => commands::clean_venv(context?),
+        SubCommand::Clean { all: false } => commands::clean_venv(context?),
+        SubCommand::Clean { all: true } => commands::clean_all_venvs(&context?),
         SubCommand::Develop {} => commands::develop(&context?),
+        SubCommand::Doctor {} => commands::doctor(&context?),
+        SubCommand::SelfCheck {} => {
+            // Note: the templates can be checked outside a project, using
+            // the Python interpreter from the command line in this case
+            let python_binary = match get_project_path(&cmd) {
+                Ok(project_path) => get_python_binary(&project_path, &settings)?,
+                Err(_) => settings.python.clone(),
+            };
+            commands::selfcheck(&python_binary, settings.use_cache)
+        }
+        SubCommand::EnvList {} => commands::list_venvs(&context?),
         SubCommand::UpgradePip {} => commands::upgrade_pip(&context?),
 
         SubCommand::ProcessScripts { force } => {
@@ -142,17 +330,49 @@ pub fn run_cmd(cmd: Command) -> Result<(), Error> {
             commands::process_scripts(&context?, mode)
         }
 
+        SubCommand::ProcessShebangs { mode } => commands::process_shebangs(&context?, *mode),
+
         SubCommand::Lock {
             python_version,
             sys_platform,
+            hashes,
+            output,
+            upgrade,
+            ..
         } => {
             let update_options = UpdateLockOptions {
                 python_version: python_version.clone(),
                 sys_platform: sys_platform.clone(),
+                hashes: *hashes,
+                output: output.as_ref().map(PathBuf::from),
+                upgrade: upgrade.clone(),
             };
             commands::update_lock(&context?, update_options)
         }
 
+        SubCommand::LockDiff { other, base } => {
+            let other = PathBuf::from(other);
+            let base = base.as_ref().map(PathBuf::from);
+            commands::diff_lock(&context?, &other, base.as_deref())
+        }
+
+        SubCommand::LockMerge {
+            other,
+            prefer_newer,
+        } => commands::merge_lock(&context?, Path::new(other), *prefer_newer),
+
+        SubCommand::LockAdd { spec } => commands::add_to_lock(&context?, spec),
+
+        SubCommand::LockPrune { names } => commands::prune_lock(&context?, names),
+
+        SubCommand::LockVerifyHashes {} => commands::verify_hashes(&context?),
+
+        SubCommand::LockCheckDuplicates {} => commands::check_duplicates(&context?),
+        SubCommand::LockStats { format } => commands::show_lock_stats(&context?, *format),
+
+        SubCommand::LockSort {} => commands::sort_lock(&context?),
+        SubCommand::LockPinPython {} => commands::pin_python(&context?),
+
         SubCommand::BumpInLock { name, version, git } => {
             let bump_type = if *git {
                 BumpType::Git
@@ -162,20 +382,64 @@ pub fn run_cmd(cmd: Command) -> Result<(), Error> {
             commands::bump_in_lock(&context?, name, version, bump_type)
         }
 
-        SubCommand::Run { ref cmd, no_exec } => {
-            if *no_exec {
-                commands::run(&context?, &cmd)
+        SubCommand::Run {
+            ref cmd,
+            no_exec,
+            no_venv_check,
+            env,
+            cwd,
+            timeout,
+            echo_only,
+            capture,
+            list,
+        } => {
+            let run_options = RunOptions {
+                no_venv_check: *no_venv_check,
+                env: env.clone(),
+                cwd: cwd.as_ref().map(PathBuf::from),
+                timeout: timeout.map(std::time::Duration::from_secs),
+                capture: *capture,
+            };
+            if *list {
+                commands::list_binaries(&context?)
+            } else if *echo_only {
+                commands::echo_run(&context?, cmd, &run_options)
+            } else if *no_exec {
+                commands::run(&context?, cmd, &run_options)
             } else {
-                commands::run_and_die(&context?, &cmd)
+                commands::run_and_die(&context?, cmd, &run_options)
             }
         }
 
-        SubCommand::ShowDeps {} => commands::show_deps(&context?),
-        SubCommand::ShowOutDated {} => commands::show_outdated(&context?),
+        SubCommand::RunAll { keep_going, args } => {
+            commands::run_all(&context?, &split_commands(args), *keep_going)
+        }
+
+        SubCommand::ShowDeps { format, tree } => commands::show_deps(&context?, *format, *tree),
+        SubCommand::ConfigShow { format } => commands::show_config(&context?, *format),
+        SubCommand::ShowPython { format } => commands::show_python(&context?, *format),
+        SubCommand::ShowOutDated { format } => commands::show_outdated(&context?, *format),
+        SubCommand::ShowLockPath {} => commands::show_lock_path(&context?),
+        SubCommand::ShowLock { format } => commands::show_lock(&context?, *format),
+        SubCommand::FreezeMetadata { format } => commands::freeze_metadata(&context?, *format),
         SubCommand::ShowVenvPath {} => commands::show_venv_path(&context?),
         SubCommand::ShowVenvBin {} => commands::show_venv_bin_path(&context?),
+        SubCommand::ShowVenvActivate { shell } => {
+            commands::show_venv_activate(&context?, shell.unwrap_or_default())
+        }
+        SubCommand::ShowVenvSize { by_dir } => commands::show_venv_size(&context?, *by_dir),
 
-        SubCommand::Tidy {} => commands::tidy(&cmd, context?),
+        SubCommand::Tidy {
+            allow_active_venv, ..
+        } => commands::tidy(&cmd, context?, *allow_active_venv),
+        SubCommand::LockUpgradeAll { allow_active_venv } => {
+            commands::upgrade_all(&cmd, context?, *allow_active_venv)
+        }
+        SubCommand::RecreateVenv {} => commands::recreate_venv(&cmd, context?),
+        SubCommand::RelocateVenv { path } => {
+            let path = path.as_ref().map(PathBuf::from);
+            commands::relocate_venv(&context?, path.as_deref())
+        }
     }
 }
 
@@ -57,7 +57,7 @@ mod tests {
     #[test]
     fn simple_change() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
+        let mut deps = parse(lock_contents).unwrap();
         let changed = simple_bump(&mut deps, "foo", "0.43").unwrap();
         assert!(changed);
         let actual = dump(deps);
@@ -67,7 +67,7 @@ mod tests {
     #[test]
     fn simple_no_change() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
+        let mut deps = parse(lock_contents).unwrap();
         let changed = simple_bump(&mut deps, "foo", "0.42").unwrap();
         assert!(!changed);
         let actual = dump(deps);
@@ -77,7 +77,7 @@ mod tests {
     #[test]
     fn dep_not_found() {
         let lock_contents = "bar==0.3\nfoo==0.42\n";
-        let mut deps = parse(&lock_contents).unwrap();
+        let mut deps = parse(lock_contents).unwrap();
         let actual_error = simple_bump(&mut deps, "no-such", "1.2");
         match actual_error {
             Err(Error::NothingToBump { name }) => assert_eq!(name, "no-such"),
@@ -88,7 +88,7 @@ mod tests {
     #[test]
     fn bump_git_ref() {
         let lock_contents = "git@example.com/bar.git@dae42f#egg=bar\n";
-        let mut deps = parse(&lock_contents).unwrap();
+        let mut deps = parse(lock_contents).unwrap();
         let changed = git_bump(&mut deps, "bar", "cda431").unwrap();
         assert!(changed);
         let actual = dump(deps);
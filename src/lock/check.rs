@@ -0,0 +1,121 @@
+use crate::dependencies::{FrozenDependency, LockedDependency};
+
+/// A locked dependency that does not match what is installed
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    Missing {
+        name: String,
+        locked_version: String,
+    },
+    Different {
+        name: String,
+        locked_version: String,
+        installed_version: String,
+    },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::Missing {
+                name,
+                locked_version,
+            } => write!(f, "{}: not installed (locked: {})", name, locked_version),
+            Mismatch::Different {
+                name,
+                locked_version,
+                installed_version,
+            } => write!(
+                f,
+                "{}: {} installed (locked: {})",
+                name, installed_version, locked_version
+            ),
+        }
+    }
+}
+
+/// Compare the locked dependencies with the ones actually installed
+//
+// Notes:
+// * git dependencies are skipped, since `pip freeze` only knows about
+//   versions, not git references
+// * missing dependencies with an environment marker are skipped too, since
+//   they are probably meant for an other platform or Python version
+pub fn check(
+    locked_dependencies: &[LockedDependency],
+    frozen_dependencies: &[FrozenDependency],
+) -> Vec<Mismatch> {
+    let mut res = vec![];
+    for locked in locked_dependencies {
+        let simple = match locked {
+            LockedDependency::Git(_) => continue,
+            LockedDependency::Simple(s) => s,
+        };
+        let name = &simple.name;
+        let locked_version = &simple.version.value;
+        let frozen = frozen_dependencies
+            .iter()
+            .find(|x| x.name.to_lowercase() == name.to_lowercase());
+        match frozen {
+            None if simple.has_marker() => (),
+            None => res.push(Mismatch::Missing {
+                name: name.to_string(),
+                locked_version: locked_version.to_string(),
+            }),
+            Some(frozen) if &frozen.version != locked_version => res.push(Mismatch::Different {
+                name: name.to_string(),
+                locked_version: locked_version.to_string(),
+                installed_version: frozen.version.to_string(),
+            }),
+            Some(_) => (),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    fn frozen(name: &str, version: &str) -> FrozenDependency {
+        FrozenDependency::from_string(format!("{}=={}", name, version)).unwrap()
+    }
+
+    #[test]
+    fn in_sync() {
+        let locked = parse("bar==0.3\nfoo==0.42\n").unwrap();
+        let mismatches = check(&locked, &[frozen("bar", "0.3"), frozen("foo", "0.42")]);
+        assert!(mismatches.is_empty());
+    }
+
+    #[test]
+    fn missing_and_different() {
+        let locked = parse("bar==0.3\nfoo==0.42\n").unwrap();
+        let mismatches = check(&locked, &[frozen("foo", "0.43")]);
+        assert_eq!(
+            mismatches,
+            &[
+                Mismatch::Missing {
+                    name: "bar".to_string(),
+                    locked_version: "0.3".to_string()
+                },
+                Mismatch::Different {
+                    name: "foo".to_string(),
+                    locked_version: "0.42".to_string(),
+                    installed_version: "0.43".to_string(),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_git_and_markers() {
+        let locked = parse(
+            "git@example.com/bar.git@dae42f#egg=bar\nwinapi==0.3 ; sys_platform == 'win32'\n",
+        )
+        .unwrap();
+        let mismatches = check(&locked, &[frozen("bar", "1.0")]);
+        assert!(mismatches.is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::dependencies::FrozenDependency;
+
+/// Semantic differences between two locks
+#[derive(Debug, Default)]
+pub struct LockDiff {
+    pub added: Vec<FrozenDependency>,
+    pub removed: Vec<FrozenDependency>,
+    pub changed: Vec<VersionChange>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VersionChange {
+    pub name: String,
+    pub old_version: String,
+    pub new_version: String,
+}
+
+impl LockDiff {
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Compute what changed when going from `base` to `other`.
+// Note: names are compared case-insensitively, and results are
+// sorted by name, like in the lock files
+pub fn diff(base: Vec<FrozenDependency>, other: Vec<FrozenDependency>) -> LockDiff {
+    let find = |deps: &[FrozenDependency], name: &str| {
+        deps.iter()
+            .position(|x| x.name.to_lowercase() == name.to_lowercase())
+    };
+    let mut res = LockDiff::default();
+    let mut other = other;
+    for base_dep in base {
+        match find(&other, &base_dep.name) {
+            None => res.removed.push(base_dep),
+            Some(index) => {
+                let other_dep = other.remove(index);
+                if other_dep.version != base_dep.version {
+                    res.changed.push(VersionChange {
+                        name: base_dep.name,
+                        old_version: base_dep.version,
+                        new_version: other_dep.version,
+                    });
+                }
+            }
+        }
+    }
+    res.added = other;
+    res.added.sort_by_key(|x| x.name.to_lowercase());
+    res.removed.sort_by_key(|x| x.name.to_lowercase());
+    res.changed.sort_by_key(|x| x.name.to_lowercase());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse_frozen;
+
+    fn diff_locks(base: &str, other: &str) -> LockDiff {
+        let base = parse_frozen(base).unwrap();
+        let other = parse_frozen(other).unwrap();
+        diff(base, other)
+    }
+
+    #[test]
+    fn no_differences() {
+        let lock_diff = diff_locks("bar==0.3\nfoo==0.42\n", "foo==0.42\nbar==0.3\n");
+        assert!(lock_diff.is_empty());
+    }
+
+    #[test]
+    fn added_removed_and_changed() {
+        let lock_diff = diff_locks(
+            "bar==0.3\nfoo==0.42\n",
+            "baz==1.0\nfoo==0.43\npkg-resources==0.0.0\n",
+        );
+        assert_eq!(lock_diff.len(), 3);
+        assert_eq!(lock_diff.added[0].name, "baz");
+        assert_eq!(lock_diff.removed[0].name, "bar");
+        assert_eq!(
+            lock_diff.changed[0],
+            VersionChange {
+                name: "foo".to_string(),
+                old_version: "0.42".to_string(),
+                new_version: "0.43".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let lock_diff = diff_locks("PyYAML==5.1\n", "pyyaml==5.1\n");
+        assert!(lock_diff.is_empty());
+    }
+}
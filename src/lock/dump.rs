@@ -7,11 +7,33 @@ where
     // Dependencies are sorted according to their *lowercase* name.
     // This is consistent with how `pip freeze` is implemented.
     // See bottom of pip/_internal/operations/freeze.py:freeze()
-    let mut lines: Vec<_> = locked_dependencies.into_iter().map(|x| x.line()).collect();
-    lines.sort_by(|x, y| x.to_lowercase().cmp(&y.to_lowercase()));
+    // Note: comments are written above each dependency, but are not
+    // taken into account when sorting
+    let mut deps: Vec<_> = locked_dependencies.into_iter().collect();
+    deps.sort_by_key(|x| x.line().to_lowercase());
+    let lines: Vec<_> = deps
+        .iter()
+        .map(|x| format!("{}{}", x.comment(), x.line()))
+        .collect();
     let mut res = lines.join("");
     if !res.ends_with('\n') {
         res.push('\n');
     }
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    #[test]
+    fn keep_comments_when_sorting() {
+        let text = "# pinned for CVE-2019-42\nfoo==0.42\nbar==1.3\n";
+        let deps = parse(text).unwrap();
+        assert_eq!(
+            dump(deps),
+            "bar==1.3\n# pinned for CVE-2019-42\nfoo==0.42\n"
+        );
+    }
+}
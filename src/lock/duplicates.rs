@@ -0,0 +1,97 @@
+use crate::dependencies::{normalize_name, FrozenDependency, LockedDependency};
+
+/// A package that appears more than once in the lock
+#[derive(Debug, PartialEq)]
+pub struct Duplicate {
+    pub name: String,
+    pub versions: Vec<String>,
+}
+
+impl std::fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.versions.join(", "))
+    }
+}
+
+/// Find the packages appearing more than once in the lock, in order
+/// of first appearance.
+//
+// Notes:
+// * names are compared after normalization, so `Foo_Bar` and `foo-bar`
+//   are the same package
+// * the same package with different environment markers (like one version
+//   for Python 2 and an other for Python 3) is not a duplicate
+pub fn find_duplicates(locked_dependencies: &[LockedDependency]) -> Vec<Duplicate> {
+    let mut seen: Vec<(String, Option<&str>, Duplicate)> = vec![];
+    for locked in locked_dependencies {
+        let marker = match locked {
+            LockedDependency::Simple(s) => s.marker(),
+            LockedDependency::Git(_) => None,
+        };
+        let frozen = FrozenDependency::from_locked(locked);
+        let key = normalize_name(&frozen.name);
+        let existing = seen.iter_mut().find(|(k, m, _)| *k == key && *m == marker);
+        match existing {
+            Some((_, _, duplicate)) => duplicate.versions.push(frozen.version),
+            None => seen.push((
+                key,
+                marker,
+                Duplicate {
+                    name: frozen.name,
+                    versions: vec![frozen.version],
+                },
+            )),
+        }
+    }
+    seen.into_iter()
+        .map(|(_, _, duplicate)| duplicate)
+        .filter(|x| x.versions.len() > 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    #[test]
+    fn test_find_duplicates() {
+        let text = "\
+foo==0.42
+bar==1.3
+Foo==0.43
+git+https://github.com/acme/baz.git@v1.0#egg=baz
+baz==1.1
+";
+        let deps = parse(text).unwrap();
+        let duplicates = find_duplicates(&deps);
+        assert_eq!(
+            duplicates,
+            &[
+                Duplicate {
+                    name: "foo".to_string(),
+                    versions: vec!["0.42".to_string(), "0.43".to_string()],
+                },
+                Duplicate {
+                    name: "baz".to_string(),
+                    versions: vec!["v1.0".to_string(), "1.1".to_string()],
+                },
+            ]
+        );
+        assert_eq!(duplicates[0].to_string(), "foo: 0.42, 0.43");
+    }
+
+    #[test]
+    fn test_different_markers_are_not_duplicates() {
+        let text = "\
+foo==0.42 ; python_version < '3.6'
+foo==0.43 ; python_version >= '3.6'
+bar==1.3 ; sys_platform == 'win32'
+bar==1.4 ; sys_platform == 'win32'
+";
+        let deps = parse(text).unwrap();
+        let duplicates = find_duplicates(&deps);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "bar");
+    }
+}
@@ -0,0 +1,429 @@
+use std::cmp::Ordering;
+
+use crate::dependencies::{normalize_name, LockedDependency};
+
+/// A package locked at different versions in the two locks
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub name: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {} vs {}", self.name, self.ours, self.theirs)
+    }
+}
+
+/// Result of merging two locks
+#[derive(Debug, Default)]
+pub struct Merge {
+    pub dependencies: Vec<LockedDependency>,
+    /// Conflicts that could not be resolved
+    pub conflicts: Vec<Conflict>,
+    /// Conflicts resolved by taking the newer version, when `prefer_newer` is true
+    pub resolved: Vec<Conflict>,
+}
+
+/// Compute the union of the dependencies of two locks.
+///
+/// When a package is in both locks with the same version, the line from
+/// `ours` is kept (including its comment). When the versions differ, this is a
+/// conflict, unless `prefer_newer` is true: in that case the higher version is used.
+// Notes:
+// * names are compared after normalization, see PEP 503
+// * git references cannot be ordered, so conflicts between git
+//   dependencies are never resolved. Neither are conflicts involving
+//   a version that is not valid according to PEP 440
+pub fn merge(
+    ours: Vec<LockedDependency>,
+    theirs: Vec<LockedDependency>,
+    prefer_newer: bool,
+) -> Merge {
+    let mut res = Merge::default();
+    let mut theirs = theirs;
+    for our_dep in ours {
+        let name = normalize_name(&our_dep.name());
+        let position = theirs
+            .iter()
+            .position(|x| normalize_name(&x.name()) == name);
+        let their_dep = match position {
+            None => {
+                res.dependencies.push(our_dep);
+                continue;
+            }
+            Some(index) => theirs.remove(index),
+        };
+        let ours_version = our_dep.version();
+        let theirs_version = their_dep.version();
+        if ours_version == theirs_version {
+            res.dependencies.push(our_dep);
+            continue;
+        }
+        let conflict = Conflict {
+            name: our_dep.name(),
+            ours: ours_version,
+            theirs: theirs_version,
+        };
+        let both_simple = matches!(
+            (&our_dep, &their_dep),
+            (LockedDependency::Simple(_), LockedDependency::Simple(_))
+        );
+        if !prefer_newer || !both_simple {
+            res.dependencies.push(our_dep);
+            res.conflicts.push(conflict);
+            continue;
+        }
+        match compare_versions(&conflict.ours, &conflict.theirs) {
+            None => {
+                res.dependencies.push(our_dep);
+                res.conflicts.push(conflict);
+                continue;
+            }
+            Some(Ordering::Less) => res.dependencies.push(their_dep),
+            Some(_) => res.dependencies.push(our_dep),
+        }
+        res.resolved.push(conflict);
+    }
+    res.dependencies.extend(theirs);
+    res
+}
+
+/// Compare two versions according to PEP 440, like `1.10.0 > 1.10.0rc1 > 1.9.2`.
+/// Return None if any of them cannot be parsed.
+fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let a = Version::parse(a)?;
+    let b = Version::parse(b)?;
+    Some(a.cmp(&b))
+}
+
+// Note: the order of the variants of the enums below matter, as it is
+// used by the derived implementations of `Ord`
+
+/// Pre-release part of a version.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum PreRelease {
+    /// Developmental releases of final releases, like `1.0.dev1`, sort
+    /// before any pre-release, like `1.0a1`
+    DevOfFinal,
+    Alpha(u64),
+    Beta(u64),
+    ReleaseCandidate(u64),
+    Final,
+}
+
+/// Developmental part of a version: `1.0.dev1 < 1.0`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum DevRelease {
+    Dev(u64),
+    Final,
+}
+
+/// Segment of a local version label, like `ubuntu` or `1` in `1.0+ubuntu.1`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LocalSegment {
+    String(String),
+    Number(u64),
+}
+
+/// A version as specified by PEP 440.
+// Note: fields are declared in comparison order
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    epoch: u64,
+    /// Without trailing zeros, so that `1.0 == 1`
+    release: Vec<u64>,
+    pre: PreRelease,
+    post: Option<u64>,
+    dev: DevRelease,
+    local: Option<Vec<LocalSegment>>,
+}
+
+impl Version {
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().to_lowercase();
+        let version = version.strip_prefix('v').unwrap_or(&version);
+
+        let (version, local) = match version.split_once('+') {
+            None => (version, None),
+            Some((version, local)) => (version, Some(parse_local(local)?)),
+        };
+
+        let (epoch, rest) = match version.split_once('!') {
+            None => (0, version),
+            Some((epoch, rest)) => (epoch.parse().ok()?, rest),
+        };
+
+        let mut release = vec![];
+        let mut rest = rest;
+        loop {
+            let (number, remaining) = take_number(rest)?;
+            release.push(number);
+            rest = remaining;
+            match rest.strip_prefix('.') {
+                Some(remaining) if remaining.starts_with(|c: char| c.is_ascii_digit()) => {
+                    rest = remaining
+                }
+                _ => break,
+            }
+        }
+        while release.len() > 1 && release.last() == Some(&0) {
+            release.pop();
+        }
+
+        let mut pre = None;
+        for (labels, make) in &[
+            (
+                &["alpha", "a"][..],
+                PreRelease::Alpha as fn(u64) -> PreRelease,
+            ),
+            (&["beta", "b"][..], PreRelease::Beta),
+            (
+                &["preview", "pre", "rc", "c"][..],
+                PreRelease::ReleaseCandidate,
+            ),
+        ] {
+            if let Some((number, remaining)) = take_suffix(rest, labels) {
+                pre = Some(make(number));
+                rest = remaining;
+                break;
+            }
+        }
+
+        let mut post = None;
+        if let Some((number, remaining)) = take_suffix(rest, &["post", "rev", "r"]) {
+            post = Some(number);
+            rest = remaining;
+        } else if let Some(remaining) = rest.strip_prefix('-') {
+            // Implicit post release, like `1.0-1`
+            let (number, remaining) = take_number(remaining)?;
+            post = Some(number);
+            rest = remaining;
+        }
+
+        let mut dev = DevRelease::Final;
+        if let Some((number, remaining)) = take_suffix(rest, &["dev"]) {
+            dev = DevRelease::Dev(number);
+            rest = remaining;
+        }
+
+        if !rest.is_empty() {
+            return None;
+        }
+
+        let pre = match (pre, post, &dev) {
+            (Some(pre), _, _) => pre,
+            (None, None, DevRelease::Dev(_)) => PreRelease::DevOfFinal,
+            (None, _, _) => PreRelease::Final,
+        };
+
+        Some(Version {
+            epoch,
+            release,
+            pre,
+            post,
+            dev,
+            local,
+        })
+    }
+}
+
+/// Separators allowed between the parts of a version, like in `1.0-rc.1`
+const SEPARATORS: [char; 3] = ['.', '-', '_'];
+
+/// Parse the number at the start of `input`, and return it
+/// with what remains.
+fn take_number(input: &str) -> Option<(u64, &str)> {
+    let end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let number = input[..end].parse().ok()?;
+    Some((number, &input[end..]))
+}
+
+/// Parse a suffix like `.post1`, `-rc.2` or `dev`, starting with one of
+/// the given labels, and return its number (0 when implicit) with what remains.
+fn take_suffix<'a>(input: &'a str, labels: &[&str]) -> Option<(u64, &'a str)> {
+    let input = input.trim_start_matches(SEPARATORS);
+    let label = labels.iter().find(|label| input.starts_with(*label))?;
+    let rest = &input[label.len()..];
+    let without_separator = rest.trim_start_matches(SEPARATORS);
+    match take_number(without_separator) {
+        Some((number, remaining)) => Some((number, remaining)),
+        None => Some((0, rest)),
+    }
+}
+
+fn parse_local(local: &str) -> Option<Vec<LocalSegment>> {
+    local
+        .split(SEPARATORS)
+        .map(|segment| {
+            if segment.is_empty() || !segment.chars().all(|c| c.is_ascii_alphanumeric()) {
+                None
+            } else if let Ok(number) = segment.parse() {
+                Some(LocalSegment::Number(number))
+            } else {
+                Some(LocalSegment::String(segment.to_string()))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::parse;
+
+    fn names_and_versions(merge: &Merge) -> Vec<String> {
+        merge
+            .dependencies
+            .iter()
+            .map(|x| format!("{}=={}", x.name(), x.version()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.0", "1.0.1"), Some(Ordering::Less));
+        assert_eq!(compare_versions("2019.3", "2019.3"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1.0", "1.0.0"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1!1.0", "2.0"), Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn test_compare_pre_releases() {
+        assert_eq!(compare_versions("1.0rc1", "1.0rc2"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.0rc1", "1.0"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.0a2", "1.0b1"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.0b2", "1.0rc1"), Some(Ordering::Less));
+        assert_eq!(
+            compare_versions("1.0-RC.1", "1.0rc1"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(compare_versions("1.0c1", "1.0rc1"), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_compare_post_releases() {
+        assert_eq!(
+            compare_versions("1.0.post1", "1.0"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_versions("1.0.post1", "1.0.post2"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_versions("1.0-1", "1.0.post1"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(compare_versions("1.0.post1", "1.0.1"), Some(Ordering::Less));
+        assert_eq!(
+            compare_versions("1.0rc1.post1", "1.0"),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn test_compare_dev_releases() {
+        assert_eq!(compare_versions("1.0.dev1", "1.0"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.0.dev1", "1.0a1"), Some(Ordering::Less));
+        assert_eq!(
+            compare_versions("1.0a1.dev1", "1.0a1"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_versions("1.0.post1.dev1", "1.0"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_versions("1.0.post1.dev1", "1.0.post1"),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn test_compare_local_versions() {
+        assert_eq!(compare_versions("1.0+cpu", "1.0"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.0+abc", "1.0+1"), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn test_compare_invalid_versions() {
+        assert_eq!(compare_versions("1.0", "latest"), None);
+        assert_eq!(compare_versions("1.0foo", "1.0"), None);
+    }
+
+    #[test]
+    fn test_union_without_conflicts() {
+        let ours = parse("# pinned\nfoo==1.0\nbar==2.0\n").unwrap();
+        let theirs = parse("Foo==1.0\nbaz==3.0\n").unwrap();
+        let merge = merge(ours, theirs, false);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(
+            names_and_versions(&merge),
+            &["foo==1.0", "bar==2.0", "baz==3.0"]
+        );
+        assert_eq!(merge.dependencies[0].comment(), "# pinned\n");
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let ours = parse("foo==1.10\nbar==2.0\n").unwrap();
+        let theirs = parse("foo==1.9\nbar==2.1\n").unwrap();
+        let merge = merge(ours, theirs, false);
+        assert_eq!(
+            merge.conflicts,
+            &[
+                Conflict {
+                    name: "foo".to_string(),
+                    ours: "1.10".to_string(),
+                    theirs: "1.9".to_string(),
+                },
+                Conflict {
+                    name: "bar".to_string(),
+                    ours: "2.0".to_string(),
+                    theirs: "2.1".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prefer_newer() {
+        let ours = parse("foo==1.10\nbar==2.0\n").unwrap();
+        let theirs = parse("foo==1.9\nbar==2.1\n").unwrap();
+        let merge = merge(ours, theirs, true);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.resolved.len(), 2);
+        assert_eq!(names_and_versions(&merge), &["foo==1.10", "bar==2.1"]);
+    }
+
+    #[test]
+    fn test_prefer_newer_with_pre_releases() {
+        let ours = parse("foo==2.0rc1\nbar==1.0.post1\n").unwrap();
+        let theirs = parse("foo==2.0\nbar==1.0\n").unwrap();
+        let merge = merge(ours, theirs, true);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(names_and_versions(&merge), &["foo==2.0", "bar==1.0.post1"]);
+    }
+
+    #[test]
+    fn test_invalid_versions_are_not_resolved() {
+        let ours = parse("foo==1.0\n").unwrap();
+        let theirs = parse("foo==nightly\n").unwrap();
+        let merge = merge(ours, theirs, true);
+        assert_eq!(merge.conflicts.len(), 1);
+        assert_eq!(names_and_versions(&merge), &["foo==1.0"]);
+    }
+
+    #[test]
+    fn test_git_conflicts_are_not_resolved() {
+        let ours = parse("git+https://host.tld/foo.git@v1#egg=foo\n").unwrap();
+        let theirs = parse("git+https://host.tld/foo.git@v2#egg=foo\n").unwrap();
+        let merge = merge(ours, theirs, true);
+        assert_eq!(merge.conflicts.len(), 1);
+    }
+}
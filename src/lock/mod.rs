@@ -1,9 +1,26 @@
 mod bump;
+mod check;
+mod diff;
 mod dump;
+mod duplicates;
+mod merge;
 mod parse;
+mod stats;
 mod update;
+mod verify;
 
 pub use bump::{git_bump, simple_bump};
+pub use check::check;
+pub use diff::diff;
 pub use dump::dump;
-pub use parse::{parse, parse_git_line, parse_line, parse_simple_line};
+pub use duplicates::find_duplicates;
+pub use merge::merge;
+pub use parse::{
+    parse, parse_frozen, parse_header, parse_metadata, parse_simple_line, parse_top_comment,
+    HEADER_PREFIX,
+};
+#[cfg(test)]
+pub use parse::{parse_git_line, parse_line};
+pub use stats::stats;
 pub use update::Updater;
+pub use verify::verify_hashes;
@@ -1,24 +1,123 @@
-use crate::dependencies::{GitDependency, LockedDependency, SimpleDependency, VersionSpec};
+use crate::dependencies::{
+    remove_pkg_resources, FrozenDependency, GitDependency, LockedDependency, SimpleDependency,
+    VersionSpec,
+};
 use crate::error::Error;
+use crate::Metadata;
 
+/// Start of the comment written by dmenv at the top of the lock
+pub const HEADER_PREFIX: &str = "# Generated with dmenv";
+
+/// Parse the lock contents into a list of LockedDependency.
+//
+// Comment lines right above a dependency are attached to it, so that
+// they are not lost when the lock is written back. A blank line ends
+// a block of comments.
 pub fn parse(text: &str) -> Result<Vec<LockedDependency>, Error> {
     let mut res = vec![];
-    let lines = split_logical_lines(&text);
+    let mut comment = String::new();
+    let lines = split_logical_lines(text);
     for line in lines.iter() {
-        if !line.starts_with('#') {
-            // skip comments
-            let locked_dependency = parse_line(line)?;
-            res.push(locked_dependency);
+        if line.trim().is_empty() {
+            comment.clear();
+            continue;
+        }
+        if line.starts_with('#') {
+            if !line.starts_with(HEADER_PREFIX) {
+                comment.push_str(line);
+            }
+            continue;
         }
+        let mut locked_dependency = parse_line(line)?;
+        locked_dependency.set_comment(&comment);
+        comment.clear();
+        res.push(locked_dependency);
     }
     Ok(res)
 }
 
+/// Return the block of comments at the top of the lock,
+/// followed by a blank line (or an empty string if there is none).
+// Note: the header written by dmenv itself is skipped: it is
+// re-generated every time the lock is written
+pub fn parse_top_comment(text: &str) -> String {
+    let mut res = String::new();
+    for line in text.lines() {
+        if line.starts_with(HEADER_PREFIX) {
+            continue;
+        }
+        if line.trim().is_empty() {
+            if !res.is_empty() {
+                res.push('\n');
+            }
+            return res;
+        }
+        if !line.starts_with('#') {
+            // This comment belongs to the first dependency
+            break;
+        }
+        res.push_str(line);
+        res.push('\n');
+    }
+    String::new()
+}
+
+/// Return the header written by dmenv at the top of the lock, if any,
+/// including the trailing newline
+pub fn parse_header(text: &str) -> Option<String> {
+    let first_line = text.lines().next()?;
+    if first_line.starts_with(HEADER_PREFIX) {
+        Some(format!("{}\n", first_line))
+    } else {
+        None
+    }
+}
+
+/// Parse the header written by `operations::lock::write_lock()`, which looks like:
+/// `# Generated with dmenv 0.20.0, python 3.7.4 (CPython), on Linux`
+/// (the implementation is missing in locks written by older versions of dmenv)
+pub fn parse_metadata(text: &str) -> Option<Metadata> {
+    let header = parse_header(text)?;
+    let rest = header[HEADER_PREFIX.len()..].trim();
+    let mut parts = rest.splitn(3, ", ");
+    let dmenv_version = parts.next()?;
+    let python = parts.next()?.strip_prefix("python ")?;
+    let python_platform = parts.next()?.strip_prefix("on ")?;
+    let (python_version, python_implementation) = match python.strip_suffix(')') {
+        Some(python) => {
+            let mut parts = python.splitn(2, " (");
+            (parts.next()?, Some(parts.next()?.to_string()))
+        }
+        None => (python, None),
+    };
+    Some(Metadata {
+        dmenv_version: dmenv_version.to_string(),
+        python_version: python_version.to_string(),
+        python_platform: python_platform.to_string(),
+        python_implementation,
+    })
+}
+
+/// Parse the lock contents into a list of FrozenDependency.
+// Note: pkg-resources is filtered out, just like when
+// running `pip freeze`
+pub fn parse_frozen(text: &str) -> Result<Vec<FrozenDependency>, Error> {
+    let locked_deps = parse(text)?;
+    let frozen_deps = locked_deps
+        .iter()
+        .map(FrozenDependency::from_locked)
+        .collect();
+    Ok(remove_pkg_resources(frozen_deps))
+}
+
+// Note: CRLF line endings (from a lock checked out on Windows, for
+// instance) are converted to LF
 fn split_logical_lines(text: &str) -> Vec<String> {
-    let not_joined = text.split_terminator(|x| x == '\n');
+    let not_joined = text.split_terminator('\n');
     let mut res = vec![];
     let mut current_line = String::new();
     for line in not_joined {
+        let line = line.strip_suffix('\r').unwrap_or(line);
         current_line.push_str(line);
         current_line.push('\n');
         if !line.ends_with('\\') {
@@ -49,9 +148,10 @@ pub fn parse_line(line: &str) -> Result<LockedDependency, Error> {
 // Note: technically this function cannot fail, but we want to keep symetry with
 // parse_git_line()
 pub fn parse_simple_line(line: &str) -> Result<SimpleDependency, Error> {
-    let version = parse_simple_version(&line);
-    let name = parse_simple_name(&line);
+    let version = parse_simple_version(line);
+    let name = parse_simple_name(line);
     Ok(SimpleDependency {
+        comment: String::new(),
         line: line.to_string(),
         name,
         version,
@@ -97,6 +197,7 @@ pub fn parse_git_line(line: &str) -> Result<GitDependency, Error> {
     let name = parse_git_name(line);
     let git_ref = parse_git_ref(line)?;
     Ok(GitDependency {
+        comment: String::new(),
         line: line.to_string(),
         name,
         git_ref,
@@ -138,7 +239,7 @@ mod tests {
     #[test]
     fn malformed_lock() {
         let lock_contents = "bar==42\ngit://foo/bar.git@master#egggg=bar";
-        let actual = parse(&lock_contents);
+        let actual = parse(lock_contents);
         let actual = actual.unwrap_err();
         match actual {
             Error::MalformedLock { .. } => (),
@@ -159,6 +260,14 @@ bar \\
         assert_eq!(lines[1], "bar \\\n  baz\n");
     }
 
+    #[test]
+    fn test_split_crlf_lines() {
+        let lines = split_logical_lines("foo\r\nbar \\\r\n  baz\r\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "foo\n");
+        assert_eq!(lines[1], "bar \\\n  baz\n");
+    }
+
     #[test]
     fn test_no_newline_at_the_end() {
         let lines = split_logical_lines("foo\nbar");
@@ -178,6 +287,96 @@ git+ssh://git@host.tld:team/name.git@v0.32#egg=bar
         assert_eq!(actual_deps.len(), 2);
     }
 
+    #[test]
+    fn test_parse_comments() {
+        let text = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on Linux
+# pinned for CVE-2019-42
+# see #42
+foo==0.42
+
+# orphan comment
+
+bar==1.3
+";
+        let deps = parse(text).unwrap();
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[0].comment(), "# pinned for CVE-2019-42\n# see #42\n");
+        assert_eq!(deps[1].comment(), "");
+    }
+
+    #[test]
+    fn test_parse_top_comment() {
+        let text = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on Linux
+# Do not edit by hand
+
+# pinned for CVE-2019-42
+foo==0.42
+";
+        assert_eq!(parse_top_comment(text), "# Do not edit by hand\n\n");
+    }
+
+    #[test]
+    fn test_no_top_comment() {
+        let text = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on Linux
+# pinned for CVE-2019-42
+foo==0.42
+";
+        assert_eq!(parse_top_comment(text), "");
+        assert_eq!(parse_top_comment(""), "");
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let text = "# Generated with dmenv 0.20.0, python 3.7.4, on Linux\nfoo==0.42\n";
+        assert_eq!(
+            parse_header(text).unwrap(),
+            "# Generated with dmenv 0.20.0, python 3.7.4, on Linux\n"
+        );
+        assert!(parse_header("foo==0.42\n").is_none());
+        assert!(parse_header("").is_none());
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let text = "# Generated with dmenv 0.20.0, python 3.7.4, on Linux\nfoo==0.42\n";
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(metadata.dmenv_version, "0.20.0");
+        assert_eq!(metadata.python_version, "3.7.4");
+        assert_eq!(metadata.python_platform, "Linux");
+        assert!(metadata.python_implementation.is_none());
+    }
+
+    #[test]
+    fn test_parse_metadata_with_implementation() {
+        let text = "# Generated with dmenv 0.21.0, python 3.7.4 (PyPy), on Linux\nfoo==0.42\n";
+        let metadata = parse_metadata(text).unwrap();
+        assert_eq!(metadata.python_version, "3.7.4");
+        assert_eq!(metadata.python_implementation.unwrap(), "PyPy");
+        assert_eq!(metadata.python_platform, "Linux");
+    }
+
+    #[test]
+    fn test_parse_metadata_without_header() {
+        assert!(parse_metadata("foo==0.42\n").is_none());
+        assert!(parse_metadata("# Generated with dmenv\nfoo==0.42\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_frozen() {
+        let text = "\
+# Generated with dmenv
+foo==0.42
+pkg-resources==0.0.0
+";
+        let frozen_deps = parse_frozen(text).unwrap();
+        assert_eq!(frozen_deps.len(), 1);
+        assert_eq!(frozen_deps[0].name, "foo");
+        assert_eq!(frozen_deps[0].version, "0.42");
+    }
+
     #[test]
     fn test_parse_simple_version() {
         assert_eq!(parse_simple_version("foo == 0.42").value, "0.42");
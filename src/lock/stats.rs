@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+use crate::dependencies::{FrozenDependency, LockedDependency};
+use crate::error::Error;
+use crate::lock::{parse, parse_metadata};
+use crate::Metadata;
+
+/// Summary of the contents of a lock, see `dmenv lock:stats`
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total: usize,
+    // `name==version` lines
+    pub pinned: usize,
+    // git URLs
+    pub git: usize,
+    pub with_hashes: usize,
+    // Read from the header written by dmenv, if any
+    pub metadata: Option<Metadata>,
+}
+
+pub fn stats(text: &str) -> Result<Stats, Error> {
+    let locked_deps = parse(text)?;
+    let pinned = locked_deps
+        .iter()
+        .filter(|x| matches!(x, LockedDependency::Simple(_)))
+        .count();
+    let with_hashes = locked_deps
+        .iter()
+        .map(FrozenDependency::from_locked)
+        .filter(|x| !x.hashes.is_empty())
+        .count();
+    Ok(Stats {
+        total: locked_deps.len(),
+        pinned,
+        git: locked_deps.len() - pinned,
+        with_hashes,
+        metadata: parse_metadata(text),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let text = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on Linux
+attrs==19.3.0 \\
+    --hash=sha256:08a96c641c3a74e44eb59afb61a24f2cb9f4d7188748e76ba4bb5edfa3cb7d1c
+foo==0.42
+git+ssh://git@host.tld:team/name.git@v0.32#egg=bar
+";
+        let stats = stats(text).unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.pinned, 2);
+        assert_eq!(stats.git, 1);
+        assert_eq!(stats.with_hashes, 1);
+        assert_eq!(stats.metadata.unwrap().python_version, "3.7.4");
+    }
+
+    #[test]
+    fn test_stats_without_header() {
+        let stats = stats("foo==0.42\n").unwrap();
+        assert_eq!(stats.total, 1);
+        assert!(stats.metadata.is_none());
+    }
+}
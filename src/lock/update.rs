@@ -20,6 +20,7 @@ impl Updater {
         UpdateLockOptions {
             python_version,
             sys_platform,
+            ..
         }: UpdateLockOptions,
     ) {
         self.python_version = python_version;
@@ -66,6 +67,9 @@ impl Updater {
             if let Some(sys_platform) = &self.sys_platform {
                 locked_dep.sys_platform(sys_platform);
             }
+            if !dep.hashes.is_empty() {
+                locked_dep.set_hashes(&dep.hashes);
+            }
             print!("+ {}", locked_dep.line);
             locked_dependencies.push(LockedDependency::Simple(locked_dep));
         }
@@ -74,7 +78,7 @@ impl Updater {
     /// Modify dependencies that were in the lock to match those passed in `frozen_deps`
     fn patch_existing_deps(
         &self,
-        locked_dependencies: &mut Vec<LockedDependency>,
+        locked_dependencies: &mut [LockedDependency],
         frozen_deps: &[FrozenDependency],
     ) {
         for dep in locked_dependencies.iter_mut() {
@@ -92,16 +96,23 @@ impl Updater {
     /// Modify an existing dependency to match the frozen version
     fn patch_existing_dep(dep: &mut SimpleDependency, frozen_deps: &[FrozenDependency]) {
         let frozen_match = frozen_deps.iter().find(|x| x.name == dep.name);
-        let frozen_version = match frozen_match {
+        let frozen = match frozen_match {
             None => return,
-            Some(frozen) => &frozen.version,
+            Some(frozen) => frozen,
         };
+        let frozen_version = &frozen.version;
         if &dep.version.value == frozen_version {
+            if !frozen.hashes.is_empty() {
+                dep.set_hashes(&frozen.hashes);
+            }
             return;
         }
 
         println!("{}: {} -> {}", dep.name, dep.version.value, &frozen_version);
-        dep.update(&frozen_version)
+        dep.update(frozen_version);
+        // Hashes from the previous version are no longer valid,
+        // so always replace them
+        dep.set_hashes(&frozen.hashes);
     }
 }
 
@@ -115,6 +126,7 @@ mod tests {
             FrozenDependency {
                 name: name.to_string(),
                 version: version.to_string(),
+                hashes: vec![],
             }
         }
     }
@@ -197,12 +209,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_hashes() {
+        let updater = Updater::new();
+        let mut frozen = FrozenDependency::new("foo", "0.43");
+        frozen.hashes = vec!["sha256:abc".to_string()];
+        assert_update(
+            updater,
+            "bar==1.3\nfoo==0.42\n",
+            &[frozen],
+            "bar==1.3\nfoo==0.43 \\\n    --hash=sha256:abc\n",
+        );
+    }
+
+    #[test]
+    fn remove_stale_hashes() {
+        let updater = Updater::new();
+        assert_update(
+            updater,
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+            &[FrozenDependency::new("foo", "0.43")],
+            "foo==0.43\n",
+        );
+    }
+
+    #[test]
+    fn keep_hashes() {
+        let updater = Updater::new();
+        assert_update(
+            updater,
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+            &[FrozenDependency::new("foo", "0.42")],
+            "foo==0.42 \\\n    --hash=sha256:abc\n",
+        );
+    }
+
     #[test]
     fn different_python_version() {
         let mut updater = Updater::new();
         updater.set_options(UpdateLockOptions {
             python_version: Some("< '3.6'".to_string()),
-            sys_platform: None,
+            ..Default::default()
         });
         assert_update(
             updater,
@@ -219,8 +266,8 @@ mod tests {
     fn different_platform() {
         let mut updater = Updater::new();
         updater.set_options(UpdateLockOptions {
-            python_version: None,
             sys_platform: Some("win32".to_string()),
+            ..Default::default()
         });
         assert_update(
             updater,
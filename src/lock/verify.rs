@@ -0,0 +1,86 @@
+use crate::dependencies::{normalize_name, FrozenDependency};
+
+/// A locked dependency whose distribution does not match
+/// any of the hashes recorded in the lock
+#[derive(Debug, PartialEq)]
+pub struct HashMismatch {
+    pub name: String,
+    pub version: String,
+    pub actual_hashes: Vec<String>,
+}
+
+impl std::fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}=={}: no recorded hash matches (got: {})",
+            self.name,
+            self.version,
+            self.actual_hashes.join(", ")
+        )
+    }
+}
+
+/// Compare the hashes recorded in the lock with the ones computed
+/// from the distributions of the installed packages.
+//
+// Note: a lock may contain several hashes for the same package (one per
+// platform, for instance), so a single match is enough
+pub fn verify_hashes(
+    locked_dependencies: &[FrozenDependency],
+    computed_dependencies: &[FrozenDependency],
+) -> Vec<HashMismatch> {
+    let mut res = vec![];
+    for locked in locked_dependencies {
+        let computed = computed_dependencies
+            .iter()
+            .find(|x| normalize_name(&x.name) == normalize_name(&locked.name));
+        let actual_hashes = match computed {
+            None => vec![],
+            Some(computed) => computed.hashes.clone(),
+        };
+        if !actual_hashes.iter().any(|x| locked.hashes.contains(x)) {
+            res.push(HashMismatch {
+                name: locked.name.to_string(),
+                version: locked.version.to_string(),
+                actual_hashes,
+            });
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frozen(name: &str, hashes: &[&str]) -> FrozenDependency {
+        FrozenDependency {
+            name: name.to_string(),
+            version: "0.42".to_string(),
+            hashes: hashes.iter().map(|x| (*x).to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_verify_hashes() {
+        let locked = vec![
+            frozen("foo", &["sha256:abc", "sha256:def"]),
+            frozen("Bar_Qux", &["sha256:123"]),
+            frozen("baz", &["sha256:456"]),
+        ];
+        let computed = vec![
+            frozen("foo", &["sha256:def"]),
+            frozen("bar-qux", &["sha256:789"]),
+        ];
+
+        let mismatches = verify_hashes(&locked, &computed);
+
+        let names: Vec<_> = mismatches.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, &["Bar_Qux", "baz"]);
+        assert_eq!(
+            mismatches[0].to_string(),
+            "Bar_Qux==0.42: no recorded hash matches (got: sha256:789)"
+        );
+    }
+}
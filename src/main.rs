@@ -2,9 +2,14 @@ use structopt::StructOpt;
 
 fn main() {
     let cmd = dmenv::Command::from_args();
+    let json_errors = cmd.json_errors;
     let result = dmenv::run_cmd(cmd);
     if let Err(error) = result {
-        dmenv::print_error(&error.to_string());
-        std::process::exit(1)
+        if json_errors {
+            eprintln!("{}", error.to_json());
+        } else {
+            dmenv::print_error(&error.to_string());
+        }
+        std::process::exit(error.exit_code())
     };
 }
@@ -0,0 +1,227 @@
+use std::path::Path;
+
+use crate::error::*;
+use crate::run::run_with_env;
+use crate::ui::*;
+
+/// Whether the `--project` option is a git URL rather than a path,
+/// for instance `git+https://github.com/acme/foo.git`
+pub fn is_git_url(project: &str) -> bool {
+    project.starts_with("git+")
+}
+
+/// Clone the repository at the given URL into a new temporary directory,
+/// which is removed when the returned value is dropped.
+/// Like for pip, a branch, a tag or a commit can be given after a `@`, as in
+/// `git+https://github.com/acme/foo.git@v1.0`.
+/// When `dry_run` is true, the git commands are printed but not run, and the
+/// temporary directory is left empty.
+// Notes:
+// * Only the last commit is fetched, since the history is not used. This
+//   does not work for commits, so in this case the whole repository is cloned
+//   and the commit is checked out afterwards
+// * The `#egg=` and `#subdirectory=` fragments are ignored here, see `subdirectory()`
+pub fn clone(url: &str, dry_run: bool) -> Result<tempdir::TempDir, Error> {
+    let (repo_url, git_ref) = split_git_url(url);
+    let tmp_dir = tempdir::TempDir::new("dmenv-project")
+        .map_err(|e| new_error(format!("Could not create temporary directory: {}", e)))?;
+    let clone_path = tmp_dir.path().to_string_lossy().to_string();
+    let commands = match git_ref {
+        Some(git_ref) if is_commit(git_ref) => vec![
+            vec!["clone", "--quiet", repo_url, &clone_path],
+            vec!["-C", &clone_path, "checkout", "--quiet", git_ref],
+        ],
+        Some(git_ref) => vec![vec![
+            "clone",
+            "--quiet",
+            "--depth",
+            "1",
+            "--branch",
+            git_ref,
+            repo_url,
+            &clone_path,
+        ]],
+        None => vec![vec![
+            "clone",
+            "--quiet",
+            "--depth",
+            "1",
+            repo_url,
+            &clone_path,
+        ]],
+    };
+    print_info_1(&format!("Cloning {}", url));
+    let git = Path::new("git");
+    let cwd = std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
+    for args in &commands {
+        print_cmd(git, args);
+        if dry_run {
+            continue;
+        }
+        run_with_env(&cwd, git, args, &[])
+            .map_err(|e| new_error(format!("Could not clone {}: {}", url, e)))?;
+    }
+    Ok(tmp_dir)
+}
+
+/// The subdirectory containing the project in the repository, as given by
+/// the `#subdirectory=` fragment of the URL, if any, like for pip
+pub fn subdirectory(url: &str) -> Option<&str> {
+    let (_, fragment) = url.split_once('#')?;
+    fragment
+        .split('&')
+        .find_map(|x| x.strip_prefix("subdirectory="))
+        .filter(|x| !x.is_empty())
+}
+
+// Full or abbreviated commit hashes, like `deadbeef`
+fn is_commit(git_ref: &str) -> bool {
+    (7..=40).contains(&git_ref.len()) && git_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// `git+https://host/foo.git@v1.0#egg=foo` -> (`https://host/foo.git`, Some(`v1.0`))
+// Note: `@` is only a ref separator after the last `/`, so that
+// `git+ssh://git@host/foo.git` works
+fn split_git_url(url: &str) -> (&str, Option<&str>) {
+    let url = url.strip_prefix("git+").unwrap_or(url);
+    let url = url.split('#').next().unwrap_or(url);
+    let last_slash = url.rfind('/').unwrap_or(0);
+    match url.rfind('@') {
+        Some(at) if at > last_slash => (&url[..at], Some(&url[at + 1..])),
+        _ => (url, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_git_url() {
+        assert!(is_git_url("git+https://github.com/acme/foo.git"));
+        assert!(!is_git_url("/path/to/foo"));
+        assert!(!is_git_url("git-projects/foo"));
+    }
+
+    #[test]
+    fn test_split_git_url() {
+        assert_eq!(
+            split_git_url("git+https://github.com/acme/foo.git"),
+            ("https://github.com/acme/foo.git", None)
+        );
+        assert_eq!(
+            split_git_url("git+https://github.com/acme/foo.git@v1.0"),
+            ("https://github.com/acme/foo.git", Some("v1.0"))
+        );
+        assert_eq!(
+            split_git_url("git+ssh://git@github.com/acme/foo.git"),
+            ("ssh://git@github.com/acme/foo.git", None)
+        );
+        assert_eq!(
+            split_git_url("git+https://github.com/acme/foo.git@v1.0#egg=foo&subdirectory=bar"),
+            ("https://github.com/acme/foo.git", Some("v1.0"))
+        );
+        assert_eq!(
+            split_git_url("git+https://github.com/acme/foo.git#egg=foo"),
+            ("https://github.com/acme/foo.git", None)
+        );
+    }
+
+    #[test]
+    fn test_subdirectory() {
+        assert_eq!(
+            subdirectory("git+https://github.com/acme/foo.git@v1.0#egg=foo&subdirectory=bar"),
+            Some("bar")
+        );
+        assert_eq!(
+            subdirectory("git+https://github.com/acme/foo.git#egg=foo"),
+            None
+        );
+        assert_eq!(subdirectory("git+https://github.com/acme/foo.git"), None);
+    }
+
+    #[test]
+    fn test_is_commit() {
+        assert!(is_commit("deadbeef"));
+        assert!(is_commit("0123456789abcdef0123456789abcdef01234567"));
+        assert!(!is_commit("v1.0"));
+        assert!(!is_commit("master"));
+        assert!(!is_commit("cafe"));
+    }
+
+    #[test]
+    fn test_clone() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let repo = tmp_dir.path();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(repo)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        std::fs::write(repo.join("setup.py"), "").unwrap();
+        git(&["add", "setup.py"]);
+        git(&[
+            "-c",
+            "user.name=dmenv",
+            "-c",
+            "user.email=dmenv@example.com",
+            "commit",
+            "--quiet",
+            "--message",
+            "initial commit",
+        ]);
+
+        let url = format!("git+file://{}", repo.display());
+        let cloned = clone(&url, false).unwrap();
+        assert!(cloned.path().join("setup.py").exists());
+        let clone_path = cloned.path().to_path_buf();
+        drop(cloned);
+        assert!(!clone_path.exists());
+
+        let cloned = clone(&format!("{}#egg=foo", url), false).unwrap();
+        assert!(cloned.path().join("setup.py").exists());
+
+        clone(&format!("{}@no-such-branch", url), false).unwrap_err();
+
+        let cloned = clone(&url, true).unwrap();
+        assert!(!cloned.path().join("setup.py").exists());
+    }
+
+    #[test]
+    fn test_clone_at_commit() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let repo = tmp_dir.path();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=dmenv",
+                    "-c",
+                    "user.email=dmenv@example.com",
+                ])
+                .args(args)
+                .current_dir(repo)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "--quiet"]);
+        std::fs::write(repo.join("setup.py"), "").unwrap();
+        git(&["add", "setup.py"]);
+        git(&["commit", "--quiet", "--message", "add setup.py"]);
+        let first_commit = git(&["rev-parse", "HEAD"]);
+        std::fs::write(repo.join("setup.cfg"), "").unwrap();
+        git(&["add", "setup.cfg"]);
+        git(&["commit", "--quiet", "--message", "add setup.cfg"]);
+
+        let url = format!("git+file://{}@{}", repo.display(), &first_commit[..10]);
+        let cloned = clone(&url, false).unwrap();
+        assert!(cloned.path().join("setup.py").exists());
+        assert!(!cloned.path().join("setup.cfg").exists());
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use crate::error::*;
+
+const EXTRAS_SECTION: &str = "[options.extras_require]";
+
+/// Make sure the `extra` used by `pip install --editable .[<extra>]`
+/// is declared in the `setup.cfg` file of the project.
+// Note: pip merely prints a warning when the extra does not exist,
+// so without this check the extra dependencies would silently be missing
+// from the virtualenv and from the lock.
+// If there is no `setup.cfg`, or if it has no `extras_require` section,
+// the extras are probably declared in `setup.py`, so we can't check anything
+pub fn check(project_path: &Path, extra: &str) -> Result<(), Error> {
+    let setup_cfg = project_path.join("setup.cfg");
+    if !setup_cfg.exists() {
+        return Ok(());
+    }
+    let contents =
+        std::fs::read_to_string(&setup_cfg).map_err(|e| new_read_error(e, &setup_cfg))?;
+    let available = match parse_extras(&contents) {
+        None => return Ok(()),
+        Some(extras) => extras,
+    };
+    if available.iter().any(|x| x == extra) {
+        return Ok(());
+    }
+    Err(Error::MissingExtra {
+        name: extra.to_string(),
+        available,
+    })
+}
+
+/// Return the names of the extras declared in the `[options.extras_require]`
+/// section, or None if there is no such section.
+// Note: we don't use the `ini` crate here because it does not
+// know about indented continuation lines, which are used to list
+// the dependencies of each extra
+fn parse_extras(contents: &str) -> Option<Vec<String>> {
+    let mut lines = contents
+        .lines()
+        .skip_while(|line| line.trim() != EXTRAS_SECTION);
+    lines.next()?;
+    let mut res = vec![];
+    for line in lines {
+        if line.starts_with('[') {
+            break;
+        }
+        if line.starts_with(char::is_whitespace) || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(pos) = line.find(&['=', ':'][..]) {
+            res.push(line[..pos].trim().to_string());
+        }
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_extras() {
+        let contents = "\
+[options]
+install_requires =
+  path.py
+
+[options.extras_require]
+# Used by `dmenv lock`
+dev =
+  pytest
+  pytest-cov
+prod = gunicorn
+docs:
+  sphinx
+
+[options.entry_points]
+console_scripts = demo=demo:main
+";
+        assert_eq!(parse_extras(contents).unwrap(), &["dev", "prod", "docs"]);
+    }
+
+    #[test]
+    fn test_no_extras_section() {
+        assert!(parse_extras("[options]\ninstall_requires = foo\n").is_none());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::dependencies::requirement_name;
 use crate::error::*;
 use crate::ui::*;
 use std::path::Path;
@@ -7,7 +8,10 @@ pub struct InitOptions {
     name: String,
     version: String,
     author: Option<String>,
+    author_email: Option<String>,
+    license: Option<String>,
     setup_cfg: bool,
+    requirements: Vec<String>,
 }
 
 impl InitOptions {
@@ -16,7 +20,10 @@ impl InitOptions {
             name,
             version,
             author: None,
+            author_email: None,
+            license: None,
             setup_cfg: true,
+            requirements: vec![],
         }
     }
 
@@ -25,10 +32,53 @@ impl InitOptions {
         self
     }
 
+    pub fn author_email(&mut self, author_email: &str) -> &mut Self {
+        self.author_email = Some(author_email.to_string());
+        self
+    }
+
+    pub fn license(&mut self, license: &str) -> &mut Self {
+        self.license = Some(license.to_string());
+        self
+    }
+
     pub fn no_setup_cfg(&mut self) -> &mut Self {
         self.setup_cfg = false;
         self
     }
+
+    /// Dependencies to write in `install_requires`, see `parse_requirements()`
+    pub fn requirements(&mut self, requirements: Vec<String>) -> &mut Self {
+        self.requirements = requirements;
+        self
+    }
+}
+
+/// Parse the contents of a requirements.txt file into a list of package names,
+/// without versions, so that they can be used in `install_requires`.
+//
+// Notes:
+// * comments, hashes, and options like `--index-url` or `-r` are skipped
+// * versions are left out, since the lock is the place to pin them
+// * URLs are skipped too, with a warning, since there is no name to use
+pub fn parse_requirements(text: &str) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for line in text.lines() {
+        let line = line.split(" #").next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('-') {
+            continue;
+        }
+        if line.contains("://") {
+            print_warning(&format!("Skipping {}: URLs are not supported", line));
+            continue;
+        }
+        let name = requirement_name(line);
+        if name.is_empty() || res.iter().any(|x| x == name) {
+            continue;
+        }
+        res.push(name.to_string());
+    }
+    res
 }
 
 fn ensure_path_does_not_exist(path: &Path) -> Result<(), Error> {
@@ -77,33 +127,81 @@ fn write_from_template(
 ) -> Result<(), Error> {
     // Warning: make sure the template files in `src/operations/` contain all those
     // placeholders
-    let with_name = template.replace("<NAME>", &options.name);
-    let with_version = with_name.replace("<VERSION>", &options.version);
-    let to_write = if let Some(ref author) = options.author {
-        with_version.replace("<AUTHOR>", author)
-    } else {
-        with_version
-    };
+    let mut to_write = template.replace("<NAME>", &options.name);
+    to_write = to_write.replace("<VERSION>", &options.version);
+    if let Some(ref author_email) = options.author_email {
+        to_write = to_write.replace("<AUTHOR_EMAIL>", author_email);
+    }
+    if let Some(ref author) = options.author {
+        to_write = to_write.replace("<AUTHOR>", author);
+    }
+    if let Some(ref license) = options.license {
+        to_write = to_write.replace("<LICENSE>", license);
+    }
+    let classifier = options.license.as_deref().and_then(license_classifier);
+    let classifiers: Vec<_> = classifier.into_iter().collect();
+    let to_write = replace_list_placeholder(&to_write, "<LICENSE_CLASSIFIER>", &classifiers);
+    let requirements: Vec<_> = options.requirements.iter().map(AsRef::as_ref).collect();
+    let to_write = replace_list_placeholder(&to_write, "<INSTALL_REQUIRES>", &requirements);
     write_to_path(dest_path, &to_write)
 }
 
+/// Return the trove classifier matching the given license, if known
+// Note: licenses are usually given as SPDX identifiers, but the trove
+// classifiers are less precise (there's no classifier for BSD-2-Clause, for instance)
+fn license_classifier(license: &str) -> Option<&'static str> {
+    let res = match license.to_lowercase().as_str() {
+        "mit" => "License :: OSI Approved :: MIT License",
+        "apache-2.0" | "apache" => "License :: OSI Approved :: Apache Software License",
+        "bsd" | "bsd-2-clause" | "bsd-3-clause" => "License :: OSI Approved :: BSD License",
+        "gpl-2.0" | "gplv2" => "License :: OSI Approved :: GNU General Public License v2 (GPLv2)",
+        "gpl-3.0" | "gplv3" => "License :: OSI Approved :: GNU General Public License v3 (GPLv3)",
+        "lgpl-3.0" | "lgplv3" => {
+            "License :: OSI Approved :: GNU Lesser General Public License v3 (LGPLv3)"
+        }
+        "mpl-2.0" => "License :: OSI Approved :: Mozilla Public License 2.0 (MPL 2.0)",
+        "isc" => "License :: OSI Approved :: ISC License (ISCL)",
+        "proprietary" => "License :: Other/Proprietary License",
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// Repeat the line containing the placeholder once for each value,
+/// replacing the placeholder with it. The line is removed if there
+/// are no values
+fn replace_list_placeholder(text: &str, placeholder: &str, values: &[&str]) -> String {
+    let mut res = String::new();
+    for line in text.lines() {
+        if line.contains(placeholder) {
+            for value in values {
+                res.push_str(&line.replace(placeholder, value));
+                res.push('\n');
+            }
+        } else {
+            res.push_str(line);
+            res.push('\n');
+        }
+    }
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempdir;
 
     #[test]
     fn creates_two_files_by_default() {
         let tmp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
         let tmp_path = tmp_dir.path();
 
-        run_init(&tmp_path.to_path_buf()).unwrap();
+        run_init(tmp_path).unwrap();
 
-        let setup_py = std::fs::read_to_string(&tmp_path.join("setup.py")).unwrap();
+        let setup_py = std::fs::read_to_string(tmp_path.join("setup.py")).unwrap();
         assert_contains(&setup_py, "setup()");
         assert_not_contains(&setup_py, "foo");
 
-        let setup_cfg = std::fs::read_to_string(&tmp_path.join("setup.cfg")).unwrap();
+        let setup_cfg = std::fs::read_to_string(tmp_path.join("setup.cfg")).unwrap();
         assert_contains(&setup_cfg, "name = foo");
         assert_contains(&setup_cfg, "version = 0.42");
     }
@@ -113,7 +211,7 @@ mod tests {
         let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
         let tmp_path = temp_dir.path();
 
-        run_init_no_setup_cfg(&tmp_path.to_path_buf()).unwrap();
+        run_init_no_setup_cfg(tmp_path).unwrap();
 
         let setup_cfg_path = tmp_path.join("setup.cfg");
         assert!(!setup_cfg_path.exists());
@@ -123,6 +221,105 @@ mod tests {
         assert_contains(&setup_py, "\"0.42\"");
     }
 
+    #[test]
+    fn author_and_license() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options
+            .author("Jane Doe")
+            .author_email("jane@example.com")
+            .license("MIT");
+
+        init(tmp_path, &init_options).unwrap();
+
+        let setup_cfg = std::fs::read_to_string(tmp_path.join("setup.cfg")).unwrap();
+        assert_contains(&setup_cfg, "author = Jane Doe\n");
+        assert_contains(&setup_cfg, "author_email = jane@example.com\n");
+        assert_contains(&setup_cfg, "license = MIT\n");
+        assert_contains(&setup_cfg, "  License :: OSI Approved :: MIT License\n");
+    }
+
+    #[test]
+    fn author_and_license_in_setup_py() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options
+            .no_setup_cfg()
+            .author_email("jane@example.com")
+            .license("BSD-3-Clause");
+
+        init(tmp_path, &init_options).unwrap();
+
+        let setup_py = std::fs::read_to_string(tmp_path.join("setup.py")).unwrap();
+        assert_contains(&setup_py, "author=\"<AUTHOR>\"");
+        assert_contains(&setup_py, "author_email=\"jane@example.com\"");
+        assert_contains(&setup_py, "license=\"BSD-3-Clause\"");
+        assert_contains(&setup_py, "\"License :: OSI Approved :: BSD License\",");
+    }
+
+    #[test]
+    fn test_parse_requirements() {
+        let text = "\
+# Common dependencies
+--index-url https://pypi.example.com/simple
+-r base.txt
+attrs==19.1.0 \\
+    --hash=sha256:abc
+Click>=7.0  # for the CLI
+requests[security] ; python_version >= '3.6'
+git+https://github.com/acme/foo.git@v1.0#egg=foo
+attrs==19.3.0
+";
+        assert_eq!(parse_requirements(text), &["attrs", "Click", "requests"]);
+    }
+
+    #[test]
+    fn install_requires_from_requirements() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options.requirements(vec!["attrs".to_string(), "click".to_string()]);
+
+        init(tmp_path, &init_options).unwrap();
+
+        let setup_cfg = std::fs::read_to_string(tmp_path.join("setup.cfg")).unwrap();
+        assert_contains(&setup_cfg, "  # colorama\n  attrs\n  click\n\n");
+    }
+
+    #[test]
+    fn install_requires_from_requirements_in_setup_py() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+        let mut init_options = InitOptions::new("foo".to_string(), "0.42".to_string());
+        init_options
+            .no_setup_cfg()
+            .requirements(vec!["attrs".to_string()]);
+
+        init(tmp_path, &init_options).unwrap();
+
+        let setup_py = std::fs::read_to_string(tmp_path.join("setup.py")).unwrap();
+        assert_contains(
+            &setup_py,
+            "        # \"colorama\",\n        \"attrs\",\n    ],",
+        );
+        assert_not_contains(&setup_py, "INSTALL_REQUIRES");
+    }
+
+    #[test]
+    fn no_license_classifier_by_default() {
+        let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
+        let tmp_path = temp_dir.path();
+
+        run_init(tmp_path).unwrap();
+
+        let setup_cfg = std::fs::read_to_string(tmp_path.join("setup.cfg")).unwrap();
+        assert_not_contains(&setup_cfg, "LICENSE_CLASSIFIER");
+        assert_not_contains(&setup_cfg, "INSTALL_REQUIRES");
+        assert_contains(&setup_cfg, "classifiers =\n  # Insert");
+    }
+
     #[test]
     fn does_not_overwrite_setup_cfg() {
         let temp_dir = tempdir::TempDir::new("test-dmenv-init").unwrap();
@@ -130,7 +327,7 @@ mod tests {
         let setup_cfg_path = tmp_path.join("setup.cfg");
         touch(&setup_cfg_path);
 
-        let err = run_init(&tmp_path.to_path_buf()).unwrap_err();
+        let err = run_init(tmp_path).unwrap_err();
         assert_file_exists_error(err, &setup_cfg_path);
     }
 
@@ -141,7 +338,7 @@ mod tests {
         let setup_py_path = tmp_path.join("setup.py");
         touch(&setup_py_path);
 
-        let err = run_init_no_setup_cfg(&tmp_path.to_path_buf()).unwrap_err();
+        let err = run_init_no_setup_cfg(tmp_path).unwrap_err();
         assert_file_exists_error(err, &setup_py_path);
     }
 
@@ -165,7 +362,7 @@ mod tests {
     }
 
     fn touch(path: &Path) {
-        std::fs::write(&path, "# don't overwrite me").unwrap()
+        std::fs::write(path, "# don't overwrite me").unwrap()
     }
 
     fn run_init(tmp_path: &Path) -> Result<(), Error> {
@@ -10,6 +10,8 @@ setup(
     version="<VERSION>",
     description="<DESCRIPTION>",
     author="<AUTHOR>",
+    author_email="<AUTHOR_EMAIL>",
+    license="<LICENSE>",
     packages=find_packages(),
     # If you have just one file, remove the line above
     # and add it in the list below, *without* the .py
@@ -18,6 +20,7 @@ setup(
     install_requires=[
         # Insert common dependencies here:
         # "colorama",
+        "<INSTALL_REQUIRES>",
     ],
     extras_require={
         "dev": [
@@ -30,6 +33,7 @@ setup(
         ]
     },
     classifiers=[
+        "<LICENSE_CLASSIFIER>",
         # Insert the list of supported Python versions here:
         # ...
         # "Programming Language :: Python :: 3.5",
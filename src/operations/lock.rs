@@ -1,7 +1,7 @@
 use colored::*;
 use std::path::Path;
 
-use crate::dependencies::FrozenDependency;
+use crate::dependencies::{FrozenDependency, LockedDependency, SimpleDependency};
 use crate::error::*;
 use crate::lock;
 use crate::lock::Updater;
@@ -17,6 +17,7 @@ pub fn bump(
     version: &str,
     bump_type: BumpType,
     metadata: &Metadata,
+    dry_run: bool,
 ) -> Result<(), Error> {
     let lock_contents =
         std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
@@ -29,8 +30,8 @@ pub fn bump(
         print_warning(&format!("Dependency {} already up-to-date", name.bold()));
         return Ok(());
     }
-    let new_contents = lock::dump(deps);
-    write_lock(lock_path, &new_contents, metadata)?;
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(deps);
+    write_lock(lock_path, &new_contents, metadata, dry_run)?;
     println!("{}", "ok!".green());
     Ok(())
 }
@@ -40,6 +41,7 @@ pub fn update(
     frozen_deps: Vec<FrozenDependency>,
     update_options: UpdateLockOptions,
     metadata: &Metadata,
+    dry_run: bool,
 ) -> Result<(), Error> {
     print_info_2(&format!("Generating {}", lock_path.display()));
     let lock_contents = if lock_path.exists() {
@@ -53,14 +55,17 @@ pub fn update(
     let mut locked_deps = lock::parse(&lock_contents)?;
     updater.update(&mut locked_deps, &frozen_deps);
 
-    let new_contents = lock::dump(locked_deps);
-    write_lock(lock_path, &new_contents, metadata)
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(locked_deps);
+    write_lock(lock_path, &new_contents, metadata, dry_run)?;
+    print_info_2(&format!("Requirements written to {}", lock_path.display()));
+    Ok(())
 }
 
 pub fn tidy(
     lock_path: &Path,
     frozen_deps: Vec<FrozenDependency>,
     metadata: &Metadata,
+    dry_run: bool,
 ) -> Result<(), Error> {
     print_info_2(&format!("Tidying {}", lock_path.display()));
     let frozen_names: Vec<_> = frozen_deps.into_iter().map(|x| x.name).collect();
@@ -75,22 +80,670 @@ pub fn tidy(
     for dep in deps_to_remove {
         println!("- {}", dep.name());
     }
-    let new_contents = &lock::dump(deps_to_keep);
-    write_lock(lock_path, new_contents, metadata)
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(deps_to_keep);
+    write_lock(lock_path, &new_contents, metadata, dry_run)
+}
+
+/// Write the lock from the given frozen dependencies: versions are updated,
+/// new dependencies are added and the ones that are no longer frozen are removed.
+// Note: comments and markers of the existing dependencies are kept, like in `update()`
+pub fn upgrade_all(
+    lock_path: &Path,
+    frozen_deps: Vec<FrozenDependency>,
+    metadata: &Metadata,
+    dry_run: bool,
+) -> Result<(), Error> {
+    print_info_2(&format!("Upgrading {}", lock_path.display()));
+    let lock_contents = if lock_path.exists() {
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?
+    } else {
+        String::new()
+    };
+    let frozen_names: Vec<_> = frozen_deps.iter().map(|x| x.name.to_string()).collect();
+    let locked_deps = lock::parse(&lock_contents)?;
+    let (mut deps_to_keep, deps_to_remove): (Vec<_>, Vec<_>) = locked_deps
+        .into_iter()
+        .partition(|x| frozen_names.contains(&x.name()));
+    for dep in deps_to_remove {
+        println!("- {}", dep.name());
+    }
+    Updater::new().update(&mut deps_to_keep, &frozen_deps);
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(deps_to_keep);
+    write_lock(lock_path, &new_contents, metadata, dry_run)?;
+    print_info_2(&format!("Requirements written to {}", lock_path.display()));
+    Ok(())
+}
+
+/// Merge the dependencies of `other_path` into the lock at `lock_path`.
+/// Conflicts are reported, and the lock is only written if there
+/// are none left, see `lock::merge()`
+pub fn merge(
+    lock_path: &Path,
+    other_path: &Path,
+    prefer_newer: bool,
+    metadata: &Metadata,
+    dry_run: bool,
+) -> Result<(), Error> {
+    print_info_1(&format!(
+        "Merging {} into {}",
+        other_path.display(),
+        lock_path.display()
+    ));
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let other_contents =
+        std::fs::read_to_string(other_path).map_err(|e| new_read_error(e, other_path))?;
+    let ours = lock::parse(&lock_contents)?;
+    let theirs = lock::parse(&other_contents)?;
+    let merge = lock::merge(ours, theirs, prefer_newer);
+    for conflict in &merge.resolved {
+        println!("{} (using the newer version)", conflict);
+    }
+    if !merge.conflicts.is_empty() {
+        print_info_2("Conflicts");
+        for conflict in &merge.conflicts {
+            println!("{}", conflict);
+        }
+        return Err(new_error(format!(
+            "{} conflict(s) found. Fix them by hand, or use `--prefer-newer`",
+            merge.conflicts.len()
+        )));
+    }
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(merge.dependencies);
+    write_lock(lock_path, &new_contents, metadata, dry_run)?;
+    println!("{}", "ok!".green());
+    Ok(())
+}
+
+/// Sort the dependencies in the lock, keeping the header untouched.
+// Note: the lock is written in the same order as `dmenv lock` does,
+// so running this on a lock generated by dmenv is a no-op
+pub fn sort(lock_path: &Path, metadata: &Metadata, dry_run: bool) -> Result<(), Error> {
+    print_info_2(&format!("Sorting {}", lock_path.display()));
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let locked_deps = lock::parse(&lock_contents)?;
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(locked_deps);
+    rewrite_lock(lock_path, &lock_contents, &new_contents, metadata, dry_run)
+}
+
+/// Re-write the header of the lock with the given metadata, so that it
+/// records the Python interpreter currently used. Dependencies are left
+/// untouched
+pub fn pin_python(lock_path: &Path, metadata: &Metadata, dry_run: bool) -> Result<(), Error> {
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    // Note: the header returned by `parse_header()` always ends with LF,
+    // so its length cannot be used to skip it in a lock using CRLF
+    let without_header = match lock::parse_header(&lock_contents) {
+        Some(_) => lock_contents
+            .split_once('\n')
+            .map(|(_, rest)| rest)
+            .unwrap_or_default(),
+        None => &lock_contents,
+    };
+    write_lock(lock_path, without_header, metadata, dry_run)?;
+    print_info_2(&format!(
+        "{} now records {} {} on {}",
+        lock_path.display(),
+        metadata
+            .python_implementation
+            .as_deref()
+            .unwrap_or("python"),
+        metadata.python_version,
+        metadata.python_platform
+    ));
+    Ok(())
+}
+
+/// Return the contents of the lock, minus the given packages,
+/// so that it can be used as a pip constraints file when upgrading them
+pub fn constraints_without(lock_path: &Path, names: &[String]) -> Result<String, Error> {
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let locked_deps = lock::parse(&lock_contents)?;
+    let names: Vec<_> = names.iter().map(|x| x.to_lowercase()).collect();
+    let deps_to_keep: Vec<_> = locked_deps
+        .into_iter()
+        .filter(|x| !names.contains(&x.name().to_lowercase()))
+        .collect();
+    Ok(lock::dump(deps_to_keep))
+}
+
+/// Remove the given packages from the lock, keeping the header untouched.
+// Note: names are compared case-insensitively, like pip does
+pub fn prune(
+    lock_path: &Path,
+    names: &[String],
+    metadata: &Metadata,
+    dry_run: bool,
+) -> Result<(), Error> {
+    print_info_2(&format!("Pruning {}", lock_path.display()));
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let locked_deps = lock::parse(&lock_contents)?;
+    let names: Vec<_> = names.iter().map(|x| x.to_lowercase()).collect();
+    let (deps_to_remove, deps_to_keep): (Vec<_>, Vec<_>) = locked_deps
+        .into_iter()
+        .partition(|x| names.contains(&x.name().to_lowercase()));
+    for name in &names {
+        if !deps_to_remove
+            .iter()
+            .any(|x| &x.name().to_lowercase() == name)
+        {
+            print_warning(&format!("{} not found in lock", name.bold()));
+        }
+    }
+    for dep in &deps_to_remove {
+        println!("- {}", dep.name());
+    }
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(deps_to_keep);
+    rewrite_lock(lock_path, &lock_contents, &new_contents, metadata, dry_run)
+}
+
+/// Add a pinned dependency (like `foo==0.42`) to the lock, keeping the header untouched.
+/// If the dependency is already in the lock, update its version instead.
+// Note: names are compared case-insensitively, like pip does
+pub fn add(lock_path: &Path, spec: &str, metadata: &Metadata, dry_run: bool) -> Result<(), Error> {
+    let frozen = FrozenDependency::from_string(spec.trim().to_string())
+        .map_err(|_| new_error(format!("'{}' should look like name==version", spec)))?;
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let mut locked_deps = lock::parse(&lock_contents)?;
+    let name = frozen.name.to_lowercase();
+    let existing = locked_deps
+        .iter_mut()
+        .find(|x| x.name().to_lowercase() == name);
+    match existing {
+        None => {
+            let mut locked_dep = SimpleDependency::from_frozen(&frozen);
+            locked_dep.set_hashes(&frozen.hashes);
+            print!("+ {}", locked_dep.line);
+            locked_deps.push(LockedDependency::Simple(locked_dep));
+        }
+        Some(LockedDependency::Simple(dep)) => {
+            print_warning(&format!(
+                "{} already in lock, updating its version",
+                dep.name.bold()
+            ));
+            println!("{}: {} -> {}", dep.name, dep.version.value, frozen.version);
+            dep.update(&frozen.version);
+            dep.set_hashes(&frozen.hashes);
+        }
+        Some(LockedDependency::Git(dep)) => {
+            return Err(Error::IncorrectLockedType {
+                name: dep.name.to_string(),
+                expected_type: "simple".to_string(),
+            })
+        }
+    }
+    let new_contents = lock::parse_top_comment(&lock_contents) + &lock::dump(locked_deps);
+    rewrite_lock(lock_path, &lock_contents, &new_contents, metadata, dry_run)
+}
+
+/// Write the new lock contents, re-using the header of the old contents
+// Note: locks written by hand have no header, so use the current metadata
+// in this case
+fn rewrite_lock(
+    lock_path: &Path,
+    old_contents: &str,
+    new_contents: &str,
+    metadata: &Metadata,
+    dry_run: bool,
+) -> Result<(), Error> {
+    match lock::parse_header(old_contents) {
+        Some(header) => write_with_lf(lock_path, &(header + new_contents), dry_run),
+        None => write_lock(lock_path, new_contents, metadata, dry_run),
+    }
+}
+
+/// Check the distributions of the installed packages match the
+/// hashes recorded in the lock.
+/// Returns an error if they don't, so that this can be used in CI
+pub fn verify_hashes(
+    locked_deps: &[FrozenDependency],
+    computed_deps: &[FrozenDependency],
+) -> Result<(), Error> {
+    let mismatches = lock::verify_hashes(locked_deps, computed_deps);
+    if mismatches.is_empty() {
+        println!("{}", "ok!".green());
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    Err(new_error(format!(
+        "{} package(s) failed hash verification",
+        mismatches.len()
+    )))
+}
+
+/// Print the differences between two locks.
+/// Returns an error if there are any, so that this can be used in CI
+pub fn diff(base_path: &Path, other_path: &Path) -> Result<(), Error> {
+    print_info_1(&format!(
+        "Comparing {} with {}",
+        base_path.display(),
+        other_path.display()
+    ));
+    let base_deps = read_frozen(base_path)?;
+    let other_deps = read_frozen(other_path)?;
+    let lock_diff = lock::diff(base_deps, other_deps);
+    if lock_diff.is_empty() {
+        println!("{}", "no differences".green());
+        return Ok(());
+    }
+    if !lock_diff.added.is_empty() {
+        print_info_2("Added");
+        for dep in &lock_diff.added {
+            println!("+ {}=={}", dep.name, dep.version);
+        }
+    }
+    if !lock_diff.removed.is_empty() {
+        print_info_2("Removed");
+        for dep in &lock_diff.removed {
+            println!("- {}=={}", dep.name, dep.version);
+        }
+    }
+    if !lock_diff.changed.is_empty() {
+        print_info_2("Changed");
+        for change in &lock_diff.changed {
+            println!(
+                "{}: {} -> {}",
+                change.name, change.old_version, change.new_version
+            );
+        }
+    }
+    Err(new_error(format!(
+        "{} difference(s) found",
+        lock_diff.len()
+    )))
 }
 
-pub fn write_lock(lock_path: &Path, lock_contents: &str, metadata: &Metadata) -> Result<(), Error> {
+/// Check that the frozen dependencies match the lock.
+/// Returns an error if they don't
+pub fn check(lock_path: &Path, frozen_deps: &[FrozenDependency]) -> Result<(), Error> {
+    print_info_2(&format!(
+        "Comparing installed packages with {}",
+        lock_path.display()
+    ));
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let locked_deps = lock::parse(&lock_contents)?;
+    let mismatches = lock::check(&locked_deps, frozen_deps);
+    if mismatches.is_empty() {
+        println!("{}", "ok!".green());
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    Err(new_error(format!(
+        "{} package(s) out of sync",
+        mismatches.len()
+    )))
+}
+
+/// Fail if some packages appear more than once in the lock,
+/// see `lock::find_duplicates()`
+pub fn check_duplicates(lock_path: &Path) -> Result<(), Error> {
+    print_info_2(&format!(
+        "Looking for duplicates in {}",
+        lock_path.display()
+    ));
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    let locked_deps = lock::parse(&lock_contents)?;
+    let duplicates = lock::find_duplicates(&locked_deps);
+    if duplicates.is_empty() {
+        println!("{}", "ok!".green());
+        return Ok(());
+    }
+    for duplicate in &duplicates {
+        println!("{}", duplicate);
+    }
+    Err(new_error(format!(
+        "{} duplicated package(s) in {}",
+        duplicates.len(),
+        lock_path.display()
+    )))
+}
+
+/// Read the lock into a list of FrozenDependency, without pkg-resources.
+// Note: also exposed as `dmenv::parse_lock()`
+pub fn read_frozen(lock_path: &Path) -> Result<Vec<FrozenDependency>, Error> {
+    let lock_contents =
+        std::fs::read_to_string(lock_path).map_err(|e| new_read_error(e, lock_path))?;
+    lock::parse_frozen(&lock_contents)
+}
+
+pub fn write_lock(
+    lock_path: &Path,
+    lock_contents: &str,
+    metadata: &Metadata,
+    dry_run: bool,
+) -> Result<(), Error> {
     let Metadata {
         dmenv_version,
         python_version,
         python_platform,
+        python_implementation,
     } = metadata;
 
+    // Note: the implementation goes after the version, so that the
+    // platform stays last. Versions of dmenv that do not know about it
+    // read it as part of the Python version, like `3.7.4 (CPython)`
+    let python = match python_implementation {
+        Some(implementation) => format!("{} ({})", python_version, implementation),
+        None => python_version.to_string(),
+    };
     let top_comment = format!(
-        "# Generated with dmenv {}, python {}, on {}\n",
-        dmenv_version, &python_version, &python_platform
+        "{} {}, python {}, on {}\n",
+        lock::HEADER_PREFIX,
+        dmenv_version,
+        &python,
+        &python_platform
     );
 
     let to_write = top_comment + lock_contents;
-    std::fs::write(&lock_path, to_write).map_err(|e| new_write_error(e, lock_path))
+    write_with_lf(lock_path, &to_write, dry_run)
+}
+
+// Notes:
+// * always use LF line endings, so that the lock does not change
+//   depending on the platform it was generated on
+// * when running dry, print the contents instead
+fn write_with_lf(lock_path: &Path, contents: &str, dry_run: bool) -> Result<(), Error> {
+    let contents = contents.replace("\r\n", "\n");
+    if dry_run {
+        print_info_2(&format!("Would write {}:", lock_path.display()));
+        print!("{}", contents);
+        return Ok(());
+    }
+    std::fs::write(lock_path, contents).map_err(|e| new_write_error(e, lock_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_metadata() -> Metadata {
+        Metadata {
+            dmenv_version: "0.21.0".to_string(),
+            python_platform: "linux".to_string(),
+            python_version: "3.7.4".to_string(),
+            python_implementation: None,
+        }
+    }
+
+    #[test]
+    fn dry_run_does_not_write_the_lock() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents =
+            "# Generated with dmenv 0.20.0, python 3.7.4, on linux\r\nfoo==0.42\r\nbar==1.3\r\n";
+        std::fs::write(&lock_path, contents).unwrap();
+        let metadata = test_metadata();
+
+        prune(&lock_path, &["foo".to_string()], &metadata, true).unwrap();
+
+        assert_eq!(std::fs::read(&lock_path).unwrap(), contents.as_bytes());
+    }
+
+    #[test]
+    fn test_pin_python() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        std::fs::write(
+            &lock_path,
+            "# Generated with dmenv 0.20.0, python 3.7.4, on Linux\n# by hand\nfoo==0.42\n",
+        )
+        .unwrap();
+        let metadata = Metadata {
+            python_version: "3.11.4".to_string(),
+            python_implementation: Some("CPython".to_string()),
+            ..test_metadata()
+        };
+
+        pin_python(&lock_path, &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "# Generated with dmenv 0.21.0, python 3.11.4 (CPython), on linux\n# by hand\nfoo==0.42\n"
+        );
+        let parsed = lock::parse_metadata(&actual).unwrap();
+        assert_eq!(parsed.python_version, "3.11.4");
+        assert_eq!(parsed.python_implementation.unwrap(), "CPython");
+    }
+
+    #[test]
+    fn test_pin_python_with_crlf() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        std::fs::write(
+            &lock_path,
+            "# Generated with dmenv 0.20.0, python 3.7.4, on linux\r\nfoo==0.42\r\nbar==1.3\r\n",
+        )
+        .unwrap();
+
+        pin_python(&lock_path, &test_metadata(), false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "# Generated with dmenv 0.21.0, python 3.7.4, on linux\nfoo==0.42\nbar==1.3\n"
+        );
+    }
+
+    #[test]
+    fn tidy_keeps_comments() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+# Managed by the release team
+
+# pinned for CVE-2019-42
+foo==0.42
+bar==1.3
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let frozen_deps = vec![FrozenDependency::from_string("foo==0.42".to_string()).unwrap()];
+        let metadata = test_metadata();
+
+        tidy(&lock_path, frozen_deps, &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "\
+# Generated with dmenv 0.21.0, python 3.7.4, on linux
+# Managed by the release team
+
+# pinned for CVE-2019-42
+foo==0.42
+"
+        );
+    }
+
+    #[test]
+    fn upgrade_all_bumps_adds_and_removes() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+# pinned for CVE-2019-42
+foo==0.42
+bar==1.3
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let frozen_deps = vec![
+            FrozenDependency::from_string("baz==2.0".to_string()).unwrap(),
+            FrozenDependency::from_string("foo==0.43".to_string()).unwrap(),
+        ];
+        let metadata = test_metadata();
+
+        upgrade_all(&lock_path, frozen_deps, &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "\
+# Generated with dmenv 0.21.0, python 3.7.4, on linux
+baz==2.0
+# pinned for CVE-2019-42
+foo==0.43
+"
+        );
+    }
+
+    #[test]
+    fn sort_keeps_header_and_is_idempotent() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+Foo==0.42
+# pinned for CVE-2019-42
+bar==1.3
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let metadata = test_metadata();
+        let expected = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+# pinned for CVE-2019-42
+bar==1.3
+Foo==0.42
+";
+
+        sort(&lock_path, &metadata, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&lock_path).unwrap(), expected);
+
+        sort(&lock_path, &metadata, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&lock_path).unwrap(), expected);
+    }
+
+    #[test]
+    fn locks_are_written_with_lf() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on windows\r
+# Do not edit by hand\r
+\r
+foo==0.42 \\\r
+    --hash=sha256:abc\r
+bar==1.3\r
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let metadata = Metadata {
+            python_platform: "windows".to_string(),
+            ..test_metadata()
+        };
+
+        sort(&lock_path, &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "\
+# Generated with dmenv 0.20.0, python 3.7.4, on windows
+# Do not edit by hand
+
+bar==1.3
+foo==0.42 \\
+    --hash=sha256:abc
+"
+        );
+
+        let frozen_deps = vec![FrozenDependency {
+            name: "baz".to_string(),
+            version: "2.0".to_string(),
+            hashes: vec![],
+        }];
+        update(
+            &lock_path,
+            frozen_deps,
+            UpdateLockOptions::default(),
+            &metadata,
+            false,
+        )
+        .unwrap();
+        assert!(!std::fs::read_to_string(&lock_path).unwrap().contains('\r'));
+    }
+
+    #[test]
+    fn constraints_without_upgraded_packages() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "# Generated with dmenv 0.20.0\nbar==1.3\nFoo==0.42\n";
+        std::fs::write(&lock_path, contents).unwrap();
+
+        let actual = constraints_without(&lock_path, &["foo".to_string()]).unwrap();
+
+        assert_eq!(actual, "bar==1.3\n");
+    }
+
+    #[test]
+    fn prune_removes_packages() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+bar==1.3
+# pinned for CVE-2019-42
+foo==0.42
+PyYAML==5.1
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let metadata = test_metadata();
+        let names = &["foo".to_string(), "pyyaml".to_string(), "baz".to_string()];
+
+        prune(&lock_path, names, &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        assert_eq!(
+            actual,
+            "# Generated with dmenv 0.20.0, python 3.7.4, on linux\nbar==1.3\n"
+        );
+    }
+
+    #[test]
+    fn add_inserts_or_updates_packages() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        let contents = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+bar==1.3
+# pinned for CVE-2019-42
+foo==0.42 ; python_version >= '3.6'
+";
+        std::fs::write(&lock_path, contents).unwrap();
+        let metadata = test_metadata();
+
+        add(&lock_path, "baz==2.0", &metadata, false).unwrap();
+        add(&lock_path, "Foo==0.43", &metadata, false).unwrap();
+
+        let actual = std::fs::read_to_string(&lock_path).unwrap();
+        let expected = "\
+# Generated with dmenv 0.20.0, python 3.7.4, on linux
+bar==1.3
+baz==2.0
+# pinned for CVE-2019-42
+foo==0.43 ; python_version >= '3.6'
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn add_rejects_invalid_specs() {
+        let tmp = tempdir::TempDir::new("test-dmenv").unwrap();
+        let lock_path = tmp.path().join("requirements.lock");
+        std::fs::write(&lock_path, "bar==1.3\n").unwrap();
+        let metadata = test_metadata();
+
+        add(&lock_path, "baz>=2.0", &metadata, false).unwrap_err();
+        add(&lock_path, "baz==", &metadata, false).unwrap_err();
+    }
 }
@@ -1,5 +1,11 @@
+pub mod clone;
+pub mod extras;
 mod init;
 pub mod lock;
+pub mod relocate;
 pub mod scripts;
+mod selfcheck;
+pub mod shebangs;
 pub mod venv;
-pub use init::{init, InitOptions};
+pub use init::{init, parse_requirements, InitOptions};
+pub use selfcheck::selfcheck;
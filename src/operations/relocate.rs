@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::operations;
+use crate::paths::SCRIPTS_SUBDIR;
+use crate::python_info::PythonInfo;
+use crate::ui::*;
+use crate::ProcessScriptsMode;
+
+/// Activation scripts written by venv and virtualenv. They contain the
+/// absolute path of the virtualenv
+const ACTIVATE_SCRIPTS: &[&str] = &[
+    "activate",
+    "activate.csh",
+    "activate.fish",
+    "activate.nu",
+    "activate.bat",
+    "deactivate.bat",
+];
+
+/// Make a virtualenv that has been moved usable at its current location:
+/// * the absolute paths in pyvenv.cfg and in the activation scripts
+///   are replaced
+/// * the `home` and `base-*` entries of pyvenv.cfg are set to the given
+///   Python interpreter, if the ones they point to no longer exist
+/// * the shebangs of the scripts are fixed, see `operations::shebangs::fix()`
+//
+// Note: the previous location is read from the activation scripts, since
+// `pyvenv.cfg` does not always contain it
+pub fn relocate(venv_path: &Path, python_info: &PythonInfo) -> Result<(), Error> {
+    operations::venv::expect(venv_path)?;
+    let scripts_path = venv_path.join(SCRIPTS_SUBDIR);
+    let old_venv_path = find_old_venv_path(&scripts_path);
+    let new_venv_path = venv_path.to_string_lossy().to_string();
+    match &old_venv_path {
+        Some(old) => print_info_1(&format!("Relocating {} to {}", old, new_venv_path)),
+        None => print_warning("Could not find the previous location of the virtualenv"),
+    }
+
+    let cfg_path = venv_path.join("pyvenv.cfg");
+    if cfg_path.exists() {
+        let contents =
+            std::fs::read_to_string(&cfg_path).map_err(|e| new_read_error(e, &cfg_path))?;
+        let new_contents = rewrite_pyvenv_cfg(
+            &contents,
+            old_venv_path.as_deref(),
+            &new_venv_path,
+            &python_info.binary,
+        );
+        std::fs::write(&cfg_path, new_contents).map_err(|e| new_write_error(e, &cfg_path))?;
+        print_info_2(&format!("Updated {}", cfg_path.display()));
+    }
+
+    if let Some(old_venv_path) = &old_venv_path {
+        for name in ACTIVATE_SCRIPTS {
+            let path = scripts_path.join(name);
+            if !path.exists() {
+                continue;
+            }
+            let contents = std::fs::read_to_string(&path).map_err(|e| new_read_error(e, &path))?;
+            let new_contents = contents.replace(old_venv_path.as_str(), &new_venv_path);
+            if new_contents != contents {
+                std::fs::write(&path, new_contents).map_err(|e| new_write_error(e, &path))?;
+                print_info_2(&format!("Updated {}", path.display()));
+            }
+        }
+    }
+
+    operations::shebangs::fix(venv_path, ProcessScriptsMode::Safe)?;
+    Ok(())
+}
+
+/// Look for the line setting VIRTUAL_ENV in the activation scripts, for instance:
+/// `VIRTUAL_ENV="/path/to/venv"` (bash) or `set "VIRTUAL_ENV=C:\path\to\venv"` (cmd.exe)
+fn find_old_venv_path(scripts_path: &Path) -> Option<String> {
+    for name in &["activate", "activate.bat"] {
+        let contents = match std::fs::read_to_string(scripts_path.join(name)) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        for line in contents.lines() {
+            let line = line
+                .trim()
+                .trim_start_matches("export ")
+                .trim_start_matches("set ")
+                .trim_matches('"');
+            if let Some(value) = line.strip_prefix("VIRTUAL_ENV=") {
+                let value = value.trim_matches(|c| c == '"' || c == '\'');
+                if !value.is_empty() && !value.contains('$') {
+                    return Some(value.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+// pyvenv.cfg looks like:
+//   home = /usr/bin
+//   include-system-site-packages = false
+//   version = 3.7.4
+//   command = /usr/bin/python3 -m venv /path/to/venv
+// (virtualenv also writes `base-prefix`, `base-exec-prefix` and `base-executable`)
+//
+// Note: entries pointing to the base interpreter are kept when they still exist: the
+// given Python binary may be a wrapper (like the ones from pyenv), while
+// the recorded ones are the real thing
+fn rewrite_pyvenv_cfg(
+    contents: &str,
+    old_venv_path: Option<&str>,
+    new_venv_path: &str,
+    python_binary: &Path,
+) -> String {
+    let home = python_binary.parent().unwrap_or_else(|| Path::new(""));
+    let prefix = python_prefix(home);
+    let mut res = String::new();
+    for line in contents.lines() {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        let new_value = match key {
+            _ if Path::new(value).exists() => None,
+            "home" => Some(home.to_path_buf()),
+            "executable" | "base-executable" => Some(python_binary.to_path_buf()),
+            "base-prefix" | "base-exec-prefix" => Some(prefix.clone()),
+            _ => None,
+        };
+        let new_line = match (new_value, old_venv_path) {
+            (Some(value), _) => format!("{} = {}", key, value.display()),
+            (None, Some(old_venv_path)) => line.replace(old_venv_path, new_venv_path),
+            (None, None) => line.to_string(),
+        };
+        res.push_str(&new_line);
+        res.push('\n');
+    }
+    res
+}
+
+// Note: on Windows, the interpreter lives at the root of its prefix,
+// (`C:\Python37\python.exe`), otherwise it's in the `bin` sub-directory
+fn python_prefix(home: &Path) -> PathBuf {
+    if cfg!(windows) {
+        home.to_path_buf()
+    } else {
+        home.parent().unwrap_or(home).to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_pyvenv_cfg() {
+        let contents = "\
+home = /old/python/bin
+include-system-site-packages = false
+version = 3.7.4
+command = /old/python/bin/python3 -m venv /old/venv
+";
+        let actual = rewrite_pyvenv_cfg(
+            contents,
+            Some("/old/venv"),
+            "/new/venv",
+            Path::new("/usr/bin/python3"),
+        );
+        let expected = "\
+home = /usr/bin
+include-system-site-packages = false
+version = 3.7.4
+command = /old/python/bin/python3 -m venv /new/venv
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rewrite_base_entries() {
+        let contents = "base-prefix = /opt/python\nbase-executable = /opt/python/bin/python3\n";
+        let actual = rewrite_pyvenv_cfg(contents, None, "/new/venv", Path::new("/usr/bin/python3"));
+        assert_eq!(
+            actual,
+            "base-prefix = /usr\nbase-executable = /usr/bin/python3\n"
+        );
+    }
+
+    #[test]
+    fn test_relocate() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_path = tmp_dir.path().join("venv");
+        let scripts_path = venv_path.join(SCRIPTS_SUBDIR);
+        std::fs::create_dir_all(&scripts_path).unwrap();
+        std::fs::write(
+            venv_path.join("pyvenv.cfg"),
+            "home = /usr/bin\nversion = 3.7.4\n",
+        )
+        .unwrap();
+        std::fs::write(
+            scripts_path.join("activate"),
+            "VIRTUAL_ENV=\"/old/venv\"\nexport VIRTUAL_ENV\n",
+        )
+        .unwrap();
+        std::fs::write(
+            scripts_path.join("pytest"),
+            "#!/old/venv/bin/python\nimport pytest\n",
+        )
+        .unwrap();
+        let python_info = PythonInfo {
+            binary: PathBuf::from("/usr/bin/python3"),
+            path: PathBuf::from("/usr/bin/python3"),
+            version: "3.7.4".to_string(),
+            platform: "linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+
+        relocate(&venv_path, &python_info).unwrap();
+
+        let activate = std::fs::read_to_string(scripts_path.join("activate")).unwrap();
+        assert_eq!(
+            activate,
+            format!(
+                "VIRTUAL_ENV=\"{}\"\nexport VIRTUAL_ENV\n",
+                venv_path.display()
+            )
+        );
+        let pytest = std::fs::read_to_string(scripts_path.join("pytest")).unwrap();
+        assert!(pytest.starts_with(&format!("#!{}", scripts_path.display())));
+    }
+
+    #[test]
+    fn test_relocate_missing_venv() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let python_info = PythonInfo {
+            binary: PathBuf::from("/usr/bin/python3"),
+            path: PathBuf::from("/usr/bin/python3"),
+            version: "3.7.4".to_string(),
+            platform: "linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+        relocate(&tmp_dir.path().join("venv"), &python_info).unwrap_err();
+    }
+
+    #[test]
+    fn test_find_old_venv_path_in_bat_file() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        std::fs::write(
+            tmp_dir.path().join("activate.bat"),
+            "@echo off\r\nset \"VIRTUAL_ENV=C:\\old\\venv\"\r\n",
+        )
+        .unwrap();
+        assert_eq!(find_old_venv_path(tmp_dir.path()).unwrap(), "C:\\old\\venv");
+    }
+}
@@ -3,7 +3,7 @@ use ini::Ini;
 use std::path::{Path, PathBuf};
 
 use crate::error::*;
-use crate::paths::{Paths, SCRIPTS_SUBDIR};
+use crate::paths::{self, Paths};
 use crate::ui::*;
 use crate::ProcessScriptsMode::{self, Override, Safe};
 
@@ -19,12 +19,7 @@ pub fn process(paths: &Paths, mode: ProcessScriptsMode) -> Result<(), Error> {
         console_scripts.len()
     ));
     for console_script in console_scripts {
-        process_script(
-            &paths.venv,
-            &scripts_path.to_path_buf(),
-            &console_script,
-            mode,
-        )?;
+        process_script(&paths.venv, scripts_path, &console_script, mode)?;
     }
     Ok(())
 }
@@ -45,7 +40,7 @@ fn process_script(
     ];
 
     for name in names.iter() {
-        process_script_with_name(venv_path, scripts_path, &name, mode)?;
+        process_script_with_name(venv_path, scripts_path, name, mode)?;
     }
     Ok(())
 }
@@ -56,7 +51,15 @@ fn process_script_with_name(
     name: &str,
     mode: ProcessScriptsMode,
 ) -> Result<(), Error> {
-    let src_path = venv_path.join(SCRIPTS_SUBDIR).join(name);
+    let candidates: Vec<_> = paths::scripts_dirs(venv_path)
+        .into_iter()
+        .map(|x| x.join(name))
+        .collect();
+    let src_path = candidates
+        .iter()
+        .find(|x| x.exists())
+        .unwrap_or(&candidates[0])
+        .to_path_buf();
     let dest_path = scripts_path.join(name);
     print_info_2(&format!("Creating script: {}", name.bold()));
     if !src_path.exists() {
@@ -164,7 +167,7 @@ fn delete_if_exists(path: &Path) -> Result<(), Error> {
 
 fn list_egg_info_dirs(project_path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut res = vec![];
-    for entry in std::fs::read_dir(&project_path)? {
+    for entry in std::fs::read_dir(project_path)? {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use crate::error::*;
+use crate::operations::{init, InitOptions};
+use crate::run::run_with_env;
+use crate::ui::*;
+
+// Compile setup.py without running it (it would call `setup()`), and
+// parse setup.cfg if there is one. Only the standard library is used,
+// so that this works with any Python3 interpreter
+const CHECK_SCRIPT: &str = r#"
+import configparser, os, py_compile
+py_compile.compile("setup.py", doraise=True)
+if os.path.exists("setup.cfg"):
+    configparser.ConfigParser().read("setup.cfg")
+"#;
+
+// Placeholders that should be gone, since all the options are set.
+// (<DESCRIPTION> is left for the user to fill in)
+const REPLACED_PLACEHOLDERS: &[&str] = &[
+    "<NAME>",
+    "<VERSION>",
+    "<AUTHOR>",
+    "<AUTHOR_EMAIL>",
+    "<LICENSE>",
+    "<INSTALL_REQUIRES>",
+    "<LICENSE_CLASSIFIER>",
+];
+
+/// Make sure the templates used by `dmenv init` are valid: render them
+/// with dummy values in a temporary directory (with and without setup.cfg),
+/// check that the placeholders are replaced, and have Python parse the result
+pub fn selfcheck(python: &Path) -> Result<(), Error> {
+    for &setup_cfg in &[true, false] {
+        let tmp_dir = tempdir::TempDir::new("dmenv-selfcheck")
+            .map_err(|e| new_error(format!("Could not create temporary directory: {}", e)))?;
+        let tmp_path = tmp_dir.path();
+        let mut init_options = InitOptions::new("selfcheck".to_string(), "0.1.0".to_string());
+        init_options
+            .author("John Doe")
+            .author_email("john@example.com")
+            .license("MIT")
+            .requirements(vec!["attrs".to_string()]);
+        if !setup_cfg {
+            init_options.no_setup_cfg();
+        }
+        init(tmp_path, &init_options)?;
+
+        let mut names = vec!["setup.py"];
+        if setup_cfg {
+            names.push("setup.cfg");
+        }
+        for name in &names {
+            let path = tmp_path.join(name);
+            let contents = std::fs::read_to_string(&path).map_err(|e| new_read_error(e, &path))?;
+            let placeholder = REPLACED_PLACEHOLDERS.iter().find(|x| contents.contains(*x));
+            if let Some(placeholder) = placeholder {
+                return Err(new_error(format!(
+                    "{} template: {} was not replaced",
+                    name, placeholder
+                )));
+            }
+        }
+        run_with_env(tmp_path, python, &["-c", CHECK_SCRIPT], &[])
+            .map_err(|e| new_error(format!("Invalid {} template: {}", names.join(" and "), e)))?;
+        print_info_2(&format!("{}: ok", names.join(" and ")));
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use crate::error::*;
+use crate::paths::{self, SCRIPTS_SUBDIR};
+use crate::ui::*;
+use crate::ProcessScriptsMode::{self, Override, Safe};
+
+/// Rewrite the shebangs of the scripts in the virtualenv so that they point
+/// to the Python binary of the virtualenv.
+/// Returns the number of rewritten scripts.
+//
+// Notes:
+// * This is required when the virtualenv has been moved, because pip writes
+//   the absolute path of the Python binary in the scripts it generates.
+// * Only shebangs whose interpreter is a python binary are considered: other
+//   scripts, including those using `/usr/bin/env python`, are left untouched.
+//   The name of the interpreter (`python3`, `python3.7`, ...) and its
+//   arguments are kept
+// * In Safe mode, scripts with a correct shebang are skipped. In Override mode,
+//   they are re-written too
+// * Scripts in the alternative scripts directories are fixed too, if they
+//   exist, see `paths::scripts_dirs()`. Their shebangs still point to the
+//   Python binary in the usual one
+pub fn fix(venv_path: &Path, mode: ProcessScriptsMode) -> Result<usize, Error> {
+    let python_dir = venv_path.join(SCRIPTS_SUBDIR);
+    let mut res = 0;
+    for (i, scripts_path) in paths::scripts_dirs(venv_path).iter().enumerate() {
+        if i > 0 && !scripts_path.exists() {
+            continue;
+        }
+        res += fix_in_dir(scripts_path, &python_dir, mode)?;
+    }
+    Ok(res)
+}
+
+fn fix_in_dir(
+    scripts_path: &Path,
+    python_dir: &Path,
+    mode: ProcessScriptsMode,
+) -> Result<usize, Error> {
+    print_info_1(&format!("Fixing shebangs in {}", scripts_path.display()));
+
+    let entries = std::fs::read_dir(scripts_path).map_err(|e| new_read_error(e, scripts_path))?;
+    let mut res = 0;
+    for entry in entries {
+        let entry = entry.map_err(|e| new_read_error(e, scripts_path))?;
+        let path = entry.path();
+        // Note: skip symlinks, such as bin/python itself
+        let is_file = std::fs::symlink_metadata(&path)
+            .map(|x| x.file_type().is_file())
+            .unwrap_or(false);
+        if !is_file {
+            continue;
+        }
+        let contents = std::fs::read(&path).map_err(|e| new_read_error(e, &path))?;
+        if let Some(new_contents) = replace_shebang(&contents, python_dir, mode) {
+            std::fs::write(&path, new_contents).map_err(|e| new_write_error(e, &path))?;
+            println!("{}", path.display());
+            res += 1;
+        }
+    }
+    Ok(res)
+}
+
+/// Return the new contents of the script, or None if it should not
+/// be re-written
+// Note: a shebang is considered correct if it points to any binary
+// in the scripts path (pip uses `python3` or `python3.7` for instance)
+// Only the directory of the interpreter is replaced, so that its
+// name and its arguments are kept
+fn replace_shebang(
+    contents: &[u8],
+    scripts_path: &Path,
+    mode: ProcessScriptsMode,
+) -> Option<Vec<u8>> {
+    if !contents.starts_with(b"#!") {
+        return None;
+    }
+    let end_of_line = contents
+        .iter()
+        .position(|&x| x == b'\n')
+        .unwrap_or(contents.len());
+    let first_line = String::from_utf8_lossy(&contents[..end_of_line]);
+    // Note: keep the `\r` of scripts using Windows line endings
+    let (first_line, line_ending) = match first_line.strip_suffix('\r') {
+        Some(line) => (line, "\r"),
+        None => (&first_line[..], ""),
+    };
+    let command = first_line[2..].trim();
+    let (interpreter, args) = match command.find(char::is_whitespace) {
+        Some(i) => command.split_at(i),
+        None => (command, ""),
+    };
+    let interpreter = Path::new(interpreter);
+    let name = interpreter.file_name()?;
+    if !name.to_string_lossy().contains("python") {
+        return None;
+    }
+    let up_to_date = interpreter.parent() == Some(scripts_path);
+    match mode {
+        Safe if up_to_date => return None,
+        Safe | Override => (),
+    }
+    let shebang = format!(
+        "#!{}{}{}",
+        scripts_path.join(name).display(),
+        args,
+        line_ending
+    );
+    let mut res = shebang.into_bytes();
+    res.extend_from_slice(&contents[end_of_line..]);
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(contents: &str, mode: ProcessScriptsMode) -> Option<String> {
+        replace_shebang(contents.as_bytes(), Path::new("/new/venv/bin"), mode)
+            .map(|x| String::from_utf8(x).unwrap())
+    }
+
+    #[test]
+    fn test_replace_outdated_shebang() {
+        let script = "#!/old/venv/bin/python\nimport sys\n";
+        assert_eq!(
+            replace(script, Safe).unwrap(),
+            "#!/new/venv/bin/python\nimport sys\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_correct_shebang_in_safe_mode() {
+        let script = "#!/new/venv/bin/python3.7\nimport sys\n";
+        assert!(replace(script, Safe).is_none());
+        assert_eq!(
+            replace(script, Override).unwrap(),
+            "#!/new/venv/bin/python3.7\nimport sys\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_interpreter_name_and_args() {
+        let script = "#!/old/venv/bin/python3 -E -s\r\nimport sys\n";
+        assert_eq!(
+            replace(script, Safe).unwrap(),
+            "#!/new/venv/bin/python3 -E -s\r\nimport sys\n"
+        );
+    }
+
+    #[test]
+    fn test_ignore_non_python_scripts() {
+        assert!(replace("#!/bin/bash\necho hello\n", Override).is_none());
+        assert!(replace("#!/usr/bin/env python\nimport sys\n", Override).is_none());
+        assert!(replace("# no shebang\n", Override).is_none());
+    }
+
+    #[test]
+    fn test_fix_shebangs_in_venv() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_path = tmp_dir.path().join("venv");
+        let scripts_path = venv_path.join(SCRIPTS_SUBDIR);
+        std::fs::create_dir_all(&scripts_path).unwrap();
+        let pytest_path = scripts_path.join("pytest");
+        std::fs::write(&pytest_path, "#!/old/venv/bin/python\nimport pytest\n").unwrap();
+
+        assert_eq!(fix(&venv_path, Safe).unwrap(), 1);
+        let actual = std::fs::read_to_string(&pytest_path).unwrap();
+        let expected_shebang = format!("#!{}", scripts_path.join("python").display());
+        assert!(actual.starts_with(&expected_shebang));
+
+        assert_eq!(fix(&venv_path, Safe).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_fix_shebangs_in_alternative_scripts_dir() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_path = tmp_dir.path().join("venv");
+        let scripts_path = venv_path.join(SCRIPTS_SUBDIR);
+        std::fs::create_dir_all(&scripts_path).unwrap();
+        let alternative_path = venv_path.join(paths::ALTERNATIVE_SCRIPTS_SUBDIRS[0]);
+        std::fs::create_dir_all(&alternative_path).unwrap();
+        let pytest_path = alternative_path.join("pytest");
+        std::fs::write(&pytest_path, "#!/old/venv/bin/python\nimport pytest\n").unwrap();
+
+        assert_eq!(fix(&venv_path, Safe).unwrap(), 1);
+        let actual = std::fs::read_to_string(&pytest_path).unwrap();
+        let expected_shebang = format!("#!{}", scripts_path.join("python").display());
+        assert!(actual.starts_with(&expected_shebang));
+    }
+}
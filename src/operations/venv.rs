@@ -1,19 +1,64 @@
-use colored::*;
 use std::path::{Path, PathBuf};
 
 use crate::error::*;
-use crate::python_info::PythonInfo;
-use crate::run::run;
-use crate::settings::Settings;
+use crate::python_info::{self, PythonInfo};
+use crate::run::run_with_env;
+use crate::settings::{Settings, VenvCreator};
 use crate::ui::*;
 
-pub fn clean(venv_path: PathBuf) -> Result<(), Error> {
+/// Remove the virtualenv, as well as its parent directories if they
+/// are empty, up to `venv_root`
+pub fn clean(venv_path: PathBuf, venv_root: Option<&Path>) -> Result<(), Error> {
     print_info_1(&format!("Cleaning {}", venv_path.display()));
     if !venv_path.exists() {
         return Ok(());
     }
     std::fs::remove_dir_all(&venv_path)
         .map_err(|e| new_error(format!("could not remove {}: {}", venv_path.display(), e)))?;
+    if let Some(venv_root) = venv_root {
+        remove_empty_parents(&venv_path, venv_root)?;
+    }
+    Ok(())
+}
+
+/// Remove every virtualenv in the venv root, then the venv root itself
+/// if it's empty. Does nothing if the venv root does not exist
+pub fn clean_all(venv_root: &Path) -> Result<(), Error> {
+    if !venv_root.exists() {
+        print_info_1(&format!("Nothing to clean in {}", venv_root.display()));
+        return Ok(());
+    }
+    for path in list_dirs(venv_root)? {
+        print_info_1(&format!("Removing {}", path.display()));
+        std::fs::remove_dir_all(&path)
+            .map_err(|e| new_error(format!("could not remove {}: {}", path.display(), e)))?;
+    }
+    let mut entries = std::fs::read_dir(venv_root).map_err(|e| new_read_error(e, venv_root))?;
+    if entries.next().is_none() {
+        std::fs::remove_dir(venv_root)
+            .map_err(|e| new_error(format!("could not remove {}: {}", venv_root.display(), e)))?;
+        print_info_2(&format!("Removed empty directory {}", venv_root.display()));
+    }
+    Ok(())
+}
+
+// Note: stop at the first directory that is not empty, so that
+// other virtualenvs are kept
+fn remove_empty_parents(venv_path: &Path, venv_root: &Path) -> Result<(), Error> {
+    let mut parent = venv_path.parent();
+    while let Some(dir) = parent {
+        if !dir.starts_with(venv_root) {
+            break;
+        }
+        let mut entries = std::fs::read_dir(dir).map_err(|e| new_read_error(e, dir))?;
+        if entries.next().is_some() {
+            break;
+        }
+        std::fs::remove_dir(dir)
+            .map_err(|e| new_error(format!("could not remove {}: {}", dir.display(), e)))?;
+        print_info_2(&format!("Removed empty directory {}", dir.display()));
+        parent = dir.parent();
+    }
     Ok(())
 }
 
@@ -26,37 +71,233 @@ pub fn create(
         .parent()
         .ok_or_else(|| new_error("venv_path has no parent".to_string()))?;
     print_info_2(&format!("Creating virtualenv in: {}", venv_path.display()));
-    std::fs::create_dir_all(&parent_venv_path).map_err(|e| {
+
+    let (binary, args) = get_create_cmd(venv_path, python_info, settings)?;
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+    print_cmd(&binary, &args);
+    if settings.dry_run {
+        return Ok(());
+    }
+    std::fs::create_dir_all(parent_venv_path).map_err(|e| {
         new_error(format!(
             "Could not create {}: {}",
             parent_venv_path.display(),
             e
         ))
     })?;
+    let cwd = std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
+    let attempts = settings.venv_creation_attempts;
+    let mut attempt = 1;
+    loop {
+        let res = run_with_env(&cwd, &binary, &args, &settings.proxy_env());
+        match res {
+            // Note: only retry when the process actually ran and failed,
+            // (for instance because ensurepip could not reach the network)
+            Err(Error::ProcessFailed { .. }) if attempt < attempts => {
+                let delay = std::time::Duration::from_secs(attempt.into());
+                print_warning(&format!(
+                    "Could not create virtualenv, retrying in {} second(s)",
+                    delay.as_secs()
+                ));
+                std::thread::sleep(delay);
+                attempt += 1;
+                print_info_2(&format!("Attempt {}/{}", attempt, attempts));
+            }
+            _ => return res,
+        }
+    }
+}
 
-    // Python -m venv should work in most cases (venv is in the stdlib since Python 3.3)
+/// Return the binary and the arguments used to create the virtualenv,
+/// depending on `settings.venv_creator`
+fn get_create_cmd(
+    venv_path: &Path,
+    python_info: &PythonInfo,
+    settings: &Settings,
+) -> Result<(PathBuf, Vec<String>), Error> {
     let venv_path_str: String = venv_path.to_string_lossy().into();
-    let mut args = vec!["-m"];
-    if settings.venv_from_stdlib {
-        args.push("venv")
-    } else {
-        // In case we can't or won't use venv from the stdlib, use `virtualenv` instead.
-        // Assume the virtualenv package is present on the system.
-        args.push("virtualenv")
+    let (binary, mut args) = match settings.venv_creator {
+        VenvCreator::Venv => {
+            // Python -m venv should work in most cases (venv is in the stdlib since Python 3.3)
+            // In case we can't or won't use venv from the stdlib, use `virtualenv` instead.
+            // Assume the virtualenv package is present on the system.
+            let module = if settings.venv_from_stdlib {
+                "venv"
+            } else {
+                "virtualenv"
+            };
+            let args = vec!["-m".to_string(), module.to_string(), venv_path_str];
+            (python_info.path.clone(), args)
+        }
+        VenvCreator::Virtualenv => {
+            let binary = which::which("virtualenv").map_err(|_| {
+                new_error(
+                    "venv_creator is set to 'virtualenv', but no `virtualenv` binary was found in PATH"
+                        .to_string(),
+                )
+            })?;
+            // Note: `virtualenv` may not have been installed for the Python
+            // interpreter used by dmenv, so tell it which one to use
+            let python_str = python_info.path.to_string_lossy().to_string();
+            let args = vec!["--python".to_string(), python_str, venv_path_str];
+            (binary, args)
+        }
     };
-    args.push(&venv_path_str);
     if settings.system_site_packages {
-        args.push("--system-site-packages");
-    }
-    let python_binary = &python_info.binary;
-    println!(
-        "{} {} {}",
-        "$".blue(),
-        python_binary.display(),
-        args.join(" ")
-    );
-    let cwd = std::env::current_dir().map_err(|e| Error::NoWorkingDirectory { io_error: e })?;
-    run(&cwd, &python_binary, &args)
+        args.push("--system-site-packages".to_string());
+    }
+    Ok((binary, args))
+}
+
+/// Disk usage of a virtualenv, see `disk_usage()`
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    pub total: u64,
+    // Size of each top-level entry (`bin`, `lib` ...), sorted by name
+    pub by_dir: Vec<(String, u64)>,
+}
+
+/// Compute the size of every file in the virtualenv.
+// Note: symlinks are not followed, and .gitignore files are not
+// taken into account (recent versions of venv add one containing `*`)
+pub fn disk_usage(venv_path: &Path) -> Result<DiskUsage, Error> {
+    let mut res = DiskUsage::default();
+    let mut by_dir = std::collections::BTreeMap::new();
+    let walk = ignore::WalkBuilder::new(venv_path)
+        .standard_filters(false)
+        .build();
+    for entry in walk {
+        let entry = entry
+            .map_err(|e| new_error(format!("could not walk {}: {}", venv_path.display(), e)))?;
+        let is_file = entry.file_type().is_some_and(|x| x.is_file());
+        if !is_file {
+            continue;
+        }
+        let path = entry.path();
+        let size = entry
+            .metadata()
+            .map_err(|e| new_error(format!("could not stat {}: {}", path.display(), e)))?
+            .len();
+        res.total += size;
+        let relative_path = path.strip_prefix(venv_path).unwrap_or(path);
+        if let Some(top) = relative_path.components().next() {
+            let top = top.as_os_str().to_string_lossy().to_string();
+            *by_dir.entry(top).or_insert(0) += size;
+        }
+    }
+    res.by_dir = by_dir.into_iter().collect();
+    Ok(res)
+}
+
+/// Format a size in bytes for humans, for instance `12.3 MiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+/// A virtualenv found in the venv root, see `list()`
+#[derive(Debug)]
+pub struct VenvEntry {
+    // For instance: `dev/3.7.4`
+    pub name: String,
+    pub path: PathBuf,
+    // Read from pyvenv.cfg
+    pub python_version: Option<String>,
+}
+
+/// Sub-directories of the venv root created by dmenv, see `PathsResolver::sub_dir()`
+const KNOWN_KINDS: &[&str] = &["dev", "dev-system", "prod", "prod-system"];
+
+impl VenvEntry {
+    /// Whether the virtualenv does not look like one created by dmenv
+    pub fn is_orphan(&self) -> bool {
+        let kind = self.name.split('/').next().unwrap_or_default();
+        !KNOWN_KINDS.contains(&kind) || self.python_version.is_none()
+    }
+}
+
+/// List the virtualenvs in the venv root, sorted by name
+pub fn list(venv_root: &Path) -> Result<Vec<VenvEntry>, Error> {
+    let mut res = vec![];
+    if !venv_root.exists() {
+        return Ok(res);
+    }
+    for kind_path in list_dirs(venv_root)? {
+        let kind = kind_path.file_name().unwrap_or_default().to_string_lossy();
+        for path in list_dirs(&kind_path)? {
+            let version = path.file_name().unwrap_or_default().to_string_lossy();
+            let name = format!("{}/{}", kind, version);
+            let python_version = read_pyvenv_version(&path);
+            res.push(VenvEntry {
+                name,
+                path,
+                python_version,
+            });
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(res)
+}
+
+fn list_dirs(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = std::fs::read_dir(path).map_err(|e| new_read_error(e, path))?;
+    let mut res = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|e| new_read_error(e, path))?;
+        if entry.path().is_dir() {
+            res.push(entry.path());
+        }
+    }
+    Ok(res)
+}
+
+// pyvenv.cfg looks like:
+//   home = /usr/bin
+//   version = 3.7.4
+// (recent versions of venv write `version_info` instead)
+fn read_pyvenv_version(venv_path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(venv_path.join("pyvenv.cfg")).ok()?;
+    contents.lines().find_map(|line| {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        if key == "version" || key == "version_info" {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Make sure the virtualenv was created with the given Python version.
+// Note: virtualenvs without a pyvenv.cfg file, or with an unknown
+// format, are assumed to be correct
+pub fn check_python_version(venv_path: &Path, python_version: &str) -> Result<(), Error> {
+    let venv_version = match read_pyvenv_version(venv_path) {
+        None => return Ok(()),
+        Some(v) => v,
+    };
+    // `version_info` looks like `3.7.4.final.0`
+    if python_info::version_matches(&venv_version, python_version) {
+        return Ok(());
+    }
+    Err(Error::VenvPythonMismatch {
+        path: venv_path.to_path_buf(),
+        venv_version,
+        python_version: python_version.to_string(),
+    })
 }
 
 pub fn expect(venv_path: &Path) -> Result<(), Error> {
@@ -67,3 +308,199 @@ pub fn expect(venv_path: &Path) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_removes_empty_parents() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_root = tmp_dir.path().join(".venv");
+        let dev_venv = venv_root.join("dev").join("3.7.4");
+        let prod_venv = venv_root.join("prod").join("3.7.4");
+        std::fs::create_dir_all(&dev_venv).unwrap();
+        std::fs::create_dir_all(&prod_venv).unwrap();
+
+        clean(dev_venv, Some(&venv_root)).unwrap();
+        assert!(!venv_root.join("dev").exists());
+        assert!(prod_venv.exists());
+
+        clean(prod_venv, Some(&venv_root)).unwrap();
+        assert!(!venv_root.exists());
+        assert!(tmp_dir.path().exists());
+    }
+
+    #[test]
+    fn test_clean_all() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_root = tmp_dir.path().join(".venv");
+        // Nothing to do
+        clean_all(&venv_root).unwrap();
+
+        std::fs::create_dir_all(venv_root.join("dev").join("3.7.4")).unwrap();
+        std::fs::create_dir_all(venv_root.join("prod").join("3.8.0")).unwrap();
+        clean_all(&venv_root).unwrap();
+        assert!(!venv_root.exists());
+        assert!(tmp_dir.path().exists());
+    }
+
+    #[test]
+    fn test_clean_all_keeps_files() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_root = tmp_dir.path().join(".venv");
+        std::fs::create_dir_all(venv_root.join("dev").join("3.7.4")).unwrap();
+        std::fs::write(venv_root.join("README"), "not a virtualenv").unwrap();
+        clean_all(&venv_root).unwrap();
+        assert!(!venv_root.join("dev").exists());
+        assert!(venv_root.join("README").exists());
+    }
+
+    #[test]
+    fn test_create_cmd_with_venv() {
+        let python_info = PythonInfo {
+            binary: PathBuf::from("/usr/bin/python3"),
+            path: PathBuf::from("/usr/bin/python3.7"),
+            version: "3.7.4".to_string(),
+            platform: "linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+        let settings = Settings {
+            system_site_packages: true,
+            ..Default::default()
+        };
+        let venv_path = Path::new("/work/.venv/dev/3.7.4");
+        let (binary, args) = get_create_cmd(venv_path, &python_info, &settings).unwrap();
+        assert_eq!(binary, python_info.path);
+        assert_eq!(
+            args,
+            &[
+                "-m",
+                "venv",
+                "/work/.venv/dev/3.7.4",
+                "--system-site-packages"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_path = tmp_dir.path();
+        let lib_path = venv_path.join("lib").join("site-packages");
+        std::fs::create_dir_all(&lib_path).unwrap();
+        std::fs::create_dir(venv_path.join("bin")).unwrap();
+        std::fs::write(venv_path.join(".gitignore"), "*\n").unwrap();
+        std::fs::write(venv_path.join("bin").join("python"), vec![0; 100]).unwrap();
+        std::fs::write(lib_path.join("foo.py"), vec![0; 1000]).unwrap();
+        std::fs::write(lib_path.join("bar.py"), vec![0; 20]).unwrap();
+
+        let usage = disk_usage(venv_path).unwrap();
+
+        assert_eq!(usage.total, 1122);
+        assert_eq!(
+            usage.by_dir,
+            &[
+                (".gitignore".to_string(), 2),
+                ("bin".to_string(), 100),
+                ("lib".to_string(), 1020)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(25 * 1024 * 1024), "25.0 MiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_list_venvs() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_root = tmp_dir.path().join(".venv");
+        let prod_venv = venv_root.join("prod").join("3.8.0");
+        let dev_venv = venv_root.join("dev").join("3.7.4");
+        let orphan = venv_root.join("old").join("3.6.0");
+        for path in &[&prod_venv, &dev_venv, &orphan] {
+            std::fs::create_dir_all(path).unwrap();
+        }
+        std::fs::write(
+            dev_venv.join("pyvenv.cfg"),
+            "home = /usr/bin\nversion = 3.7.4\n",
+        )
+        .unwrap();
+        std::fs::write(
+            prod_venv.join("pyvenv.cfg"),
+            "version_info = 3.8.0.final.0\n",
+        )
+        .unwrap();
+
+        let entries = list(&venv_root).unwrap();
+
+        let names: Vec<_> = entries.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, &["dev/3.7.4", "old/3.6.0", "prod/3.8.0"]);
+        assert_eq!(entries[0].python_version.as_deref(), Some("3.7.4"));
+        assert_eq!(entries[2].python_version.as_deref(), Some("3.8.0.final.0"));
+        assert!(!entries[0].is_orphan());
+        assert!(entries[1].is_orphan());
+    }
+
+    #[test]
+    fn test_check_python_version() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let venv_path = tmp_dir.path();
+        check_python_version(venv_path, "3.7.4").unwrap();
+
+        let pyvenv_cfg = venv_path.join("pyvenv.cfg");
+        std::fs::write(&pyvenv_cfg, "version = 3.7.4\n").unwrap();
+        check_python_version(venv_path, "3.7.4").unwrap();
+        check_python_version(venv_path, "3.8.0").unwrap_err();
+
+        std::fs::write(&pyvenv_cfg, "version_info = 3.7.4.final.0\n").unwrap();
+        check_python_version(venv_path, "3.7.4").unwrap();
+        check_python_version(venv_path, "3.7.40").unwrap_err();
+    }
+
+    #[test]
+    fn test_list_venvs_no_root() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let entries = list(&tmp_dir.path().join(".venv")).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_retry_venv_creation() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let tmp_path = tmp_dir.path();
+        // A fake python that always fails, and counts how many times it was called
+        let counter_path = tmp_path.join("counter");
+        let python_path = tmp_path.join("python");
+        let script = format!("#!/bin/sh\necho . >> {}\nexit 1\n", counter_path.display());
+        std::fs::write(&python_path, script).unwrap();
+        std::fs::set_permissions(&python_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let python_info = PythonInfo {
+            binary: python_path.clone(),
+            path: python_path,
+            version: "3.7.4".to_string(),
+            platform: "linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+        let settings = Settings {
+            venv_creation_attempts: 2,
+            ..Default::default()
+        };
+        let venv_path = tmp_path.join("venv");
+
+        create(&venv_path, &python_info, &settings).unwrap_err();
+
+        let counter = std::fs::read_to_string(&counter_path).unwrap();
+        assert_eq!(counter.lines().count(), 2);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::settings::Settings;
 use app_dirs::{AppDataType, AppInfo};
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 
-const APP_INFO: AppInfo = AppInfo {
+pub const APP_INFO: AppInfo = AppInfo {
     name: "dmenv",
     author: "Tanker",
 };
@@ -12,18 +13,41 @@ pub const SCRIPTS_SUBDIR: &str = "bin";
 #[cfg(windows)]
 pub const SCRIPTS_SUBDIR: &str = "Scripts";
 
+/// Other places where scripts may be installed, depending on how Python
+/// was built: Debian's `posix_local` scheme uses `local/bin`, and
+/// MSYS2 / MinGW builds use `bin` on Windows
+#[cfg(unix)]
+pub const ALTERNATIVE_SCRIPTS_SUBDIRS: &[&str] = &["local/bin"];
+#[cfg(windows)]
+pub const ALTERNATIVE_SCRIPTS_SUBDIRS: &[&str] = &["bin"];
+
+/// The directories where scripts may be installed in the given virtualenv,
+/// in order: the usual one (`SCRIPTS_SUBDIR`) first, then the alternative ones
+pub fn scripts_dirs(venv_path: &Path) -> Vec<PathBuf> {
+    let mut res = vec![venv_path.join(SCRIPTS_SUBDIR)];
+    for subdir in ALTERNATIVE_SCRIPTS_SUBDIRS {
+        res.push(venv_path.join(subdir));
+    }
+    res
+}
+
 pub const PROD_LOCK_FILENAME: &str = "production.lock";
 pub const DEV_LOCK_FILENAME: &str = "requirements.lock";
+pub const REQUIREMENTS_IN_FILENAME: &str = "requirements.in";
 
 use crate::error::*;
 
 // Container for all the PathsBuf used by the venv_manager
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Paths {
     pub project: PathBuf,
     pub venv: PathBuf,
+    // Directory containing all the virtualenvs of the project (`.venv` by default),
+    // or None if the virtualenv is not in the project
+    pub venv_root: Option<PathBuf>,
     pub lock: PathBuf,
     pub setup_py: PathBuf,
+    pub requirements_in: PathBuf,
 }
 
 #[derive(Debug)]
@@ -32,7 +56,16 @@ pub struct PathsResolver {
     production: bool,
     system_site_packages: bool,
     python_version: String,
+    python_platform: Option<String>,
     project_path: PathBuf,
+    // Note: `lock_path` and `lock_name` come from `prod_lock_path` and
+    // `prod_lock_name` in production, so that the development and
+    // production locks never get mixed up
+    lock_path: Option<PathBuf>,
+    lock_name: Option<String>,
+    platform_specific_locks: bool,
+    venv_outdir: Option<PathBuf>,
+    ignore_active_venv: bool,
 }
 
 /// Compute paths depending on settings and Python version
@@ -46,28 +79,112 @@ impl PathsResolver {
             venv_outside_project: settings.venv_outside_project,
             project_path,
             python_version,
+            python_platform: None,
             production: settings.production,
             system_site_packages: settings.system_site_packages,
+            lock_path: if settings.production {
+                settings.prod_lock_path.clone()
+            } else {
+                settings.lock_path.clone()
+            },
+            lock_name: if settings.production {
+                settings.prod_lock_name.clone()
+            } else {
+                settings.lock_name.clone()
+            },
+            platform_specific_locks: settings.platform_specific_locks,
+            venv_outdir: settings.venv_outdir.clone(),
+            ignore_active_venv: settings.ignore_active_venv,
         }
     }
 
+    /// Set the platform of the Python interpreter (as returned by
+    /// `platform.system()`), used when `platform_specific_locks` is set
+    pub fn set_python_platform(&mut self, platform: &str) {
+        self.python_platform = Some(platform.to_string());
+    }
+
     pub fn paths(&self) -> Result<Paths, Error> {
-        let lock_path = if self.production {
-            PROD_LOCK_FILENAME
-        } else {
-            DEV_LOCK_FILENAME
-        };
         Ok(Paths {
             project: self.project_path.clone(),
             venv: self.get_venv_path()?,
-            lock: self.project_path.join(lock_path),
+            venv_root: self.get_venv_root()?,
+            lock: self.get_lock_path()?,
             setup_py: self.project_path.join("setup.py"),
+            requirements_in: self.project_path.join(REQUIREMENTS_IN_FILENAME),
         })
     }
 
+    /// Use the lock path from the settings if there is one (so that several
+    /// projects can share the same lock), or a lock in the project path.
+    /// In production, the lock path is `prod_lock_path`, and falls back to
+    /// a lock in the project path when it's not set.
+    /// The name of the lock in the project path defaults to `requirements.lock`
+    /// or `production.lock`, unless `lock_name` or `prod_lock_name` is set
+    /// (respectively).
+    /// When `platform_specific_locks` is set, the platform is added to the
+    /// file name, see `platform_lock_name()`
+    fn get_lock_path(&self) -> Result<PathBuf, Error> {
+        let lock_path = match &self.lock_path {
+            Some(lock_path) => Self::check_shared_lock_path(lock_path)?,
+            None => {
+                let lock_name = match &self.lock_name {
+                    Some(name) => Self::check_lock_name(name)?,
+                    None if self.production => PROD_LOCK_FILENAME,
+                    None => DEV_LOCK_FILENAME,
+                };
+                self.project_path.join(lock_name)
+            }
+        };
+        if !self.platform_specific_locks {
+            return Ok(lock_path);
+        }
+        let platform = self.python_platform.as_ref().ok_or_else(|| {
+            new_error("platform-specific locks: Python platform is unknown".to_string())
+        })?;
+        let file_name = lock_path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(lock_path.with_file_name(platform_lock_name(&file_name, platform)))
+    }
+
+    // Note: use `lock_path` for locks outside the project
+    fn check_lock_name(name: &str) -> Result<&str, Error> {
+        let is_file_name = Path::new(name).file_name() == Some(name.as_ref());
+        if !is_file_name {
+            return Err(new_error(format!(
+                "lock name: {} should be a file name, not a path",
+                name
+            )));
+        }
+        Ok(name)
+    }
+
+    // Note: we don't create the parent directory ourselves: it's likely
+    // there's a typo in the path if it does not exist
+    fn check_shared_lock_path(lock_path: &Path) -> Result<PathBuf, Error> {
+        if !lock_path.is_absolute() {
+            return Err(new_error(format!(
+                "lock path: {} should be absolute",
+                lock_path.display()
+            )));
+        }
+        let parent_exists = lock_path.parent().map(Path::is_dir).unwrap_or(false);
+        if !parent_exists {
+            return Err(new_error(format!(
+                "lock path: parent directory of {} does not exist",
+                lock_path.display()
+            )));
+        }
+        Ok(lock_path.to_path_buf())
+    }
+
     fn get_venv_path(&self) -> Result<PathBuf, Error> {
-        if let Ok(existing_venv) = std::env::var("VIRTUAL_ENV") {
-            return Ok(PathBuf::from(existing_venv));
+        if !self.ignore_active_venv {
+            if let Ok(existing_venv) = std::env::var("VIRTUAL_ENV") {
+                return Ok(PathBuf::from(existing_venv));
+            }
         }
         if self.venv_outside_project {
             self.get_venv_path_outside()
@@ -78,32 +195,56 @@ impl PathsResolver {
 
     fn get_venv_path_inside(&self) -> Result<PathBuf, Error> {
         let subdir = &self.sub_dir();
-        let res = self.project_path.join(".venv").join(subdir);
+        let res = self.get_venv_outdir()?.join(subdir);
         Ok(res)
     }
 
+    // Notes:
+    // * when `venv_outdir` is relative, it's relative to the project path
+    // * when it's outside the project, it's likely shared by several projects
+    //   (a cache directory, or a directory set in a `.dmenv.toml` file at the
+    //   root of a monorepo for instance), so the project name is appended, like
+    //   in `get_venv_path_outside()`
+    fn get_venv_outdir(&self) -> Result<PathBuf, Error> {
+        let outdir = match &self.venv_outdir {
+            None => return Ok(self.project_path.join(".venv")),
+            Some(outdir) => self.project_path.join(outdir),
+        };
+        if outdir.starts_with(&self.project_path) {
+            Ok(outdir)
+        } else {
+            Ok(outdir.join(self.project_name()?))
+        }
+    }
+
+    fn get_venv_root(&self) -> Result<Option<PathBuf>, Error> {
+        let uses_active_venv = !self.ignore_active_venv && std::env::var("VIRTUAL_ENV").is_ok();
+        if uses_active_venv || self.venv_outside_project {
+            return Ok(None);
+        }
+        self.get_venv_outdir().map(Some)
+    }
+
     /// Get a suitable virtualenv path in the HOME directory.
     //
     // Note: use app_dir UserCache so that we honor XDG spec on Linux,
     // and use otherwise "expected" paths on macOS and Windows
     // (`Library/Cachches` and `AppData\Local` respectively)
     fn get_venv_path_outside(&self) -> Result<PathBuf, Error> {
-        let data_dir =
-            app_dirs::app_dir(AppDataType::UserCache, &APP_INFO, "venv").map_err(|e| {
-                new_error(format!(
-                    "Could not create dmenv cache path: {}",
-                    e.to_string()
-                ))
-            })?;
+        let data_dir = app_dirs::app_dir(AppDataType::UserCache, &APP_INFO, "venv")
+            .map_err(|e| new_error(format!("Could not create dmenv cache path: {}", e)))?;
         let subdir = &self.sub_dir();
-        let project_name = self.project_path.file_name().ok_or_else(|| {
+        let res = data_dir.join(subdir).join(self.project_name()?);
+        Ok(res)
+    }
+
+    fn project_name(&self) -> Result<&std::ffi::OsStr, Error> {
+        self.project_path.file_name().ok_or_else(|| {
             new_error(format!(
                 "project path: {} has no file name",
                 self.project_path.display()
             ))
-        })?;
-        let res = data_dir.join(subdir).join(project_name);
-        Ok(res)
+        })
     }
 
     fn sub_dir(&self) -> String {
@@ -117,10 +258,20 @@ impl PathsResolver {
     }
 }
 
+/// Insert the platform, in lower case, before the `.lock` extension:
+/// `requirements.lock` becomes `requirements.linux.lock` on Linux.
+/// The platform is appended to names without this extension
+fn platform_lock_name(name: &str, platform: &str) -> String {
+    let platform = platform.to_lowercase();
+    match name.strip_suffix(".lock") {
+        Some(stem) => format!("{}.{}.lock", stem, platform),
+        None => format!("{}.{}", name, platform),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
 
     fn get_venv_path(project_path: PathBuf, settings: Settings, python_version: &str) -> PathBuf {
         let paths_resolver =
@@ -192,4 +343,202 @@ mod tests {
 
         assert_ne!(default_path, system_packages_path);
     }
+
+    #[test]
+    fn test_resolving_paths_with_relative_outdir() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            venv_outdir: Some(PathBuf::from("venvs")),
+            ..Default::default()
+        };
+        let path = get_venv_path(project_path.to_path_buf(), settings, "3.7");
+        assert_eq!(path, Path::new("/tmp/foo/venvs/dev/3.7"));
+    }
+
+    #[test]
+    fn test_resolving_paths_with_absolute_outdir() {
+        let settings = Settings {
+            venv_outdir: Some(PathBuf::from("/cache/venvs")),
+            ..Default::default()
+        };
+        let foo_path = get_venv_path(PathBuf::from("/tmp/foo"), settings.clone(), "3.7");
+        assert_eq!(foo_path, Path::new("/cache/venvs/foo/dev/3.7"));
+
+        let bar_path = get_venv_path(PathBuf::from("/tmp/bar"), settings, "3.7");
+        assert_ne!(foo_path, bar_path);
+    }
+
+    #[test]
+    fn test_resolving_paths_with_absolute_outdir_in_project() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            venv_outdir: Some(PathBuf::from("/tmp/foo/.envs")),
+            ..Default::default()
+        };
+        let path = get_venv_path(project_path.to_path_buf(), settings, "3.7");
+        assert_eq!(path, Path::new("/tmp/foo/.envs/dev/3.7"));
+    }
+
+    #[test]
+    fn test_venv_root_with_absolute_outdir_is_per_project() {
+        let settings = Settings {
+            venv_outdir: Some(PathBuf::from("/cache/venvs")),
+            ignore_active_venv: true,
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(PathBuf::from("/tmp/foo"), "3.7".to_string(), &settings);
+        let venv_root = paths_resolver.paths().unwrap().venv_root.unwrap();
+        assert_eq!(venv_root, Path::new("/cache/venvs/foo"));
+    }
+
+    fn get_lock_path(lock_path: &Path) -> Result<PathBuf, Error> {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            lock_path: Some(lock_path.to_path_buf()),
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        paths_resolver.paths().map(|x| x.lock)
+    }
+
+    #[test]
+    fn test_resolving_lock_path_in_project_by_default() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings::default();
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join(DEV_LOCK_FILENAME));
+    }
+
+    #[test]
+    fn test_resolving_lock_name() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            lock_name: Some("requirements.ci.lock".to_string()),
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join("requirements.ci.lock"));
+
+        let settings = Settings {
+            lock_name: Some("../ci.lock".to_string()),
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        paths_resolver.paths().unwrap_err();
+    }
+
+    #[test]
+    fn test_resolving_prod_lock_name() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            production: true,
+            lock_name: Some("requirements.ci.lock".to_string()),
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join(PROD_LOCK_FILENAME));
+
+        let settings = Settings {
+            prod_lock_name: Some("production.ci.lock".to_string()),
+            ..settings
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join("production.ci.lock"));
+    }
+
+    #[test]
+    fn test_platform_lock_name() {
+        assert_eq!(
+            platform_lock_name("requirements.lock", "Linux"),
+            "requirements.linux.lock"
+        );
+        assert_eq!(
+            platform_lock_name("production.lock", "Windows"),
+            "production.windows.lock"
+        );
+        assert_eq!(platform_lock_name("ci.txt", "Darwin"), "ci.txt.darwin");
+    }
+
+    #[test]
+    fn test_resolving_platform_specific_lock_path() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings {
+            platform_specific_locks: true,
+            ..Default::default()
+        };
+        let mut paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        paths_resolver.paths().unwrap_err();
+
+        paths_resolver.set_python_platform("Windows");
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join("requirements.windows.lock"));
+    }
+
+    #[test]
+    fn test_platform_is_ignored_by_default() {
+        let project_path = Path::new("/tmp/foo");
+        let settings = Settings::default();
+        let mut paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        paths_resolver.set_python_platform("Linux");
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join(DEV_LOCK_FILENAME));
+    }
+
+    #[test]
+    fn test_resolving_shared_lock_path() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let shared_lock = tmp_dir.path().join("shared.lock");
+        assert_eq!(get_lock_path(&shared_lock).unwrap(), shared_lock);
+    }
+
+    #[test]
+    fn test_resolving_shared_prod_lock_path() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let project_path = Path::new("/tmp/foo");
+        let shared_lock = tmp_dir.path().join("shared.lock");
+        let settings = Settings {
+            production: true,
+            lock_path: Some(shared_lock),
+            ..Default::default()
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, project_path.join(PROD_LOCK_FILENAME));
+
+        let shared_prod_lock = tmp_dir.path().join("shared-production.lock");
+        let settings = Settings {
+            prod_lock_path: Some(shared_prod_lock.clone()),
+            ..settings
+        };
+        let paths_resolver =
+            PathsResolver::new(project_path.to_path_buf(), "3.7".to_string(), &settings);
+        let lock_path = paths_resolver.paths().unwrap().lock;
+        assert_eq!(lock_path, shared_prod_lock);
+    }
+
+    #[test]
+    fn test_shared_lock_path_parent_must_exist() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let shared_lock = tmp_dir.path().join("no-such-dir").join("shared.lock");
+        get_lock_path(&shared_lock).unwrap_err();
+    }
+
+    #[test]
+    fn test_shared_lock_path_must_be_absolute() {
+        get_lock_path(Path::new("shared.lock")).unwrap_err();
+    }
 }
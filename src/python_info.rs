@@ -1,24 +1,54 @@
 use crate::error::*;
-use std::path::PathBuf;
+use crate::paths::APP_INFO;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 /// Represent output of the info.py script
 /// This allows dmenv to know details about
 /// the Python intrepreter it is using.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PythonInfo {
     pub binary: PathBuf,
+    // The interpreter `binary` actually runs, as reported by `sys.executable`.
+    // It differs from `binary` for pyenv shims or wrapper scripts, which pick
+    // an interpreter depending on the environment
+    pub path: PathBuf,
     pub version: String,
     pub platform: String,
+    // As returned by `platform.python_implementation()`: CPython, PyPy ...
+    pub implementation: String,
 }
 
 impl PythonInfo {
+    /// Find the Python binary to use, and run the `info.py` script with it.
+    /// Unless `use_cache` is false, the results are cached, see `read_cache()`
     #[allow(clippy::new_ret_no_self)]
-    pub fn new(python: &Option<String>) -> Result<Self, Error> {
-        let binary = get_python_binary(&python)?;
+    pub fn new(python: &Option<String>, use_cache: bool) -> Result<Self, Error> {
+        let binary = get_python_binary(python)?;
+        let cache_path = match app_dirs::app_root(app_dirs::AppDataType::UserCache, &APP_INFO) {
+            Ok(dir) if use_cache => Some(dir.join(CACHE_FILENAME)),
+            _ => None,
+        };
+        if let Some(cache_path) = &cache_path {
+            if let Some(python_info) = read_cache(cache_path, &binary) {
+                log::debug!("Using cached info for {}", binary.display());
+                return Ok(python_info);
+            }
+        }
+        let python_info = Self::from_binary(binary)?;
+        if let Some(cache_path) = &cache_path {
+            write_cache(cache_path, &python_info);
+        }
+        Ok(python_info)
+    }
+
+    fn from_binary(binary: PathBuf) -> Result<Self, Error> {
         let info_script = include_str!("info.py");
 
         let command = std::process::Command::new(&binary)
-            .args(&["-c", info_script])
+            .args(["-c", info_script])
             .output();
         let command = command.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
         if !command.status.success() {
@@ -33,7 +63,7 @@ impl PythonInfo {
         }
         let info_out = String::from_utf8_lossy(&command.stdout);
         let lines: Vec<_> = info_out.split_terminator('\n').collect();
-        let expected_lines = 2; // Keep this in sync with src/info.py
+        let expected_lines = 4; // Keep this in sync with src/info.py
         if lines.len() != expected_lines {
             return Err(Error::RunInfoPyError {
                 message: format!(
@@ -44,20 +74,109 @@ impl PythonInfo {
         }
         let version = lines[0].trim().to_string();
         let platform = lines[1].trim().to_string();
+        // Note: sys.executable may be empty, for instance when Python is embedded
+        let path = match lines[2].trim() {
+            "" => binary.clone(),
+            executable => PathBuf::from(executable),
+        };
+        let implementation = lines[3].trim().to_string();
         Ok(PythonInfo {
             binary,
+            path,
             version,
             platform,
+            implementation,
         })
     }
 }
 
-/// Look for a suitable Python binary in PATH
-// Note: doses not get called if `dmenv` was invoked with an explicit `--python`
-// option.
+const CACHE_FILENAME: &str = "python-info.json";
+
+/// Cached output of the `info.py` script, along with the modification
+/// time of the binary, so that the entry is discarded when the
+/// interpreter is re-installed or upgraded
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    modified: (u64, u32),
+    python_info: PythonInfo,
+}
+
+type Cache = BTreeMap<PathBuf, CacheEntry>;
+
+/// Return the cached info for the given binary, if any. Errors
+/// are ignored, they just mean the cache can't be used
+fn read_cache(cache_path: &Path, binary: &Path) -> Option<PythonInfo> {
+    let modified = modified_time(binary)?;
+    let contents = std::fs::read_to_string(cache_path).ok()?;
+    let mut cache: Cache = serde_json::from_str(&contents).ok()?;
+    let entry = cache.remove(binary)?;
+    if entry.modified != modified || !entry.python_info.path.exists() {
+        return None;
+    }
+    Some(entry.python_info)
+}
+
+/// Add the info to the cache.
+// Notes:
+// * Wrappers (like pyenv shims) are not cached: the interpreter they run
+//   depends on the environment, and their modification time does not change
+//   when it does
+// * Writing the cache is not critical, so errors are only logged
+fn write_cache(cache_path: &Path, python_info: &PythonInfo) {
+    let canonicalize = |x: &Path| std::fs::canonicalize(x).ok();
+    let is_wrapper = canonicalize(&python_info.binary) != canonicalize(&python_info.path);
+    let modified = match modified_time(&python_info.binary) {
+        Some(modified) if !is_wrapper => modified,
+        _ => return,
+    };
+    let mut cache: Cache = std::fs::read_to_string(cache_path)
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default();
+    let python_info = PythonInfo {
+        binary: python_info.binary.clone(),
+        path: python_info.path.clone(),
+        version: python_info.version.clone(),
+        platform: python_info.platform.clone(),
+        implementation: python_info.implementation.clone(),
+    };
+    let entry = CacheEntry {
+        modified,
+        python_info,
+    };
+    cache.insert(entry.python_info.binary.clone(), entry);
+    let res = serde_json::to_string(&cache)
+        .map_err(|e| e.to_string())
+        .and_then(|x| std::fs::write(cache_path, x).map_err(|e| e.to_string()));
+    if let Err(e) = res {
+        log::debug!("Could not write {}: {}", cache_path.display(), e);
+    }
+}
+
+// Note: follows symlinks, so this is the modification time of the
+// actual interpreter
+fn modified_time(binary: &Path) -> Option<(u64, u32)> {
+    let modified = std::fs::metadata(binary).ok()?.modified().ok()?;
+    let duration = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some((duration.as_secs(), duration.subsec_nanos()))
+}
+
+/// Look for a suitable Python binary in PATH, unless `dmenv` was
+/// invoked with an explicit `--python` option
 fn get_python_binary(requested_python: &Option<String>) -> Result<PathBuf, Error> {
     if let Some(python) = requested_python {
-        return Ok(PathBuf::from(python));
+        let python = &expand_env_vars(python)?;
+        if let Some(version) = parse_py_launcher_spec(python)? {
+            return python_from_py_launcher(version);
+        }
+        // Note: `which` also checks that the binary is executable,
+        // and allows using `--python python3.8`
+        return which::which(python).map_err(|_| {
+            new_error(format!(
+                "python setting: {} does not exist or is not executable",
+                python
+            ))
+        });
     }
 
     if let Ok(python3) = which::which("python3") {
@@ -67,3 +186,294 @@ fn get_python_binary(requested_python: &Option<String>) -> Result<PathBuf, Error
     which::which("python")
         .map_err(|_| new_error("Neither `python3` nor `python` found in PATH".to_string()))
 }
+
+/// Parse values like `py:3.11`, meaning "ask the `py` launcher
+/// for Python 3.11". Returns None for regular binaries.
+fn parse_py_launcher_spec(python: &str) -> Result<Option<&str>, Error> {
+    let version = match python.strip_prefix("py:") {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    // Note: the launcher also accepts suffixes like `-32` or `-64`
+    let re = regex::Regex::new(r"^\d+(\.\d+)?(-(32|64))?$").unwrap();
+    if !re.is_match(version) {
+        return Err(new_error(format!(
+            "python setting: {} should look like `py:3.11`",
+            python
+        )));
+    }
+    Ok(Some(version))
+}
+
+/// Use the `py` launcher (usually found on Windows) to get the
+/// path of the interpreter matching the given version.
+// Note: the launcher itself is not used to run commands, since its
+// behavior depends on the current directory (shebangs, py.ini files ...)
+fn python_from_py_launcher(version: &str) -> Result<PathBuf, Error> {
+    let launcher = which::which("py").map_err(|_| {
+        new_error(format!(
+            "python setting: py:{} requires the `py` launcher, but it was not found in PATH",
+            version
+        ))
+    })?;
+    let version_arg = format!("-{}", version);
+    let command = std::process::Command::new(&launcher)
+        .args([&version_arg, "-c", "import sys; print(sys.executable)"])
+        .output();
+    let command = command.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
+    if !command.status.success() {
+        return Err(new_error(format!(
+            "python setting: `py {}` failed:\n{}",
+            version_arg,
+            String::from_utf8_lossy(&command.stderr)
+        )));
+    }
+    let executable = String::from_utf8_lossy(&command.stdout).trim().to_string();
+    if executable.is_empty() {
+        return Err(new_error(format!(
+            "python setting: `py {}` did not return any interpreter path",
+            version_arg
+        )));
+    }
+    Ok(PathBuf::from(executable))
+}
+
+/// Replace `${VAR}` with the value of the VAR environment variable,
+/// so that `--python '${PYENV_ROOT}/versions/3.7.4/bin/python'` works
+/// for everyone.
+fn expand_env_vars(text: &str) -> Result<String, Error> {
+    let mut res = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| new_error(format!("python setting: unterminated `${{` in {}", text)))?;
+        let name = &rest[start + 2..start + end];
+        let value = std::env::var(name).map_err(|_| {
+            new_error(format!(
+                "python setting: environment variable {} is not set",
+                name
+            ))
+        })?;
+        res.push_str(&rest[..start]);
+        res.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    res.push_str(rest);
+    Ok(res)
+}
+
+/// Look for a `.python-version` file (as used by pyenv) in the project
+/// path, and return the path of the matching Python binary.
+/// Returns None if there is no such file, or if it contains `system`.
+pub fn python_from_version_file(project_path: &Path) -> Result<Option<String>, Error> {
+    let version_file = project_path.join(".python-version");
+    let version = match read_python_version_file(project_path)? {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    let binary = find_python_for_version(&version).ok_or_else(|| {
+        new_error(format!(
+            "{}: could not find a Python interpreter for version {}",
+            version_file.display(),
+            version
+        ))
+    })?;
+    Ok(Some(binary.to_string_lossy().to_string()))
+}
+
+/// Return the Python version requested by the `.python-version` file
+/// in the project path, if any
+pub fn read_python_version_file(project_path: &Path) -> Result<Option<String>, Error> {
+    let version_file = project_path.join(".python-version");
+    if !version_file.exists() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(&version_file).map_err(|e| new_read_error(e, &version_file))?;
+    Ok(parse_python_version_file(&contents))
+}
+
+// Note: pyenv allows several versions, one per line - we
+// only care about the first one
+fn parse_python_version_file(contents: &str) -> Option<String> {
+    let version = contents
+        .lines()
+        .map(|x| x.trim())
+        .find(|x| !x.is_empty() && !x.starts_with('#'))?;
+    if version == "system" {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+/// Return true if `version` is `requested`, or a more precise version
+/// of it: `3.7.4` matches `3.7`, but `3.70.1` does not
+pub fn version_matches(version: &str, requested: &str) -> bool {
+    version == requested || version.starts_with(&format!("{}.", requested))
+}
+
+/// Look in pyenv installation first, then for a `pythonX.Y` binary in PATH
+fn find_python_for_version(version: &str) -> Option<PathBuf> {
+    let pyenv_root = std::env::var_os("PYENV_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|x| Path::new(&x).join(".pyenv")));
+    if let Some(pyenv_root) = pyenv_root {
+        let prefix = pyenv_root.join("versions").join(version);
+        #[cfg(unix)]
+        let candidate = prefix.join("bin").join("python");
+        #[cfg(windows)]
+        let candidate = prefix.join("python.exe");
+        if candidate.exists() {
+            return Some(candidate);
+        }
+    }
+
+    let short_version: Vec<_> = version.split('.').take(2).collect();
+    which::which(format!("python{}", short_version.join("."))).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_py_launcher_spec() {
+        assert_eq!(parse_py_launcher_spec("python3.7").unwrap(), None);
+        assert_eq!(parse_py_launcher_spec("py:3.11").unwrap(), Some("3.11"));
+        assert_eq!(parse_py_launcher_spec("py:3").unwrap(), Some("3"));
+        assert_eq!(parse_py_launcher_spec("py:3.8-32").unwrap(), Some("3.8-32"));
+        parse_py_launcher_spec("py:").unwrap_err();
+        parse_py_launcher_spec("py:latest").unwrap_err();
+    }
+
+    #[test]
+    fn test_cache() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let cache_path = tmp_dir.path().join(CACHE_FILENAME);
+        let binary = tmp_dir.path().join("python3.7");
+        std::fs::write(&binary, "").unwrap();
+        let python_info = PythonInfo {
+            binary: binary.clone(),
+            path: binary.clone(),
+            version: "3.7.4".to_string(),
+            platform: "Linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+        assert!(read_cache(&cache_path, &binary).is_none());
+
+        write_cache(&cache_path, &python_info);
+        let cached = read_cache(&cache_path, &binary).unwrap();
+        assert_eq!(cached.version, "3.7.4");
+        assert_eq!(cached.platform, "Linux");
+
+        // Re-installing the interpreter invalidates the cache
+        let file = std::fs::File::options().write(true).open(&binary).unwrap();
+        file.set_modified(UNIX_EPOCH).unwrap();
+        assert!(read_cache(&cache_path, &binary).is_none());
+    }
+
+    #[test]
+    fn test_wrappers_are_not_cached() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let cache_path = tmp_dir.path().join(CACHE_FILENAME);
+        let shim = tmp_dir.path().join("python3");
+        let binary = tmp_dir.path().join("python3.7");
+        std::fs::write(&shim, "").unwrap();
+        std::fs::write(&binary, "").unwrap();
+        let python_info = PythonInfo {
+            binary: shim.clone(),
+            path: binary,
+            version: "3.7.4".to_string(),
+            platform: "Linux".to_string(),
+            implementation: "CPython".to_string(),
+        };
+        write_cache(&cache_path, &python_info);
+        assert!(read_cache(&cache_path, &shim).is_none());
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("DMENV_TEST_PYENV_ROOT", "/home/jane/.pyenv");
+        assert_eq!(
+            expand_env_vars("${DMENV_TEST_PYENV_ROOT}/versions/3.7.4/bin/python").unwrap(),
+            "/home/jane/.pyenv/versions/3.7.4/bin/python"
+        );
+    }
+
+    #[test]
+    fn test_expand_env_vars_untouched() {
+        assert_eq!(
+            expand_env_vars("/usr/bin/$python3").unwrap(),
+            "/usr/bin/$python3"
+        );
+    }
+
+    #[test]
+    fn test_expand_env_vars_unset() {
+        let err = expand_env_vars("${DMENV_TEST_NO_SUCH_VAR}/bin/python").unwrap_err();
+        assert!(err.to_string().contains("DMENV_TEST_NO_SUCH_VAR"));
+    }
+
+    #[test]
+    fn test_expand_env_vars_unterminated() {
+        expand_env_vars("${PYENV_ROOT/bin/python").unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_python_version_file() {
+        assert_eq!(
+            parse_python_version_file("\n3.7.4\n3.8.0\n"),
+            Some("3.7.4".to_string())
+        );
+        assert_eq!(parse_python_version_file("system\n"), None);
+        assert_eq!(parse_python_version_file(""), None);
+    }
+
+    #[test]
+    fn test_version_matches() {
+        assert!(version_matches("3.7.4", "3.7.4"));
+        assert!(version_matches("3.7.4", "3.7"));
+        assert!(version_matches("3.7.4", "3"));
+        assert!(!version_matches("3.70.1", "3.7"));
+        assert!(!version_matches("3.7", "3.7.4"));
+    }
+
+    #[test]
+    fn test_python_version_file_not_found() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        std::fs::write(tmp_dir.path().join(".python-version"), "2.1.42\n").unwrap();
+        let err = python_from_version_file(tmp_dir.path()).unwrap_err();
+        assert!(err.to_string().contains(".python-version"));
+    }
+
+    #[test]
+    fn test_no_python_version_file() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        assert!(python_from_version_file(tmp_dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_requested_python_does_not_exist() {
+        let requested = Some("/no/such/python".to_string());
+        let err = get_python_binary(&requested).unwrap_err();
+        assert!(err.to_string().contains("/no/such/python"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_requested_python_not_executable() {
+        let tmp_dir = tempdir::TempDir::new("test-dmenv").unwrap();
+        let python_path = tmp_dir.path().join("python");
+        std::fs::write(&python_path, "").unwrap();
+        let requested = Some(python_path.to_string_lossy().to_string());
+        get_python_binary(&requested).unwrap_err();
+    }
+
+    #[test]
+    fn test_requested_python_in_path() {
+        let python = get_python_binary(&None).unwrap();
+        let requested = Some(python.to_string_lossy().to_string());
+        assert_eq!(get_python_binary(&requested).unwrap(), python);
+    }
+}
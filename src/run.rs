@@ -1,6 +1,5 @@
 use std::path::{Path, PathBuf};
-
-use colored::*;
+use std::time::{Duration, Instant};
 
 use crate::error::*;
 #[cfg(unix)]
@@ -8,12 +7,16 @@ use crate::execv::execv;
 #[cfg(windows)]
 use crate::win_job;
 
-use crate::paths::SCRIPTS_SUBDIR;
+use crate::paths::{self, SCRIPTS_SUBDIR};
+use crate::ui::*;
+use crate::RunOptions;
 
 #[derive(Debug)]
 pub struct VenvRunner {
     project_path: PathBuf,
     venv_path: PathBuf,
+    dry_run: bool,
+    progress: bool,
 }
 
 #[derive(Debug)]
@@ -37,12 +40,8 @@ impl RunnableCommand {
     }
 
     pub fn print_self(&self) {
-        println!(
-            "{} {} {}",
-            "$".blue(),
-            self.binary_path.display(),
-            self.args.join(" ")
-        );
+        let args: Vec<&str> = self.args.iter().map(AsRef::as_ref).collect();
+        print_cmd(&self.binary_path, &args);
     }
 }
 
@@ -51,65 +50,368 @@ impl VenvRunner {
         VenvRunner {
             project_path: project_path.to_path_buf(),
             venv_path: venv_path.to_path_buf(),
+            dry_run: false,
+            progress: false,
         }
     }
 
-    pub fn run_and_die<T: AsRef<str>>(&self, cmd: &[T]) -> Result<(), Error> {
+    /// When `dry_run` is true, commands are printed but never run,
+    /// and `get_output()` returns an empty string
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// When `progress` is true, the output of pip is replaced by
+    /// the phases it goes through, see `run_with_progress()`
+    pub fn set_progress(&mut self, progress: bool) {
+        self.progress = progress;
+    }
+
+    pub fn run_and_die<T: AsRef<str>>(&self, cmd: &[T], options: &RunOptions) -> Result<(), Error> {
+        // Note: on Windows, this must be done before any process is
+        // started, so that they are all killed when dmenv exits
+        #[cfg(windows)]
+        unsafe {
+            win_job::setup();
+        }
+
+        // Note: with a timeout, we need to stay around to kill the
+        // process, and when capturing, to print its output,
+        // so execv() cannot be used
+        if self.dry_run || options.timeout.is_some() || options.capture {
+            return self.run_with_options(cmd, options);
+        }
+
         #[cfg(windows)]
         {
-            unsafe {
-                win_job::setup();
-            }
-            self.run(cmd)
+            self.run_with_options(cmd, options)
         }
 
         #[cfg(unix)]
         {
-            let runnable = self.get_runnable(cmd)?;
+            let working_path = self.working_path(options)?;
+            let runnable = self.get_runnable_with_options(cmd, options)?;
             runnable.print_self();
             let mut cmd: Vec<&str> = runnable.args.iter().map(AsRef::as_ref).collect();
             let arg0 = &runnable.binary_path;
             let arg0 = arg0
                 .to_str()
                 .ok_or_else(|| new_error(format!("Could not convert {:?} to string", arg0)))?;
-            cmd.insert(0, &arg0);
+            cmd.insert(0, arg0);
+            // Note: this does not leak into dmenv's environment, since
+            // the dmenv process is replaced by execv() right after
+            for (key, value) in &options.env {
+                std::env::set_var(key, value);
+            }
+            std::env::set_current_dir(&working_path)
+                .map_err(|e| new_error(format!("Could not change directory: {}", e)))?;
             execv(arg0, &cmd)
         }
     }
 
     pub fn run<T: AsRef<str>>(&self, cmd: &[T]) -> Result<(), Error> {
-        let runnable = self.get_runnable(cmd)?;
+        self.run_with_options(cmd, &RunOptions::default())
+    }
+
+    pub fn run_with_options<T: AsRef<str>>(
+        &self,
+        cmd: &[T],
+        options: &RunOptions,
+    ) -> Result<(), Error> {
+        let working_path = self.working_path(options)?;
+        let runnable = self.get_runnable_with_options(cmd, options)?;
         runnable.print_self();
-        run(&self.project_path, &runnable.binary_path, &runnable.args)
+        if self.dry_run {
+            return Ok(());
+        }
+        let args: Vec<&str> = runnable.args.iter().map(AsRef::as_ref).collect();
+        let is_pip = process_name(&runnable.binary_path, &args) == "pip";
+        if self.progress && is_pip && options.timeout.is_none() && !options.capture {
+            return run_with_progress(
+                &working_path,
+                &runnable.binary_path,
+                &runnable.args,
+                &options.env,
+            );
+        }
+        if options.capture {
+            return run_captured(
+                &working_path,
+                &runnable.binary_path,
+                &runnable.args,
+                &options.env,
+            );
+        }
+        match options.timeout {
+            None => run_with_env(
+                &working_path,
+                &runnable.binary_path,
+                &runnable.args,
+                &options.env,
+            ),
+            Some(timeout) => run_with_timeout(
+                &working_path,
+                &runnable.binary_path,
+                &runnable.args,
+                &options.env,
+                timeout,
+            ),
+        }
+    }
+
+    /// Directory in which commands are run: the project path,
+    /// unless `options.cwd` is set
+    // Note: relative paths are relative to the project path
+    fn working_path(&self, options: &RunOptions) -> Result<PathBuf, Error> {
+        let cwd = match &options.cwd {
+            None => return Ok(self.project_path.clone()),
+            Some(cwd) => self.project_path.join(cwd),
+        };
+        if !cwd.is_dir() {
+            return Err(new_error(format!(
+                "Cannot run in {}: no such directory",
+                cwd.display()
+            )));
+        }
+        Ok(cwd)
     }
 
     pub fn get_output<T: AsRef<str>>(&self, cmd: &[T]) -> Result<String, Error> {
+        let (stdout, _) = self.get_output_and_stderr(cmd)?;
+        Ok(stdout)
+    }
+
+    /// Same as `get_output`, but also return the error output
+    /// of the command
+    pub fn get_output_and_stderr<T: AsRef<str>>(
+        &self,
+        cmd: &[T],
+    ) -> Result<(String, String), Error> {
         let runnable = self.get_runnable(cmd)?;
+        if self.dry_run {
+            runnable.print_self();
+            return Ok((String::new(), String::new()));
+        }
         get_output(&self.project_path, &runnable.binary_path, &runnable.args)
     }
 
+    /// Return the absolute path of the binary that would be run for
+    /// the given command, followed by its arguments
+    pub fn resolve_command<T: AsRef<str>>(
+        &self,
+        cmd: &[T],
+        options: &RunOptions,
+    ) -> Result<Vec<String>, Error> {
+        let runnable = self.get_runnable_with_options(cmd, options)?;
+        let mut res = vec![runnable.binary_path.to_string_lossy().to_string()];
+        res.extend(runnable.args);
+        Ok(res)
+    }
+
+    /// Same as `run`, but return whether the command succeeded, along with
+    /// its error output.
+    // Note: contrary to `get_output_and_stderr()`, the error output is still
+    // printed as it occurs, and so is the output (or its summary, when
+    // `progress` is true, see `run_with_progress()`)
+    pub fn run_and_get_stderr<T: AsRef<str>>(&self, cmd: &[T]) -> Result<(bool, String), Error> {
+        let runnable = self.get_runnable(cmd)?;
+        runnable.print_self();
+        if self.dry_run {
+            return Ok((true, String::new()));
+        }
+        let mut args: Vec<&str> = runnable.args.iter().map(AsRef::as_ref).collect();
+        let summarize = self.progress && process_name(&runnable.binary_path, &args) == "pip";
+        let stdout = if summarize {
+            args.push(NO_INPUT_OPTION);
+            std::process::Stdio::piped()
+        } else {
+            std::process::Stdio::inherit()
+        };
+        let mut child = std::process::Command::new(&runnable.binary_path)
+            .args(&args)
+            .current_dir(&self.project_path)
+            .stdin(std::process::Stdio::inherit())
+            .stdout(stdout)
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::StartProcessError {
+                message: format!("could not start {}: {}", runnable.binary_path.display(), e),
+            })?;
+        let stderr = tee_stderr(&mut child);
+        let status = if summarize {
+            wait_summarizing_pip_output(&mut child)?
+        } else {
+            child
+                .wait()
+                .map_err(|e| Error::WaitProcessError { io_error: e })?
+        };
+        let stderr = stderr.join().unwrap_or_default();
+        Ok((status.success(), stderr))
+    }
+
+    fn get_runnable_with_options<T: AsRef<str>>(
+        &self,
+        cmd: &[T],
+        options: &RunOptions,
+    ) -> Result<RunnableCommand, Error> {
+        let runnable = self.get_runnable(cmd);
+        if runnable.is_ok() || !options.no_venv_check {
+            return runnable;
+        }
+        Self::get_runnable_from_path(cmd)
+    }
+
+    /// Look for the binary in PATH instead of the virtualenv.
+    // Note: only used with `dmenv run --no-venv-check`
+    fn get_runnable_from_path<T: AsRef<str>>(cmd: &[T]) -> Result<RunnableCommand, Error> {
+        let name = cmd[0].as_ref();
+        let binary_path = which::which(name).map_err(|_| {
+            new_error(format!(
+                "Cannot run: {} found neither in the virtualenv nor in PATH",
+                name
+            ))
+        })?;
+        print_warning(&format!(
+            "{} not found in the virtualenv, using {} from PATH",
+            name,
+            binary_path.display()
+        ));
+        RunnableCommand::new(&binary_path, &cmd[1..])
+    }
+
     fn get_runnable<T: AsRef<str>>(&self, cmd: &[T]) -> Result<RunnableCommand, Error> {
         let first_arg = &cmd[0].as_ref();
         if first_arg.ends_with(".py") {
             let script_path = self.project_path.join(first_arg);
             if script_path.exists() {
                 let python_binary = self.get_binary_path("python");
-                return RunnableCommand::new(&python_binary, &cmd);
+                return self.new_runnable(&python_binary, cmd);
             }
         }
 
-        let binary_path = self.get_binary_path(&cmd[0].as_ref());
+        let binary_path = self.find_binary(cmd[0].as_ref())?;
         let args = &cmd[1..];
-        RunnableCommand::new(&binary_path, args)
+        self.new_runnable(&binary_path, args)
+    }
+
+    // Note: when running dry, the virtualenv may not have been
+    // created, so don't check the binary exists
+    fn new_runnable<T: AsRef<str>>(
+        &self,
+        binary_path: &Path,
+        args: &[T],
+    ) -> Result<RunnableCommand, Error> {
+        if !self.dry_run {
+            return RunnableCommand::new(binary_path, args);
+        }
+        Ok(RunnableCommand {
+            binary_path: binary_path.to_path_buf(),
+            args: args.iter().map(|x| x.as_ref().to_string()).collect(),
+        })
+    }
+
+    /// Return the path of the given binary in the virtualenv
+    /// (for instance, `.venv/dev/3.7/bin/pytest`), or an error if it does not exist
+    pub fn resolve_binary(&self, name: &str) -> Result<PathBuf, Error> {
+        let binary_path = self.get_binary_path(name);
+        if binary_path.exists() {
+            return Ok(binary_path);
+        }
+        Err(self.binary_not_found(name))
+    }
+
+    // Like `get_binary_path()`, but return an error listing the directories
+    // searched if the binary is not found.
+    // Note: when running dry, the virtualenv may not have been
+    // created, so don't check the binary exists
+    fn find_binary(&self, name: &str) -> Result<PathBuf, Error> {
+        if self.dry_run {
+            return Ok(self.get_binary_path(name));
+        }
+        self.resolve_binary(name)
+    }
+
+    fn binary_not_found(&self, name: &str) -> Error {
+        let searched: Vec<_> = self
+            .scripts_dirs()
+            .iter()
+            .map(|x| x.display().to_string())
+            .collect();
+        new_error(format!(
+            "Cannot run: {} not found in the virtualenv (searched in: {})",
+            Self::get_binary_name(name),
+            searched.join(", ")
+        ))
     }
 
     pub fn binaries_path(&self) -> PathBuf {
         self.venv_path.join(SCRIPTS_SUBDIR)
     }
 
+    /// List the names of the binaries in the virtualenv that can be
+    /// used with `dmenv run`, sorted and without duplicates.
+    // Notes:
+    // * On Unix, only files with the executable bit set are listed
+    //   (this skips the `activate` scripts). On Windows, only `.exe` files
+    //   are, and the suffix is removed
+    // * Alternative scripts directories are skipped if they do not exist
+    pub fn list_binaries(&self) -> Result<Vec<String>, Error> {
+        let mut res = vec![];
+        for (i, scripts_dir) in self.scripts_dirs().iter().enumerate() {
+            if i > 0 && !scripts_dir.exists() {
+                continue;
+            }
+            let entries =
+                std::fs::read_dir(scripts_dir).map_err(|e| new_read_error(e, scripts_dir))?;
+            for entry in entries {
+                let entry = entry.map_err(|e| new_read_error(e, scripts_dir))?;
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+                if let Some(name) = Self::binary_name_from_path(&path) {
+                    res.push(name);
+                }
+            }
+        }
+        res.sort();
+        res.dedup();
+        Ok(res)
+    }
+
+    #[cfg(unix)]
+    fn binary_name_from_path(path: &Path) -> Option<String> {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path).ok()?.permissions().mode();
+        if mode & 0o111 == 0 {
+            return None;
+        }
+        Some(path.file_name()?.to_string_lossy().to_string())
+    }
+
+    #[cfg(windows)]
+    fn binary_name_from_path(path: &Path) -> Option<String> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        if extension != "exe" {
+            return None;
+        }
+        Some(path.file_stem()?.to_string_lossy().to_string())
+    }
+
+    /// The directories where binaries are looked for, see `paths::scripts_dirs()`
+    pub fn scripts_dirs(&self) -> Vec<PathBuf> {
+        paths::scripts_dirs(&self.venv_path)
+    }
+
+    // Note: the path in the usual directory is returned if the binary
+    // is not found anywhere
     fn get_binary_path(&self, name: &str) -> PathBuf {
         let binary_name = Self::get_binary_name(name);
-        self.binaries_path().join(&binary_name)
+        self.scripts_dirs()
+            .into_iter()
+            .map(|x| x.join(&binary_name))
+            .find(|x| x.exists())
+            .unwrap_or_else(|| self.binaries_path().join(&binary_name))
     }
 
     fn get_binary_name(name: &str) -> String {
@@ -121,28 +423,365 @@ impl VenvRunner {
     }
 }
 
-pub fn run<T: AsRef<str>>(
+/// Run the given binary, setting the given environment variables
+/// in the child process
+// Note: stdin, stdout and stderr are explicitly inherited, so that
+// interactive programs (like the Python REPL) work, even when dmenv
+// itself is run by another program
+pub fn run_with_env<T: AsRef<str>>(
     working_path: &Path,
     binary_path: &Path,
     args: &[T],
+    env: &[(String, String)],
 ) -> Result<(), Error> {
     let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
     let command = std::process::Command::new(binary_path)
-        .args(args)
+        .args(&args)
+        .envs(env.iter().cloned())
         .current_dir(working_path)
+        .stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
         .status();
     let command = command.map_err(|e| Error::WaitProcessError { io_error: e })?;
-    if !command.success() {
-        return Err(new_error("command failed".to_string()));
+    check_status(command, working_path, binary_path, &args)
+}
+
+/// Same as `run_with_env`, but kill the process if it is still
+/// running after `timeout`.
+// Notes:
+// * On Unix, the process is started in its own process group, so that
+//   its own child processes can be killed too. Since Ctrl-C no longer
+//   reaches this group, SIGINT and SIGTERM are forwarded to it while
+//   it runs, see `ForwardSignals`
+// * On Windows, only the process itself is killed. Its child processes
+//   are killed when dmenv exits, provided `win_job::setup()` was called,
+//   see `VenvRunner::run_and_die()`
+pub fn run_with_timeout<T: AsRef<str>>(
+    working_path: &Path,
+    binary_path: &Path,
+    args: &[T],
+    env: &[(String, String)],
+    timeout: Duration,
+) -> Result<(), Error> {
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+    let mut command = std::process::Command::new(binary_path);
+    command
+        .args(&args)
+        .envs(env.iter().cloned())
+        .current_dir(working_path)
+        .stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn().map_err(|e| Error::StartProcessError {
+        message: format!("could not start {}: {}", binary_path.display(), e),
+    })?;
+    #[cfg(unix)]
+    let _forward_signals = ForwardSignals::new(&child);
+    let start = Instant::now();
+    loop {
+        let status = child
+            .try_wait()
+            .map_err(|e| Error::WaitProcessError { io_error: e })?;
+        if let Some(status) = status {
+            return check_status(status, working_path, binary_path, &args);
+        }
+        if start.elapsed() >= timeout {
+            kill(&mut child);
+            return Err(Error::ProcessTimedOut {
+                name: process_name(binary_path, &args),
+                timeout,
+                working_path: working_path.to_path_buf(),
+            });
+        }
+        std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+/// Same as `run_with_env`, but capture stdout and stderr, and only
+/// print them if the process fails.
+// Note: stdout and stderr share the same pipe, so the output is printed
+// in the order the process wrote it
+pub fn run_captured<T: AsRef<str>>(
+    working_path: &Path,
+    binary_path: &Path,
+    args: &[T],
+    env: &[(String, String)],
+) -> Result<(), Error> {
+    use std::io::{Read, Write};
+
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+    let (mut reader, writer) =
+        std::io::pipe().map_err(|e| Error::WaitProcessError { io_error: e })?;
+    let writer_clone = writer
+        .try_clone()
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    let mut command = std::process::Command::new(binary_path);
+    command
+        .args(&args)
+        .envs(env.iter().cloned())
+        .current_dir(working_path)
+        .stdin(std::process::Stdio::inherit())
+        .stdout(writer)
+        .stderr(writer_clone);
+    let mut child = command
+        .spawn()
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    // Drop our copies of the write end, otherwise reading never
+    // reaches end of file
+    drop(command);
+    let mut output = vec![];
+    reader
+        .read_to_end(&mut output)
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    let status = child
+        .wait()
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    if !status.success() {
+        // Note: nothing useful can be done if writing fails, and we
+        // still want to report the exit code
+        let _ = std::io::stdout().write_all(&output);
+        let _ = std::io::stdout().flush();
+    }
+    check_status(status, working_path, binary_path, &args)
+}
+
+/// The phases of a `pip install` command, in order
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum PipPhase {
+    Resolving,
+    Downloading,
+    Installing,
+}
+
+impl std::fmt::Display for PipPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let description = match self {
+            PipPhase::Resolving => "Resolving dependencies",
+            PipPhase::Downloading => "Downloading packages",
+            PipPhase::Installing => "Installing packages",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+// Note: pip prints a `Collecting` line for each requirement, followed by
+// a `Downloading` line if it's not in the cache, so the phases are interleaved
+// until everything is resolved
+fn pip_phase(line: &str) -> Option<PipPhase> {
+    let line = line.trim_start();
+    let starts_with_any = |prefixes: &[&str]| prefixes.iter().any(|x| line.starts_with(x));
+    if starts_with_any(&["Collecting ", "Obtaining ", "Requirement already satisfied"]) {
+        return Some(PipPhase::Resolving);
+    }
+    if starts_with_any(&["Downloading ", "Using cached "]) {
+        return Some(PipPhase::Downloading);
+    }
+    if starts_with_any(&["Installing collected packages"]) {
+        return Some(PipPhase::Installing);
+    }
+    None
+}
+
+/// Run pip, printing the phases it goes through instead of its output:
+/// a phase is only printed when it comes after the previous one, and
+/// the number of installed packages is printed at the end.
+/// The output of pip is printed if it fails.
+// Note: stderr is not captured, so that pip's warnings and errors
+// are still shown as they occur. Prompts would be hidden in the
+// captured output though, so pip is told to never ask for input
+fn run_with_progress<T: AsRef<str>>(
+    working_path: &Path,
+    binary_path: &Path,
+    args: &[T],
+    env: &[(String, String)],
+) -> Result<(), Error> {
+    let mut args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+    args.push(NO_INPUT_OPTION);
+    let mut child = std::process::Command::new(binary_path)
+        .args(&args)
+        .envs(env.iter().cloned())
+        .current_dir(working_path)
+        .stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()
+        .map_err(|e| Error::StartProcessError {
+            message: format!("could not start {}: {}", binary_path.display(), e),
+        })?;
+    let status = wait_summarizing_pip_output(&mut child)?;
+    check_status(status, working_path, binary_path, &args)
+}
+
+/// Passed to pip when its output is summarized, see `run_with_progress()`
+const NO_INPUT_OPTION: &str = "--no-input";
+
+/// Read the piped output of pip, printing its phases instead, then wait
+/// for it, see `run_with_progress()`
+fn wait_summarizing_pip_output(
+    child: &mut std::process::Child,
+) -> Result<std::process::ExitStatus, Error> {
+    use std::io::BufRead;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| new_error("Could not read the output of pip".to_string()))?;
+    let mut lines = vec![];
+    let mut current_phase = None;
+    for line in std::io::BufReader::new(stdout).lines() {
+        let line = line.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
+        let phase = pip_phase(&line);
+        if phase.is_some() && phase > current_phase {
+            current_phase = phase;
+            if let Some(phase) = phase {
+                print_info_2(&format!("{} ...", phase));
+            }
+        }
+        if let Some(installed) = line.strip_prefix("Successfully installed ") {
+            let count = installed.split_whitespace().count();
+            print_info_2(&format!("Installed {} package(s)", count));
+        }
+        lines.push(line);
+    }
+    let status = child
+        .wait()
+        .map_err(|e| Error::WaitProcessError { io_error: e })?;
+    if !status.success() {
+        for line in &lines {
+            println!("{}", line);
+        }
+    }
+    Ok(status)
+}
+
+/// Print the piped error output of the child as it occurs, from another
+/// thread, which returns a copy of it once the child closes it
+fn tee_stderr(child: &mut std::process::Child) -> std::thread::JoinHandle<String> {
+    use std::io::BufRead;
+
+    let stderr = child.stderr.take();
+    std::thread::spawn(move || {
+        let mut res = String::new();
+        let stderr = match stderr {
+            None => return res,
+            Some(stderr) => stderr,
+        };
+        for line in std::io::BufReader::new(stderr).split(b'\n') {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let line = String::from_utf8_lossy(&line);
+            eprintln!("{}", line.trim_end_matches('\r'));
+            res.push_str(&line);
+            res.push('\n');
+        }
+        res
+    })
+}
+
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[cfg(unix)]
+fn kill(child: &mut std::process::Child) {
+    // Note: a negative pid means the whole process group, see `run_with_timeout`
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+    let _ = child.wait();
+}
+
+/// Forward SIGINT and SIGTERM to the process group of the given child
+/// until dropped, at which point the previous handlers are restored
+#[cfg(unix)]
+struct ForwardSignals {
+    previous_handlers: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+// Note: signal handlers cannot capture anything, so the group
+// is stored in a global
+#[cfg(unix)]
+static FORWARD_TO_GROUP: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let group = FORWARD_TO_GROUP.load(std::sync::atomic::Ordering::SeqCst);
+    if group > 0 {
+        // Note: kill() is async-signal-safe
+        unsafe {
+            libc::kill(-group, signal);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl ForwardSignals {
+    fn new(child: &std::process::Child) -> Self {
+        FORWARD_TO_GROUP.store(child.id() as i32, std::sync::atomic::Ordering::SeqCst);
+        let handler = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous_handlers = [libc::SIGINT, libc::SIGTERM]
+            .iter()
+            .map(|&signal| (signal, unsafe { libc::signal(signal, handler) }))
+            .collect();
+        ForwardSignals { previous_handlers }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ForwardSignals {
+    fn drop(&mut self) {
+        for &(signal, previous_handler) in &self.previous_handlers {
+            unsafe {
+                libc::signal(signal, previous_handler);
+            }
+        }
+        FORWARD_TO_GROUP.store(0, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(windows)]
+fn kill(child: &mut std::process::Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn check_status(
+    status: std::process::ExitStatus,
+    working_path: &Path,
+    binary_path: &Path,
+    args: &[&str],
+) -> Result<(), Error> {
+    if !status.success() {
+        return Err(Error::ProcessFailed {
+            name: process_name(binary_path, args),
+            exit_code: status.code(),
+            working_path: working_path.to_path_buf(),
+        });
     }
     Ok(())
 }
 
+/// Name of the process to use in error messages.
+// Note: use `pip` instead of `python` for `python -m pip ...`
+fn process_name(binary_path: &Path, args: &[&str]) -> String {
+    if let ["-m", module, ..] = args {
+        return module.to_string();
+    }
+    let file_stem = binary_path.file_stem().unwrap_or_default();
+    file_stem.to_string_lossy().to_string()
+}
+
 fn get_output<T: AsRef<str>>(
     working_path: &Path,
     binary_path: &Path,
     args: &[T],
-) -> Result<String, Error> {
+) -> Result<(String, String), Error> {
     let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
     let cmd_str = format!("{} {}", binary_path.display(), args.join(" "));
     let command = std::process::Command::new(binary_path)
@@ -151,14 +790,21 @@ fn get_output<T: AsRef<str>>(
         .output();
 
     let command = command.map_err(|e| Error::GetProcessOutputError { io_error: e })?;
+    if is_verbose() {
+        eprint!("{}", String::from_utf8_lossy(&command.stderr));
+    }
     if !command.status.success() {
         return Err(new_error(format!(
-            "`{}` failed\n: {}",
+            "`{}` failed (in {})\n: {}",
             cmd_str,
+            working_path.display(),
             String::from_utf8_lossy(&command.stderr)
         )));
     }
-    Ok(String::from_utf8_lossy(&command.stdout).to_string())
+    Ok((
+        String::from_utf8_lossy(&command.stdout).to_string(),
+        String::from_utf8_lossy(&command.stderr).to_string(),
+    ))
 }
 
 #[cfg(test)]
@@ -245,6 +891,37 @@ mod tests {
         runnable.assert_args(&["foo.py"]);
     }
 
+    #[test]
+    fn test_run_from_path_when_missing_in_venv() {
+        let fs = FileSystem::new();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let options = RunOptions {
+            no_venv_check: true,
+            ..Default::default()
+        };
+        let runnable = venv_runner
+            .get_runnable_with_options(&["cargo", "--version"], &options)
+            .unwrap();
+        let expected_binary = which::which("cargo").unwrap();
+        runnable.assert_binary(&expected_binary);
+        runnable.assert_args(&["--version"]);
+    }
+
+    #[test]
+    fn test_run_prefers_venv_binary() {
+        let fs = FileSystem::new();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let options = RunOptions {
+            no_venv_check: true,
+            ..Default::default()
+        };
+        let runnable = venv_runner
+            .get_runnable_with_options(&["python"], &options)
+            .unwrap();
+        let expected_binary = venv_runner.get_binary_path("python");
+        runnable.assert_binary(&expected_binary);
+    }
+
     #[test]
     fn test_run_py_script_in_venv() {
         let fs = FileSystem::new();
@@ -254,4 +931,204 @@ mod tests {
         runnable.assert_binary(&docutils_script);
         runnable.assert_args(&[]);
     }
+
+    #[test]
+    fn test_run_binary_from_alternative_scripts_dir() {
+        let fs = FileSystem::new();
+        let alternative_dir = fs.venv.join(paths::ALTERNATIVE_SCRIPTS_SUBDIRS[0]);
+        std::fs::create_dir_all(&alternative_dir).unwrap();
+        let binary = alternative_dir.join(VenvRunner::get_binary_name("pytest"));
+        std::fs::write(&binary, "").unwrap();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let runnable = venv_runner.get_runnable(&["pytest", "-k", "foo"]).unwrap();
+        runnable.assert_binary(&binary);
+        runnable.assert_args(&["-k", "foo"]);
+        assert_eq!(venv_runner.resolve_binary("pytest").unwrap(), binary);
+    }
+
+    #[test]
+    fn test_usual_scripts_dir_comes_first() {
+        let fs = FileSystem::new();
+        let alternative_dir = fs.venv.join(paths::ALTERNATIVE_SCRIPTS_SUBDIRS[0]);
+        std::fs::create_dir_all(&alternative_dir).unwrap();
+        std::fs::write(
+            alternative_dir.join(VenvRunner::get_binary_name("pytest")),
+            "",
+        )
+        .unwrap();
+        let expected = fs.add_binary_in_venv("pytest");
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        assert_eq!(venv_runner.resolve_binary("pytest").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_missing_binary_error_lists_searched_dirs() {
+        let fs = FileSystem::new();
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        let error = venv_runner.get_runnable(&["no-such-binary"]).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains(&fs.venv.join(SCRIPTS_SUBDIR).display().to_string()));
+        for subdir in paths::ALTERNATIVE_SCRIPTS_SUBDIRS {
+            assert!(message.contains(&fs.venv.join(subdir).display().to_string()));
+        }
+    }
+
+    #[test]
+    fn test_dry_run_does_not_run_anything() {
+        let fs = FileSystem::new();
+        let mut venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        venv_runner.set_dry_run(true);
+        venv_runner.run(&["no-such-binary", "--foo"]).unwrap();
+        let output = venv_runner.get_output(&["python", "-m", "pip", "freeze"]);
+        assert_eq!(output.unwrap(), "");
+    }
+
+    #[test]
+    fn test_process_name() {
+        let python = Path::new("/path/to/venv/bin/python");
+        assert_eq!(process_name(python, &["-m", "pip", "install"]), "pip");
+        assert_eq!(process_name(python, &["foo.py"]), "python");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_forwards_exit_code() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let error = run_with_env(&fs.project, &sh, &["-c", "exit 2"], &[]).unwrap_err();
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_timeout() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let timeout = Duration::from_secs(5);
+        run_with_timeout(&fs.project, &sh, &["-c", "exit 0"], &[], timeout).unwrap();
+        let error =
+            run_with_timeout(&fs.project, &sh, &["-c", "exit 2"], &[], timeout).unwrap_err();
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_timeout_kills_process_group() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let marker = fs.project.join("marker");
+        // The grand-child process would create the marker file if not killed
+        let script = format!("(sleep 2; touch {}) & wait", marker.display());
+        let start = Instant::now();
+        let error = run_with_timeout(
+            &fs.project,
+            &sh,
+            &["-c", &script],
+            &[],
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(error.exit_code(), 124);
+        std::thread::sleep(Duration::from_secs(3));
+        assert!(!marker.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_captured() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let env = &[("DMENV_TEST_VAR".to_string(), "42".to_string())];
+        let script = r#"echo out; echo err >&2; test "$DMENV_TEST_VAR" = 42"#;
+        run_captured(&fs.project, &sh, &["-c", script], env).unwrap();
+        let error = run_captured(&fs.project, &sh, &["-c", "echo oops; exit 3"], &[]).unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_pip_phase() {
+        assert_eq!(
+            pip_phase("Collecting attrs==19.1.0"),
+            Some(PipPhase::Resolving)
+        );
+        assert_eq!(
+            pip_phase("  Downloading attrs-19.1.0-py2.py3-none-any.whl (35 kB)"),
+            Some(PipPhase::Downloading)
+        );
+        assert_eq!(
+            pip_phase("Installing collected packages: attrs, foo"),
+            Some(PipPhase::Installing)
+        );
+        assert_eq!(pip_phase("Successfully installed attrs-19.1.0"), None);
+        assert!(PipPhase::Resolving < PipPhase::Installing);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_progress() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let script = "echo Collecting foo; echo Installing collected packages: foo";
+        run_with_progress(&fs.project, &sh, &["-c", script], &[]).unwrap();
+        let error =
+            run_with_progress(&fs.project, &sh, &["-c", "echo oops; exit 3"], &[]).unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_get_output_returns_stderr() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let (stdout, stderr) =
+            get_output(&fs.project, &sh, &["-c", "echo out; echo warning >&2"]).unwrap();
+        assert_eq!(stdout, "out\n");
+        assert_eq!(stderr, "warning\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_binaries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fs = FileSystem::new();
+        for name in &["pytest", "black"] {
+            let path = fs.add_binary_in_venv(name);
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        fs.add_binary_in_venv("activate");
+        let alternative_dir = fs.venv.join(paths::ALTERNATIVE_SCRIPTS_SUBDIRS[0]);
+        std::fs::create_dir_all(&alternative_dir).unwrap();
+        for name in &["flake8", "pytest"] {
+            let path = alternative_dir.join(name);
+            std::fs::write(&path, "").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        assert_eq!(
+            venv_runner.list_binaries().unwrap(),
+            &["black", "flake8", "pytest"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_binary() {
+        let fs = FileSystem::new();
+        let pytest = fs.add_binary_in_venv("pytest");
+        let venv_runner = VenvRunner::new(&fs.project, &fs.venv);
+        assert_eq!(venv_runner.resolve_binary("pytest").unwrap(), pytest);
+        venv_runner.resolve_binary("no-such-binary").unwrap_err();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_env() {
+        let fs = FileSystem::new();
+        let sh = which::which("sh").unwrap();
+        let env = &[("DMENV_TEST_VAR".to_string(), "42".to_string())];
+        let script = r#"test "$DMENV_TEST_VAR" = 42"#;
+        run_with_env(&fs.project, &sh, &["-c", script], env).unwrap();
+        assert!(std::env::var("DMENV_TEST_VAR").is_err());
+    }
 }
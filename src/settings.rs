@@ -1,22 +1,119 @@
-use crate::cli::syntax::Command;
+use crate::cli::syntax::{Command, SubCommand};
+use crate::config::Config;
+use crate::dependencies::requirement_name;
+use crate::ui::{print_warning, ColorChoice, Verbosity};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+/// The tool used to create virtualenvs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VenvCreator {
+    /// `python -m venv` (or `python -m virtualenv`, see `venv_from_stdlib`)
+    Venv,
+    /// The `virtualenv` binary found in PATH
+    Virtualenv,
+}
+
+impl std::str::FromStr for VenvCreator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "venv" => Ok(VenvCreator::Venv),
+            "virtualenv" => Ok(VenvCreator::Virtualenv),
+            _ => Err(format!("expected 'venv' or 'virtualenv', got '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 /// Represent variables that change behavior of
 /// dmenv commands
 pub struct Settings {
+    /// The Python interpreter to use, from `--python` or the configuration
+    pub python: Option<String>,
     pub venv_from_stdlib: bool,
+    pub venv_creator: VenvCreator,
     pub venv_outside_project: bool,
     pub production: bool,
     pub system_site_packages: bool,
+    pub pip_extra_args: Vec<String>,
+    pub use_python_version_file: bool,
+    pub use_requirements_in: bool,
+    pub verbosity: Verbosity,
+    pub show_warnings: bool,
+    pub color: ColorChoice,
+    pub index_url: Option<String>,
+    pub extra_index_urls: Vec<String>,
+    pub lock_path: Option<PathBuf>,
+    pub prod_lock_path: Option<PathBuf>,
+    pub lock_name: Option<String>,
+    pub prod_lock_name: Option<String>,
+    pub platform_specific_locks: bool,
+    pub venv_outdir: Option<PathBuf>,
+    pub ignore_active_venv: bool,
+    pub venv_creation_attempts: u32,
+    pub strict_platform: bool,
+    pub install_requirement: Option<PathBuf>,
+    pub pip_version: Option<String>,
+    pub upgrade_pip: bool,
+    pub dry_run: bool,
+    pub progress: bool,
+    pub check_venv_python: bool,
+    pub find_links_dir: Option<PathBuf>,
+    pub post_install: Vec<String>,
+    pub exclude: Vec<String>,
+    pub build_deps: Vec<String>,
+    pub use_cache: bool,
+    pub aliases: BTreeMap<String, String>,
+    pub proxy: Option<String>,
+    pub dev_extra: String,
+    pub prod_extra: String,
 }
 
 impl Default for Settings {
     fn default() -> Settings {
         Settings {
+            python: None,
             venv_from_stdlib: true,
+            venv_creator: VenvCreator::Venv,
             venv_outside_project: false,
             production: false,
             system_site_packages: false,
+            pip_extra_args: vec![],
+            use_python_version_file: false,
+            use_requirements_in: false,
+            verbosity: Verbosity::Normal,
+            show_warnings: false,
+            color: ColorChoice::Auto,
+            index_url: None,
+            extra_index_urls: vec![],
+            lock_path: None,
+            prod_lock_path: None,
+            lock_name: None,
+            prod_lock_name: None,
+            platform_specific_locks: false,
+            venv_outdir: None,
+            ignore_active_venv: false,
+            venv_creation_attempts: 1,
+            strict_platform: false,
+            install_requirement: None,
+            pip_version: None,
+            upgrade_pip: true,
+            dry_run: false,
+            progress: false,
+            check_venv_python: true,
+            find_links_dir: None,
+            post_install: vec![],
+            exclude: vec![],
+            build_deps: vec![],
+            use_cache: true,
+            aliases: BTreeMap::new(),
+            proxy: None,
+            dev_extra: "dev".to_string(),
+            prod_extra: "prod".to_string(),
         }
     }
 }
@@ -28,17 +125,527 @@ impl Settings {
     //
     // Note:  Called in `main()` and in test heplers.
     pub fn from_shell(cmd: &Command) -> Settings {
-        let mut res = Settings {
-            production: cmd.production,
-            system_site_packages: cmd.system_site_packages,
-            ..Default::default()
-        };
+        Self::new(cmd, &Config::default())
+    }
+
+    /// Same as `from_shell`, but start with the values from the
+    /// configuration file. Command line options and environment
+    /// variables take precedence.
+    pub fn new(cmd: &Command, config: &Config) -> Settings {
+        let mut res = Settings::from_config(config);
+        if cmd.python_binary.is_some() {
+            res.python = cmd.python_binary.clone();
+        }
+        if cmd.production {
+            res.production = true;
+        }
+        if cmd.system_site_packages {
+            res.system_site_packages = true;
+        }
+        res.dry_run = cmd.dry_run;
+        if cmd.progress || std::env::var("DMENV_PROGRESS").is_ok() {
+            res.progress = true;
+        }
+        if cmd.no_cache || std::env::var("DMENV_NO_CACHE").is_ok() {
+            res.use_cache = false;
+        }
+        if cmd.no_upgrade_pip || std::env::var("DMENV_NO_UPGRADE_PIP").is_ok() {
+            res.upgrade_pip = false;
+        }
+        // `dmenv lock --dev` and `dmenv lock --prod` take precedence
+        // over the global `--production` flag
+        if let SubCommand::Lock {
+            dev, prod, exclude, ..
+        } = &cmd.sub_cmd
+        {
+            if *dev {
+                res.production = false;
+            }
+            if *prod {
+                res.production = true;
+            }
+            // Note: excluded packages from the command line are added to
+            // the ones from the configuration
+            res.exclude.extend(exclude.iter().cloned());
+        }
+        if let SubCommand::Install {
+            strict,
+            requirement,
+            ..
+        } = &cmd.sub_cmd
+        {
+            if *strict {
+                res.strict_platform = true;
+            }
+            res.install_requirement = requirement.as_ref().map(PathBuf::from);
+        }
+        match &cmd.sub_cmd {
+            SubCommand::Tidy {
+                allow_active_venv, ..
+            }
+            | SubCommand::LockUpgradeAll { allow_active_venv } => {
+                res.ignore_active_venv = *allow_active_venv;
+            }
+            _ => (),
+        }
+        res.color = cmd.color;
+        res.show_warnings = cmd.show_warnings;
+        if cmd.quiet {
+            res.verbosity = Verbosity::Quiet;
+        }
+        if cmd.verbose {
+            res.verbosity = Verbosity::Verbose;
+        }
         if std::env::var("DMENV_NO_VENV_STDLIB").is_ok() {
             res.venv_from_stdlib = false;
         }
+        if let Ok(creator) = std::env::var("DMENV_VENV_CREATOR") {
+            match creator.parse() {
+                Ok(creator) => res.venv_creator = creator,
+                Err(e) => print_warning(&format!("DMENV_VENV_CREATOR: {}", e)),
+            }
+        }
         if std::env::var("DMENV_VENV_OUTSIDE_PROJECT").is_ok() {
             res.venv_outside_project = true;
         }
+        if std::env::var("DMENV_USE_PYTHON_VERSION_FILE").is_ok() {
+            res.use_python_version_file = true;
+        }
+        if std::env::var("DMENV_USE_REQUIREMENTS_IN").is_ok() {
+            res.use_requirements_in = true;
+        }
+        if let Ok(url) = std::env::var("DMENV_INDEX_URL") {
+            res.index_url = Some(url);
+        }
+        // `dmenv lock --index-url` and `dmenv tidy --index-url` take
+        // precedence over DMENV_INDEX_URL, and `--with-build-deps`
+        // adds to the build dependencies from the configuration
+        match &cmd.sub_cmd {
+            SubCommand::Lock {
+                index_url,
+                extra_index_url,
+                with_build_deps,
+                ..
+            }
+            | SubCommand::Tidy {
+                index_url,
+                extra_index_url,
+                with_build_deps,
+                ..
+            } => {
+                if index_url.is_some() {
+                    res.index_url = index_url.clone();
+                }
+                res.extra_index_urls = extra_index_url.clone();
+                res.build_deps.extend(with_build_deps.iter().cloned());
+            }
+            _ => (),
+        }
+        if let Ok(extra) = std::env::var("DMENV_DEV_EXTRA") {
+            res.dev_extra = extra;
+        }
+        if let Ok(extra) = std::env::var("DMENV_PROD_EXTRA") {
+            res.prod_extra = extra;
+        }
+        if let Ok(proxy) = std::env::var("DMENV_PROXY") {
+            res.proxy = Some(proxy);
+        }
+        if let Ok(path) = std::env::var("DMENV_LOCK_PATH") {
+            res.lock_path = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = std::env::var("DMENV_PROD_LOCK_PATH") {
+            res.prod_lock_path = Some(PathBuf::from(path));
+        }
+        if let Ok(name) = std::env::var("DMENV_LOCK_NAME") {
+            res.lock_name = Some(name);
+        }
+        if let Ok(name) = std::env::var("DMENV_PROD_LOCK_NAME") {
+            res.prod_lock_name = Some(name);
+        }
+        if std::env::var("DMENV_PLATFORM_SPECIFIC_LOCKS").is_ok() {
+            res.platform_specific_locks = true;
+        }
+        if let Ok(path) = std::env::var("DMENV_FIND_LINKS_DIR") {
+            res.find_links_dir = Some(PathBuf::from(path));
+        }
+        if let Ok(path) = std::env::var("DMENV_VENV_OUTDIR") {
+            res.venv_outdir = Some(PathBuf::from(path));
+        }
+        if let Ok(version) = std::env::var("DMENV_PIP_VERSION") {
+            res.pip_version = Some(version);
+        }
+        if std::env::var("DMENV_NO_VENV_PYTHON_CHECK").is_ok() {
+            res.check_venv_python = false;
+        }
+        if std::env::var("DMENV_STRICT_PLATFORM").is_ok() {
+            res.strict_platform = true;
+        }
+        if let Ok(attempts) = std::env::var("DMENV_VENV_CREATION_ATTEMPTS") {
+            match attempts.parse() {
+                Ok(attempts) if attempts > 0 => res.venv_creation_attempts = attempts,
+                _ => print_warning(&format!(
+                    "DMENV_VENV_CREATION_ATTEMPTS should be a positive number, got '{}'",
+                    attempts
+                )),
+            }
+        }
+        if let Ok(hook) = std::env::var("DMENV_POST_INSTALL") {
+            res.post_install = vec![hook];
+        }
+        if let Ok(args) = std::env::var("DMENV_PIP_EXTRA_ARGS") {
+            res.pip_extra_args = args.split_whitespace().map(String::from).collect();
+        }
         res
     }
 }
+
+impl Settings {
+    fn from_config(config: &Config) -> Settings {
+        let default = Settings::default();
+        Settings {
+            python: config.python.clone(),
+            venv_from_stdlib: config.venv_from_stdlib.unwrap_or(default.venv_from_stdlib),
+            venv_creator: config.venv_creator.unwrap_or(default.venv_creator),
+            venv_outside_project: config
+                .venv_outside_project
+                .unwrap_or(default.venv_outside_project),
+            production: config.production.unwrap_or(default.production),
+            system_site_packages: config
+                .system_site_packages
+                .unwrap_or(default.system_site_packages),
+            pip_extra_args: config.pip_extra_args.clone().unwrap_or_default(),
+            use_python_version_file: config
+                .use_python_version_file
+                .unwrap_or(default.use_python_version_file),
+            use_requirements_in: config
+                .use_requirements_in
+                .unwrap_or(default.use_requirements_in),
+            index_url: config.index_url.clone(),
+            lock_path: config.lock_path.clone(),
+            prod_lock_path: config.prod_lock_path.clone(),
+            lock_name: config.lock_name.clone(),
+            prod_lock_name: config.prod_lock_name.clone(),
+            platform_specific_locks: config
+                .platform_specific_locks
+                .unwrap_or(default.platform_specific_locks),
+            venv_outdir: config.venv_outdir.clone(),
+            venv_creation_attempts: config
+                .venv_creation_attempts
+                .filter(|x| *x > 0)
+                .unwrap_or(default.venv_creation_attempts),
+            strict_platform: config.strict_platform.unwrap_or(default.strict_platform),
+            pip_version: config.pip_version.clone(),
+            upgrade_pip: config.upgrade_pip.unwrap_or(default.upgrade_pip),
+            find_links_dir: config.find_links_dir.clone(),
+            exclude: config.exclude.clone().unwrap_or_default(),
+            build_deps: config.build_deps.clone().unwrap_or_default(),
+            aliases: config.aliases.clone().unwrap_or_default(),
+            proxy: config.proxy.clone(),
+            dev_extra: config.dev_extra.clone().unwrap_or(default.dev_extra),
+            prod_extra: config.prod_extra.clone().unwrap_or(default.prod_extra),
+            post_install: config
+                .post_install
+                .as_ref()
+                .map(|x| x.to_vec())
+                .unwrap_or_default(),
+            check_venv_python: config
+                .check_venv_python
+                .unwrap_or(default.check_venv_python),
+            ..default
+        }
+    }
+}
+
+impl Settings {
+    /// The extra dependencies to install along with the project:
+    /// `prod_extra` in production, `dev_extra` otherwise
+    pub fn extra(&self) -> &str {
+        if self.production {
+            &self.prod_extra
+        } else {
+            &self.dev_extra
+        }
+    }
+}
+
+impl Settings {
+    /// Names of the packages in `build_deps`, without the version specifiers
+    pub fn build_dep_names(&self) -> Vec<String> {
+        self.build_deps
+            .iter()
+            .map(|x| requirement_name(x.trim()).to_string())
+            .collect()
+    }
+}
+
+impl Settings {
+    /// Expand `:<alias>` into the command defined in the `aliases` table of
+    /// the configuration, followed by the other arguments. For instance, with
+    /// `test = "pytest --maxfail=1"`, `:test -k foo` becomes `pytest --maxfail=1 -k foo`.
+    /// Commands that do not start with a known alias are returned unchanged
+    // Note: aliases are split on whitespace, like DMENV_PIP_EXTRA_ARGS
+    pub fn expand_alias<T: AsRef<str>>(&self, cmd: &[T]) -> Vec<String> {
+        let args: Vec<String> = cmd.iter().map(|x| x.as_ref().to_string()).collect();
+        let expansion = args
+            .first()
+            .and_then(|first| first.strip_prefix(':'))
+            .and_then(|name| self.aliases.get(name));
+        match expansion {
+            None => args,
+            Some(expansion) => expansion
+                .split_whitespace()
+                .map(String::from)
+                .chain(args.into_iter().skip(1))
+                .collect(),
+        }
+    }
+}
+
+impl Settings {
+    /// Arguments to pass to every pip command that downloads packages.
+    //
+    // Note: when both `index_url` and `pip_extra_args` contain `--index-url`,
+    // the one in `pip_extra_args` wins, since they are added after these ones
+    // and pip only keeps the last value.
+    pub fn pip_index_args(&self) -> Vec<String> {
+        let mut res = vec![];
+        if let Some(url) = &self.index_url {
+            res.push("--index-url".to_string());
+            res.push(url.to_string());
+        }
+        for url in &self.extra_index_urls {
+            res.push("--extra-index-url".to_string());
+            res.push(url.to_string());
+        }
+        if let Some(proxy) = &self.proxy {
+            res.push("--proxy".to_string());
+            res.push(proxy.to_string());
+        }
+        res
+    }
+
+    /// Environment variables to set when creating the virtualenv,
+    /// so that the proxy (if any) is used when pip is bootstrapped
+    pub fn proxy_env(&self) -> Vec<(String, String)> {
+        let proxy = match &self.proxy {
+            None => return vec![],
+            Some(proxy) => proxy,
+        };
+        ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]
+            .iter()
+            .map(|name| (name.to_string(), proxy.to_string()))
+            .collect()
+    }
+}
+
+impl Settings {
+    /// The requirement to use when installing pip in the virtualenv:
+    /// `pip==<version>` if `pip_version` is set, or just `pip` (meaning the latest version)
+    pub fn pip_requirement(&self) -> String {
+        match &self.pip_version {
+            None => "pip".to_string(),
+            Some(version) => format!("pip=={}", version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn test_extra() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.extra(), "dev");
+        settings.production = true;
+        assert_eq!(settings.extra(), "prod");
+
+        let config = crate::config::parse_config(
+            "[tool.dmenv]\ndev_extra = \"test\"\nprod_extra = \"all\"\n",
+        )
+        .unwrap();
+        let mut settings = Settings::from_config(&config);
+        assert_eq!(settings.extra(), "test");
+        settings.production = true;
+        assert_eq!(settings.extra(), "all");
+    }
+
+    #[test]
+    fn test_upgrade_pip() {
+        assert!(Settings::default().upgrade_pip);
+        let config = crate::config::parse_config("[tool.dmenv]\nupgrade_pip = false\n").unwrap();
+        assert!(!Settings::from_config(&config).upgrade_pip);
+
+        let cmd = Command::from_iter_safe(&["dmenv", "--no-upgrade-pip", "lock"]).unwrap();
+        assert!(!Settings::from_shell(&cmd).upgrade_pip);
+    }
+
+    #[test]
+    fn test_proxy() {
+        let mut settings = Settings::default();
+        assert!(settings.proxy_env().is_empty());
+
+        settings.proxy = Some("http://proxy.corp.tld:3128".to_string());
+        assert_eq!(
+            settings.pip_index_args(),
+            &["--proxy", "http://proxy.corp.tld:3128"]
+        );
+        assert!(settings.proxy_env().contains(&(
+            "HTTPS_PROXY".to_string(),
+            "http://proxy.corp.tld:3128".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let mut settings = Settings::default();
+        settings
+            .aliases
+            .insert("test".to_string(), "pytest --maxfail=1".to_string());
+        assert_eq!(
+            settings.expand_alias(&[":test", "-k", "foo"]),
+            &["pytest", "--maxfail=1", "-k", "foo"]
+        );
+        assert_eq!(settings.expand_alias(&[":lint"]), &[":lint"]);
+        assert_eq!(settings.expand_alias(&["test"]), &["test"]);
+        assert!(settings.expand_alias::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_pip_index_args() {
+        let mut settings = Settings::default();
+        assert!(settings.pip_index_args().is_empty());
+
+        settings.index_url = Some("https://pypi.corp.tld/simple".to_string());
+        assert_eq!(
+            settings.pip_index_args(),
+            &["--index-url", "https://pypi.corp.tld/simple"]
+        );
+
+        settings.extra_index_urls = vec![
+            "https://one.tld/simple".to_string(),
+            "https://two.tld/simple".to_string(),
+        ];
+        assert_eq!(
+            settings.pip_index_args(),
+            &[
+                "--index-url",
+                "https://pypi.corp.tld/simple",
+                "--extra-index-url",
+                "https://one.tld/simple",
+                "--extra-index-url",
+                "https://two.tld/simple",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_urls_from_command_line() {
+        use structopt::StructOpt;
+
+        let cmd = Command::from_iter(&[
+            "dmenv",
+            "tidy",
+            "--index-url",
+            "https://pypi.corp.tld/simple",
+            "--extra-index-url",
+            "https://one.tld/simple",
+            "--extra-index-url",
+            "https://two.tld/simple",
+        ]);
+        let settings = Settings::from_shell(&cmd);
+        assert_eq!(settings.index_url.unwrap(), "https://pypi.corp.tld/simple");
+        assert_eq!(
+            settings.extra_index_urls,
+            &["https://one.tld/simple", "https://two.tld/simple"]
+        );
+    }
+
+    #[test]
+    fn test_parse_venv_creator() {
+        assert_eq!("venv".parse(), Ok(VenvCreator::Venv));
+        assert_eq!("virtualenv".parse(), Ok(VenvCreator::Virtualenv));
+        assert!("conda".parse::<VenvCreator>().is_err());
+    }
+
+    #[test]
+    fn test_pip_requirement() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.pip_requirement(), "pip");
+
+        settings.pip_version = Some("19.2.3".to_string());
+        assert_eq!(settings.pip_requirement(), "pip==19.2.3");
+    }
+
+    #[test]
+    fn test_lock_extras_override_production() {
+        use structopt::StructOpt;
+
+        let cmd = Command::from_iter(&["dmenv", "lock", "--prod"]);
+        assert!(Settings::from_shell(&cmd).production);
+
+        let cmd = Command::from_iter(&["dmenv", "--production", "lock", "--dev"]);
+        assert!(!Settings::from_shell(&cmd).production);
+    }
+
+    #[test]
+    fn test_command_line_overrides_config() {
+        use structopt::StructOpt;
+
+        let config = Config {
+            production: Some(true),
+            venv_creation_attempts: Some(3),
+            ..Default::default()
+        };
+        let cmd = Command::from_iter(&["dmenv", "install"]);
+        let settings = Settings::new(&cmd, &config);
+        assert!(settings.production);
+        assert_eq!(settings.venv_creation_attempts, 3);
+
+        let cmd = Command::from_iter(&["dmenv", "lock", "--dev"]);
+        assert!(!Settings::new(&cmd, &config).production);
+    }
+
+    #[test]
+    fn test_python() {
+        use structopt::StructOpt;
+
+        let config = Config {
+            python: Some("python3.7".to_string()),
+            ..Default::default()
+        };
+        let cmd = Command::from_iter(&["dmenv", "install"]);
+        assert_eq!(Settings::new(&cmd, &config).python.unwrap(), "python3.7");
+
+        let cmd = Command::from_iter(&["dmenv", "--python", "python3.8", "install"]);
+        assert_eq!(Settings::new(&cmd, &config).python.unwrap(), "python3.8");
+    }
+
+    #[test]
+    fn test_exclude() {
+        use structopt::StructOpt;
+
+        let config = Config {
+            exclude: Some(vec!["foo".to_string()]),
+            ..Default::default()
+        };
+        let cmd = Command::from_iter(&["dmenv", "lock", "--exclude", "bar", "--exclude", "baz"]);
+        let settings = Settings::new(&cmd, &config);
+        assert_eq!(settings.exclude, &["foo", "bar", "baz"]);
+    }
+
+    #[test]
+    fn test_build_deps() {
+        use structopt::StructOpt;
+
+        let config = Config {
+            build_deps: Some(vec!["wheel".to_string()]),
+            ..Default::default()
+        };
+        let cmd = Command::from_iter(&["dmenv", "tidy", "--with-build-deps", "cython<3"]);
+        let settings = Settings::new(&cmd, &config);
+        assert_eq!(settings.build_deps, &["wheel", "cython<3"]);
+        assert_eq!(settings.build_dep_names(), &["wheel", "cython"]);
+    }
+}
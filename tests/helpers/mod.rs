@@ -3,8 +3,6 @@ use std::path::PathBuf;
 use ignore::Walk;
 use structopt::StructOpt;
 
-use dmenv;
-
 #[derive(Debug)]
 pub struct TestApp {
     tmp_dir: tempdir::TempDir,
@@ -68,17 +66,48 @@ impl TestApp {
     }
 
     pub fn run(&self, args: Vec<String>) -> Result<(), dmenv::Error> {
+        dmenv::run_cmd(self.command(args))
+    }
+
+    /// Parse the given dmenv command line, using the test project path
+    pub fn command(&self, args: Vec<String>) -> dmenv::Command {
         let mut cmd = vec![];
         cmd.extend(vec!["dmenv".to_string()]);
         let tmp_path: String = self.path().to_string_lossy().into();
         cmd.extend(vec!["--project".to_string(), tmp_path]);
         cmd.extend(args);
-        let cmd = dmenv::Command::from_iter_safe(cmd).unwrap();
-        dmenv::run_cmd(cmd)
+        dmenv::Command::from_iter_safe(cmd).unwrap()
+    }
+
+    /// Run the dmenv binary in a child process, writing `stdin` to its
+    /// standard input, and return its standard output.
+    // Note: unlike `run()`, this goes through main(), so that the
+    // process IO wiring is tested too
+    pub fn run_binary_with_stdin(&self, args: &[&str], stdin: &str) -> String {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_dmenv"))
+            .arg("--project")
+            .arg(self.path())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
     }
 
     pub fn assert_run_ok(&self, args: &[&str]) {
-        let args = to_string_args(&args);
+        let args = to_string_args(args);
         self.run(args).unwrap();
     }
 
@@ -101,7 +130,7 @@ impl TestApp {
     }
 
     pub fn assert_run_error(&self, args: &[&str]) -> String {
-        let args = to_string_args(&args);
+        let args = to_string_args(args);
         let res = self.run(args);
         res.unwrap_err().to_string()
     }
@@ -7,6 +7,317 @@ fn show_venv_path() {
     test_app.assert_run_ok(&["show:venv_path"]);
 }
 
+#[test]
+fn show_bin() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["bin", "python"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["bin", "python"]);
+    test_app.assert_run_error(&["bin", "no-such-binary"]);
+}
+
+#[test]
+fn venv_python_version_mismatch() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    let venv_path = test_app.path().join(".venv/dev");
+    let venv_path = std::fs::read_dir(venv_path)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    std::fs::write(venv_path.join("pyvenv.cfg"), "version = 2.7.18\n").unwrap();
+    let error = test_app.assert_run_error(&["bin", "python"]);
+    assert!(error.contains("was created with Python 2.7.18"));
+
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(&pyproject, "[tool.dmenv]\ncheck_venv_python = false\n").unwrap();
+    test_app.assert_run_ok(&["bin", "python"]);
+}
+
+#[test]
+fn doctor() {
+    let test_app = TestApp::new();
+    let error = test_app.assert_run_error(&["doctor"]);
+    assert!(error.contains("1 check(s) failed"));
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["doctor"]);
+}
+
+#[test]
+fn selfcheck() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["selfcheck"]);
+}
+
+#[test]
+fn env_list() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["env:list"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["env:list"]);
+}
+
+#[test]
+fn show_deps_tree() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["show:deps", "--tree"]);
+    test_app.assert_run_ok(&["show:deps", "--tree", "--format", "json"]);
+}
+
+#[test]
+fn show_python() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["show:python"]);
+    test_app.assert_run_ok(&["show:python", "--format", "json"]);
+}
+
+#[test]
+fn config_show() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["config:show"]);
+    test_app.assert_run_ok(&["--production", "config:show", "--format", "json"]);
+}
+
+#[test]
+fn run_forwards_stdin() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    let script = "import sys; sys.stdout.write(sys.stdin.read().upper())";
+
+    let output =
+        test_app.run_binary_with_stdin(&["--quiet", "run", "python", "-c", script], "hello");
+    assert_eq!(output, "HELLO");
+
+    #[rustfmt::skip]
+    let args = &["--quiet", "run:all", "--", "python", "-c", script];
+    let output = test_app.run_binary_with_stdin(args, "hello");
+    assert_eq!(output, "HELLO");
+}
+
+#[test]
+fn run_all() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    #[rustfmt::skip]
+    test_app.assert_run_ok(&[
+        "run:all",
+        "--", "python", "--version",
+        "--", "python", "-c", "print('ok')",
+    ]);
+
+    #[rustfmt::skip]
+    let failing = &[
+        "--", "python", "-c", "raise SystemExit(3)",
+        "--", "python", "--version",
+    ];
+    let mut args = vec!["run:all"];
+    args.extend(failing);
+    let error = test_app.assert_run_error(&args);
+    assert!(error.contains("failed with exit code 3"));
+
+    let mut args = vec!["run:all", "--keep-going"];
+    args.extend(failing);
+    let error = test_app.assert_run_error(&args);
+    assert!(error.contains("1 command(s) out of 2 failed"));
+}
+
+#[test]
+fn run_with_timeout() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["run", "--timeout", "60", "python", "--version"]);
+    #[rustfmt::skip]
+    let error = test_app.assert_run_error(&[
+        "run", "--timeout", "1",
+        "--", "python", "-c", "import time; time.sleep(30)",
+    ]);
+    assert!(error.contains("killed after 1 second(s)"));
+}
+
+#[test]
+fn run_echo_only() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["run", "--echo-only", "python"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["run", "--echo-only", "--", "python", "-c", "print('ok')"]);
+    test_app.assert_run_error(&["run", "--echo-only", "no-such-binary"]);
+}
+
+#[test]
+fn run_list() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["run", "--list"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["run", "--list"]);
+}
+
+#[test]
+fn run_capture() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    // Note: --capture never uses execv(), so --no-exec is not needed
+    test_app.assert_run_ok(&["run", "--capture", "--", "python", "-c", "print('ok')"]);
+    test_app.assert_run_error(&[
+        "run",
+        "--capture",
+        "--",
+        "python",
+        "-c",
+        "raise SystemExit(3)",
+    ]);
+}
+
+#[test]
+fn run_alias() {
+    let test_app = TestApp::new();
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(&pyproject, "[tool.dmenv.aliases]\ncheck = \"python -c\"\n").unwrap();
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["run", "--no-exec", ":check", "print('ok')"]);
+    test_app.assert_run_error(&["run", "--no-exec", ":check", "raise SystemExit(3)"]);
+    test_app.assert_run_error(&["run", "--no-exec", ":unknown"]);
+}
+
+#[test]
+fn config_from_pyproject() {
+    let test_app = TestApp::new();
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(&pyproject, "[tool.dmenv]\nvenv_outdir = \".envs\"\n").unwrap();
+    test_app.assert_run_ok(&["create"]);
+    assert!(test_app.path().join(".envs").exists());
+
+    std::fs::write(&pyproject, "[tool.dmenv]\nvenv_outdir = 42\n").unwrap();
+    let error = test_app.assert_run_error(&["config:show"]);
+    assert!(error.contains("Malformed config"));
+}
+
+#[test]
+fn explicit_config_file() {
+    let test_app = TestApp::new();
+    let config_path = test_app.path().join("ci.toml");
+    let config_str = config_path.to_string_lossy();
+    let error = test_app.assert_run_error(&["--config", &config_str, "config:show"]);
+    assert!(error.contains("ci.toml does not exist"));
+
+    std::fs::write(&config_path, "[tool.dmenv]\nvenv_outdir = \".envs\"\n").unwrap();
+    test_app.assert_run_ok(&["--config", &config_str, "create"]);
+    assert!(test_app.path().join(".envs").exists());
+}
+
+#[test]
+fn dry_run() {
+    let test_app = TestApp::new();
+    let lock_before = test_app.read_dev_lock();
+    test_app.assert_run_ok(&["--dry-run", "install"]);
+    test_app.assert_run_ok(&["--dry-run", "lock"]);
+    test_app.assert_run_ok(&["--dry-run", "run", "pytest"]);
+    assert!(!test_app.path().join(".venv").exists());
+    assert_eq!(test_app.read_dev_lock(), lock_before);
+}
+
+#[test]
+fn show_venv_activate() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["show:venv:activate"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["show:venv:activate"]);
+    test_app.assert_run_ok(&["show:venv:activate", "--shell", "fish"]);
+}
+
+#[test]
+fn show_venv_size() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["show:venv:size"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["show:venv:size", "--by-dir"]);
+}
+
+#[test]
+fn lock_check_duplicates() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock:check-duplicates"]);
+    let lock = test_app.read_dev_lock();
+    test_app.override_lock(&format!("{}attrs==0.1\n", lock));
+    test_app.assert_run_error(&["lock:check-duplicates"]);
+    test_app.remove_dev_lock();
+    test_app.assert_run_error(&["lock:check-duplicates"]);
+}
+
+#[test]
+fn lock_stats() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock:stats"]);
+    test_app.assert_run_ok(&["lock:stats", "--format", "json"]);
+    test_app.remove_dev_lock();
+    test_app.assert_run_error(&["lock:stats"]);
+}
+
+#[test]
+fn relocate_venv() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["venv:relocate"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["venv:relocate"]);
+    test_app.assert_run_ok(&["run:all", "--", "python", "-c", "pass"]);
+}
+
+#[test]
+fn clean_all() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["clean", "--all"]);
+    test_app.assert_run_ok(&["create"]);
+    test_app.assert_run_ok(&["--production", "create"]);
+    test_app.assert_run_ok(&["clean", "--all"]);
+    assert!(!test_app.path().join(".venv").exists());
+}
+
+#[test]
+fn post_install_hooks() {
+    let test_app = TestApp::new();
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(
+        &pyproject,
+        "[tool.dmenv]\npost_install = [\"python -c pass\", \"python -c exit(1)\"]\n",
+    )
+    .unwrap();
+    test_app.assert_run_error(&["install"]);
+
+    std::fs::write(
+        &pyproject,
+        "[tool.dmenv]\npost_install = \"python -c pass\"\n",
+    )
+    .unwrap();
+    test_app.assert_run_ok(&["install"]);
+}
+
+#[test]
+fn install_from_custom_requirements_file() {
+    let test_app = TestApp::new();
+    let requirements = test_app.path().join("debug.txt");
+    let requirements_str = requirements.to_string_lossy();
+    let error = test_app.assert_run_error(&["install", "--requirement", &requirements_str]);
+    assert!(error.contains("debug.txt"));
+
+    std::fs::write(&requirements, "six==1.12.0\n").unwrap();
+    test_app.remove_dev_lock();
+    test_app.assert_run_ok(&["--dry-run", "install", "--requirement", &requirements_str]);
+}
+
+#[test]
+fn install_with_missing_find_links_dir() {
+    let test_app = TestApp::new();
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(&pyproject, "[tool.dmenv]\nfind_links_dir = \"wheels\"\n").unwrap();
+    let error = test_app.assert_run_error(&["install"]);
+    assert!(error.contains("does not exist"));
+
+    std::fs::create_dir(test_app.path().join("wheels")).unwrap();
+    test_app.assert_run_ok(&["--dry-run", "install"]);
+}
+
 #[test]
 fn init_works() {
     let test_app = TestApp::new();
@@ -31,6 +342,164 @@ fn bump_in_lock_simple() {
     assert!(actual.contains("attrs==19.2.0"));
 }
 
+#[test]
+fn lock_prune() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock:prune", "Attrs"]);
+    let actual = test_app.read_dev_lock();
+    assert!(!actual.contains("attrs=="));
+}
+
+#[test]
+fn show_lock() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["show:lock"]);
+    test_app.assert_run_ok(&["show:lock", "--format", "json"]);
+    test_app.remove_dev_lock();
+    test_app.assert_run_ok(&["show:lock"]);
+    assert!(!test_app.path().join(".venv").exists());
+}
+
+#[test]
+fn freeze_metadata() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["venv:freeze-metadata"]);
+    test_app.assert_run_ok(&["venv:freeze-metadata", "--format", "json"]);
+    test_app.remove_dev_lock();
+    test_app.assert_run_error(&["venv:freeze-metadata"]);
+}
+
+#[test]
+fn lock_pin_python() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock:pin-python"]);
+    let lock_path = test_app.path().join(dmenv::DEV_LOCK_FILENAME);
+    let contents = std::fs::read_to_string(&lock_path).unwrap();
+    let header = contents.lines().next().unwrap();
+    assert!(header.starts_with("# Generated with dmenv"));
+    assert!(header.contains("), on "));
+    test_app.remove_dev_lock();
+    test_app.assert_run_error(&["lock:pin-python"]);
+}
+
+#[test]
+fn parse_lock_as_a_library() {
+    let test_app = TestApp::new();
+    let lock_path = test_app.path().join(dmenv::DEV_LOCK_FILENAME);
+    let deps = dmenv::parse_lock(&lock_path).unwrap();
+    let attrs = deps.iter().find(|x| x.name == "attrs").unwrap();
+    assert!(!attrs.version.is_empty());
+    assert!(!deps.iter().any(|x| x.name == "pkg-resources"));
+
+    dmenv::parse_lock(&test_app.path().join("no-such.lock")).unwrap_err();
+}
+
+#[test]
+fn frozen_dependencies_as_a_library() {
+    let test_app = TestApp::new();
+    let lock_before = test_app.read_dev_lock();
+    let cmd = test_app.command(vec!["lock".to_string()]);
+    let options = dmenv::UpdateLockOptions::default();
+    let deps = dmenv::frozen_dependencies(&cmd, &options).unwrap();
+    assert!(deps.iter().any(|x| x.name == "attrs"));
+    assert_eq!(test_app.read_dev_lock(), lock_before);
+}
+
+#[test]
+fn lock_merge() {
+    let test_app = TestApp::new();
+    let other_lock = test_app.path().join("other.lock");
+    std::fs::write(&other_lock, "attrs==0.1\nfoobar==4.2\n").unwrap();
+    let other_lock = other_lock.to_string_lossy().to_string();
+
+    let error = test_app.assert_run_error(&["lock:merge", &other_lock]);
+    assert!(error.contains("1 conflict(s) found"));
+    assert!(!test_app.read_dev_lock().contains("foobar"));
+
+    test_app.assert_run_ok(&["lock:merge", "--prefer-newer", &other_lock]);
+    let lock_contents = test_app.read_dev_lock();
+    assert!(lock_contents.contains("foobar==4.2"));
+    assert!(!lock_contents.contains("attrs==0.1"));
+}
+
+#[test]
+fn lock_add() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock:add", "foobar==4.2"]);
+    test_app.assert_run_ok(&["lock:add", "attrs==0.1"]);
+    let actual = test_app.read_dev_lock();
+    assert!(actual.contains("foobar==4.2\n"));
+    assert!(actual.contains("attrs==0.1\n"));
+    test_app.assert_run_error(&["lock:add", "foobar"]);
+}
+
+#[test]
+fn custom_lock_name() {
+    let test_app = TestApp::new();
+    let dev_lock = test_app.path().join(dmenv::DEV_LOCK_FILENAME);
+    let ci_lock = test_app.path().join("requirements.ci.lock");
+    std::fs::copy(&dev_lock, &ci_lock).unwrap();
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(
+        &pyproject,
+        "[tool.dmenv]\nlock_name = \"requirements.ci.lock\"\n",
+    )
+    .unwrap();
+    test_app.assert_run_ok(&["show:lock_path"]);
+    test_app.assert_run_ok(&["lock:add", "foobar==4.2"]);
+    assert!(std::fs::read_to_string(&ci_lock)
+        .unwrap()
+        .contains("foobar==4.2"));
+    assert!(!test_app.read_dev_lock().contains("foobar"));
+}
+
+#[test]
+fn lock_diff() {
+    let test_app = TestApp::new();
+    let other_path = test_app.path().join("other.lock");
+    let other_contents = test_app.read_dev_lock().replace("attrs==", "attrs==0");
+    std::fs::write(&other_path, other_contents).unwrap();
+    let other_path: String = other_path.to_string_lossy().into();
+
+    let dev_lock_path = test_app.path().join(dmenv::DEV_LOCK_FILENAME);
+    let dev_lock_path: String = dev_lock_path.to_string_lossy().into();
+    test_app.assert_run_ok(&["lock:diff", &dev_lock_path]);
+
+    let error = test_app.assert_run_error(&["lock:diff", &other_path]);
+    assert!(error.contains("1 difference(s) found"));
+}
+
+#[test]
+fn lock_with_missing_extra() {
+    let test_app = TestApp::new();
+    test_app.override_setup_cfg(
+        "\
+[metadata]
+name = demo
+
+[options.extras_require]
+tests =
+  pytest
+",
+    );
+    let error = test_app.assert_run_error(&["lock"]);
+    assert!(error.contains("'dev' extra not found"));
+    assert!(error.contains("Available extras: tests"));
+}
+
+#[test]
+fn errors_contain_the_project_path() {
+    let test_app = TestApp::new();
+    let project_path = test_app.path().to_string_lossy().to_string();
+
+    let error = test_app.assert_run_error(&["show:bin_path"]);
+    assert!(error.contains(&project_path));
+
+    test_app.remove_setup_py();
+    let error = test_app.assert_run_error(&["lock"]);
+    assert!(error.contains(&format!("setup.py not found in {}", project_path)));
+}
+
 #[test]
 fn init_does_not_overwrite_existing_setup_py() {
     let test_app = TestApp::new();
@@ -53,11 +522,58 @@ fn lock_workflow() {
     assert!(lock_contents.contains("pytest=="));
     assert!(!lock_contents.contains("pkg-resources=="));
     test_app.assert_run_ok(&["show:deps"]);
+    test_app.assert_run_ok(&["show:deps", "--format", "json"]);
     test_app.assert_run_ok(&["show:outdated"]);
+    test_app.assert_run_ok(&["show:outdated", "--format", "json"]);
     test_app.assert_run_ok(&["run", "--no-exec", "demo"]);
     test_app.assert_run_ok(&["run", "--no-exec", "pytest"]);
 }
 
+#[test]
+fn lock_with_hashes() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock", "--hashes"]);
+    let lock_contents = test_app.read_dev_lock();
+    assert!(lock_contents.contains("--hash=sha256:"));
+    test_app.assert_run_ok(&["install"]);
+}
+
+#[test]
+fn verify_hashes() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["lock", "--hashes"]);
+    test_app.assert_run_ok(&["install"]);
+    test_app.assert_run_ok(&["lock:verify-hashes"]);
+}
+
+#[test]
+fn lock_with_requirements_in() {
+    let test_app = TestApp::new();
+    let requirements_in = test_app.path().join("requirements.in");
+    std::fs::write(requirements_in, "tabulate\n").unwrap();
+    test_app.assert_run_ok(&["lock"]);
+    let lock_contents = test_app.read_dev_lock();
+    assert!(!lock_contents.contains("tabulate=="));
+
+    let pyproject = test_app.path().join("pyproject.toml");
+    std::fs::write(&pyproject, "[tool.dmenv]\nuse_requirements_in = true\n").unwrap();
+    test_app.assert_run_ok(&["lock"]);
+    let lock_contents = test_app.read_dev_lock();
+    assert!(lock_contents.contains("tabulate=="));
+}
+
+#[test]
+fn lock_to_custom_output() {
+    let test_app = TestApp::new();
+    let dev_lock = test_app.read_dev_lock();
+    let output = test_app.path().join("other.lock");
+    let output: String = output.to_string_lossy().into();
+    test_app.assert_run_ok(&["lock", "--output", &output]);
+    let other_lock = std::fs::read_to_string(&output).unwrap();
+    assert!(other_lock.contains("pytest=="));
+    assert_eq!(test_app.read_dev_lock(), dev_lock);
+}
+
 #[test]
 fn production_workflow() {
     let test_app = TestApp::new();
@@ -81,6 +597,34 @@ fn install_workflow_basic() {
     test_app.assert_run_ok(&["run", "--no-exec", "pytest"]);
 }
 
+#[test]
+fn check_workflow() {
+    let test_app = TestApp::new();
+    test_app.assert_run_error(&["check"]);
+    test_app.assert_run_ok(&["install"]);
+    test_app.assert_run_ok(&["check"]);
+    test_app.assert_run_ok(&["bump-in-lock", "attrs", "19.2.0"]);
+    let error = test_app.assert_run_error(&["check"]);
+    assert!(error.contains("1 package(s) out of sync"));
+}
+
+#[test]
+fn force_install_works() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["install"]);
+    test_app.assert_run_ok(&["install", "--force"]);
+    test_app.assert_run_ok(&["run", "--no-exec", "demo"]);
+}
+
+#[test]
+fn install_force_without_lock() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["create"]);
+    test_app.remove_file(dmenv::DEV_LOCK_FILENAME);
+    test_app.assert_run_error(&["install", "--force"]);
+    test_app.assert_run_ok(&["show:bin_path"]);
+}
+
 #[test]
 fn install_workflow_step_by_step() {
     let test_app = TestApp::new();
@@ -90,6 +634,14 @@ fn install_workflow_step_by_step() {
     test_app.assert_run_ok(&["run", "--no-exec", "pytest"]);
 }
 
+#[test]
+fn install_no_develop() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["install", "--no-develop"]);
+    test_app.assert_run_ok(&["run", "--no-exec", "pytest"]);
+    test_app.assert_run_error(&["run", "--no-exec", "demo"]);
+}
+
 #[test]
 fn run_project_script() {
     let test_app = TestApp::new();
@@ -104,12 +656,58 @@ fn install_without_lock() {
     test_app.assert_run_error(&["install"]);
 }
 
+#[test]
+fn recreate_venv() {
+    let test_app = TestApp::new();
+    test_app.assert_run_ok(&["venv:recreate"]);
+    test_app.assert_run_ok(&["run", "--no-exec", "--", "python", "--version"]);
+    test_app.assert_run_ok(&["venv:recreate"]);
+    test_app.assert_run_ok(&["show:bin_path"]);
+}
+
 #[test]
 fn run_without_virtualenv() {
     let test_app = TestApp::new();
     test_app.assert_run_error(&["run", "python"]);
 }
 
+#[test]
+fn run_without_virtualenv_no_venv_check() {
+    let test_app = TestApp::new();
+    #[rustfmt::skip]
+    test_app.assert_run_ok(&[
+        "run", "--no-exec", "--no-venv-check",
+        "--", "python", "--version",
+    ]);
+    test_app.assert_run_error(&["run", "--no-venv-check", "no-such-binary"]);
+}
+
+#[test]
+fn run_passes_flag_like_args_verbatim() {
+    let test_app = TestApp::new();
+    #[rustfmt::skip]
+    test_app.assert_run_ok(&[
+        "run", "--no-exec", "--no-venv-check",
+        "python", "-c", "import sys; assert sys.argv[1:] == ['--project']", "--project",
+    ]);
+}
+
+#[test]
+fn run_in_sub_directory() {
+    let test_app = TestApp::new();
+    std::fs::create_dir(test_app.path().join("tests")).unwrap();
+    #[rustfmt::skip]
+    test_app.assert_run_ok(&[
+        "run", "--no-exec", "--no-venv-check", "--cwd", "tests",
+        "python", "-c", "import os; assert os.path.basename(os.getcwd()) == 'tests'",
+    ]);
+    #[rustfmt::skip]
+    let error = test_app.assert_run_error(&[
+        "run", "--no-exec", "--no-venv-check", "--cwd", "no-such-dir", "python",
+    ]);
+    assert!(error.contains("no such directory"));
+}
+
 #[test]
 fn test_process_scripts() {
     let test_app = TestApp::new();
@@ -146,3 +744,29 @@ fn test_tidy() {
     assert!(!lock_contents.contains("appdirs"));
     assert!(lock_contents.contains("attrs==19.2.0"));
 }
+
+#[test]
+fn lock_upgrade_all() {
+    let test_app = TestApp::new();
+    // Same setup as in `test_tidy()`
+    test_app.override_lock(include_str!("tidy/requirements.lock"));
+    test_app.override_setup_cfg(include_str!("tidy/setup.cfg"));
+
+    test_app.assert_run_ok(&["lock:upgrade-all"]);
+
+    // `appdirs` should be removed from the lock, and `attrs` bumped
+    let lock_contents = test_app.read_dev_lock();
+    assert!(!lock_contents.contains("appdirs"));
+    assert!(lock_contents.contains("attrs=="));
+    assert!(!lock_contents.contains("attrs==19.2.0"));
+}
+
+#[test]
+fn lock_with_broken_setup_py() {
+    let test_app = TestApp::new();
+    let setup_py = test_app.path().join("setup.py");
+    std::fs::write(&setup_py, "from setuptools import setup\nsetup(\n").unwrap();
+    let error = test_app.assert_run_error(&["lock"]);
+    assert!(error.contains("Could not install the project"));
+    assert!(error.contains("SyntaxError"));
+}